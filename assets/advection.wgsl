@@ -1,8 +1,16 @@
 // 流体平流计算着色器
+#import sand::wind::decode_burns
 @group(0) @binding(0) var velocity: texture_2d<f32>;
 @group(0) @binding(1) var source: texture_2d<f32>;
 @group(0) @binding(2) var wind: texture_2d<f32>;
+// 输出格式由 FluidFormats::output_def 选择，没有定义时是原来的 rgba8unorm
+#ifdef OUTPUT_RG16FLOAT
+@group(0) @binding(3) var output: texture_storage_2d<rg16float, write>;
+#else ifdef OUTPUT_RG32FLOAT
+@group(0) @binding(3) var output: texture_storage_2d<rg32float, write>;
+#else
 @group(0) @binding(3) var output: texture_storage_2d<rgba8unorm, write>;
+#endif
 @group(0) @binding(4) var sampler_velocity: sampler;
 @group(0) @binding(5) var sampler_source: sampler;
 @group(0) @binding(6) var sampler_wind: sampler;
@@ -30,7 +38,7 @@ fn advection_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
 
     let coord = vUv - advection_uniforms.dt * textureSampleLevel(velocity, sampler_velocity, vUv,0.0).xy * advection_uniforms.texel_size;
-    let density = textureSampleLevel(wind, sampler_wind, vUv,0.0).w * 1.0;
+    let density = decode_burns(textureSampleLevel(wind, sampler_wind, vUv,0.0)).density;
 
     var result = advection_uniforms.dissipation * (textureSampleLevel(source, sampler_source, coord,0.0) + vec4<f32>(density, 0.0, 0.0, 0.0));
     result.a = 1.0;
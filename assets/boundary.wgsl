@@ -0,0 +1,99 @@
+#define_import_path sand::boundary
+
+// 流体的边界处理，divergence、pressure、gradient_subtract 和 multigrid 共用
+// 与 src/fluid_solver.rs 的 Walls 一一对应，uniform 由 src/boundary.rs 的 BoundaryUniforms 填写
+//
+//   网格四周（FluidConfig::edge_boundary）：
+//     closed   封闭的墙：邻居的法向速度取本格的相反数，压力取本格的值（法向梯度为 0）
+//     open     流出边界：邻居的速度取本格的值，压力为 0
+//     periodic 从对边绕回
+//   固体物质（SpeciesDef::solid，FluidConfig::solid_boundary）：
+//     速度和压力按 closed 的边处理；贴着固体或封闭边的流体格，no-slip 时速度为 0，free-slip 时去掉法向分量
+
+const EDGE_CLOSED: u32 = 0u;
+const EDGE_OPEN: u32 = 1u;
+const EDGE_PERIODIC: u32 = 2u;
+
+const SOLID_NO_SLIP: u32 = 0u;
+const SOLID_FREE_SLIP: u32 = 1u;
+
+struct Boundary {
+    edge: u32,
+    solid: u32,
+    // 第 i 位对应 id 为 i 的物质，见 SpeciesRegistry::solid_mask 和 undamped_mask
+    solid_mask: u32,
+    undamped_mask: u32,
+}
+
+// periodic 时 coord 绕回对边；否则 outside 标出落在域外的邻居，coord 贴在边上
+struct Neighbour {
+    coord: vec2<i32>,
+    outside: bool,
+}
+
+fn neighbour(b: Boundary, size: vec2<i32>, p: vec2<i32>, offset: vec2<i32>) -> Neighbour {
+    let q = p + offset;
+    if (b.edge == EDGE_PERIODIC) {
+        return Neighbour((q % size + size) % size, false);
+    }
+    let inside = all(q >= vec2<i32>(0)) && all(q < size);
+    return Neighbour(clamp(q, vec2<i32>(0), size - 1), !inside);
+}
+
+// cells 与 CellGrid 的细胞纹理相同，r 通道是物质 id
+fn species_in(mask: u32, cells: texture_2d<f32>, p: vec2<i32>) -> bool {
+    let species = u32(textureLoad(cells, p, 0).r * 255.0 + 0.5);
+    return species < 32u && ((mask >> species) & 1u) != 0u;
+}
+
+fn is_solid(b: Boundary, cells: texture_2d<f32>, p: vec2<i32>) -> bool {
+    return species_in(b.solid_mask, cells, p);
+}
+
+// 风在其中不衰减（SpeciesDef::undamped）
+fn is_undamped(b: Boundary, cells: texture_2d<f32>, p: vec2<i32>) -> bool {
+    return species_in(b.undamped_mask, cells, p);
+}
+
+// 邻居格的压力，center 是本格的压力
+fn neighbour_pressure(b: Boundary, pressure: texture_2d<f32>, cells: texture_2d<f32>, p: vec2<i32>, offset: vec2<i32>, center: f32) -> f32 {
+    let n = neighbour(b, vec2<i32>(textureDimensions(pressure)), p, offset);
+    if (n.outside) {
+        return select(center, 0.0, b.edge == EDGE_OPEN);
+    }
+    if (is_solid(b, cells, n.coord)) {
+        return center;
+    }
+    return textureLoad(pressure, n.coord, 0).x;
+}
+
+// 邻居格的速度（散度用），center 是本格的速度
+fn neighbour_velocity(b: Boundary, velocity: texture_2d<f32>, cells: texture_2d<f32>, p: vec2<i32>, offset: vec2<i32>, center: vec2<f32>) -> vec2<f32> {
+    let n = neighbour(b, vec2<i32>(textureDimensions(velocity)), p, offset);
+    if (n.outside) {
+        return select(-center, center, b.edge == EDGE_OPEN);
+    }
+    if (is_solid(b, cells, n.coord)) {
+        return -center;
+    }
+    return textureLoad(velocity, n.coord, 0).xy;
+}
+
+// 邻居是固体或封闭的边
+fn blocked(b: Boundary, cells: texture_2d<f32>, p: vec2<i32>, offset: vec2<i32>) -> bool {
+    let n = neighbour(b, vec2<i32>(textureDimensions(cells)), p, offset);
+    if (n.outside) {
+        return b.edge == EDGE_CLOSED;
+    }
+    return is_solid(b, cells, n.coord);
+}
+
+// 贴着固体或封闭边的流体格的速度
+fn wall_velocity(b: Boundary, cells: texture_2d<f32>, p: vec2<i32>, v: vec2<f32>) -> vec2<f32> {
+    let x_blocked = blocked(b, cells, p, vec2(-1, 0)) || blocked(b, cells, p, vec2(1, 0));
+    let y_blocked = blocked(b, cells, p, vec2(0, -1)) || blocked(b, cells, p, vec2(0, 1));
+    if (b.solid == SOLID_NO_SLIP && (x_blocked || y_blocked)) {
+        return vec2<f32>(0.0);
+    }
+    return vec2<f32>(select(v.x, 0.0, x_blocked), select(v.y, 0.0, y_blocked));
+}
@@ -1,7 +1,15 @@
+#import sand::wind::decode_burns
 
 @group(0) @binding(0) var uTexture: texture_2d<f32>;
 @group(0) @binding(1) var uWind: texture_2d<f32>;
+// 输出格式由 FluidFormats::output_def 选择，没有定义时是原来的 rgba8unorm
+#ifdef OUTPUT_R32FLOAT
+@group(0) @binding(2) var output: texture_storage_2d<r32float, write>;
+#else ifdef OUTPUT_R16FLOAT
+@group(0) @binding(2) var output: texture_storage_2d<r16float, write>;
+#else
 @group(0) @binding(2) var output: texture_storage_2d<rgba8unorm, write>;
+#endif
 @group(0) @binding(3) var sampler_uTexture: sampler;
 @group(0) @binding(4) var sampler_uWind: sampler;
 
@@ -23,7 +31,7 @@ fn clear_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         f32(global_id.y) / f32(textureDimensions(output).y)
     );
 
-    var pressure = textureSampleLevel(uWind, sampler_uWind, vUv,0.0).z;
+    var pressure = decode_burns(textureSampleLevel(uWind, sampler_uWind, vUv,0.0)).pressure;
     pressure*=512.;
     pressure*=pressure;
     var result = clear_uniforms.value * (textureSampleLevel(uTexture, sampler_uTexture, vUv,0.0) + vec4<f32>(pressure, 0.0, 0.0, 0.0));
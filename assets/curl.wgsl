@@ -1,6 +1,13 @@
 // 流体旋度(curl)计算着色器
 @group(0) @binding(0) var velocity: texture_2d<f32>;
+// 输出格式由 FluidFormats::output_def 选择，没有定义时是原来的 rgba8unorm
+#ifdef OUTPUT_R32FLOAT
+@group(0) @binding(1) var output: texture_storage_2d<r32float, write>;
+#else ifdef OUTPUT_R16FLOAT
+@group(0) @binding(1) var output: texture_storage_2d<r16float, write>;
+#else
 @group(0) @binding(1) var output: texture_storage_2d<rgba8unorm, write>;
+#endif
 @group(0) @binding(2) var sampler_linear: sampler;
 
 struct CurlUniforms {
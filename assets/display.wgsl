@@ -1,56 +1,144 @@
-//
-//@group(0) @binding(0) var uTexture: texture_2d<f32>;
-//@group(0) @binding(1) var output: texture_storage_2d<rgba8unorm, write>;
-//@group(0) @binding(2) var sampler_linear: sampler;
-//
-//
-//@compute @workgroup_size(8, 8)
-//fn display_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
-//    if (global_id.x >= u32(textureDimensions(output).x) ||
-//        global_id.y >= u32(textureDimensions(output).y)) {
-//        return;
-//    }
-//
-//    let vUv = vec2<f32>(
-//        f32(global_id.x) / f32(textureDimensions(output).x),
-//        f32(global_id.y) / f32(textureDimensions(output).y)
-//    );
-////// 翻转Y并交换XY
-//    var color = textureSampleLevel(uTexture, sampler_linear, vec2(1.0 - vUv.y,vUv.x),0.0).rgb * 0.1;
-////    // 颜色处理流程
-//    color *= 0.5;
-//     // 钳制分量式
-////      color = vec4<f32>(min(color.rgb, 0.9),1.0);
-//    color =  min(color.rgb, vec3(0.9));
-//     // 颜色反相
-//    color = vec3(1.0) - color;
-//     // 暖色调滤镜
-//    color *= vec3(0.95, 0.9, 0.9);
-//    textureStore(output, vec2<i32>(global_id.xy), vec4<f32>(color,1.0));
-//}
-//
-
 #import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
-@group(0) @binding(0) var uTexture: texture_2d<f32>;
-@group(0) @binding(1) var sampler_linear: sampler;
+#import sand::grid::grid_uv_to_texel
+
+// 调试显示（见 src/display.rs）：读取画好细胞的主纹理，把选中的流体场按色带画上去
+// 全屏三角形的 uv 原点在左上角，就是网格 uv（见 grid.wgsl），流体场按纹素读取
+
+// 与 DisplayMode 一致
+const MODE_CELLS: u32 = 0u;
+const MODE_DENSITY: u32 = 1u;
+const MODE_VELOCITY: u32 = 2u;
+const MODE_VELOCITY_ARROWS: u32 = 3u;
+const MODE_PRESSURE: u32 = 4u;
+const MODE_CURL: u32 = 5u;
+const MODE_DIVERGENCE: u32 = 6u;
+const MODE_BURNS: u32 = 7u;
+
+// 箭头之间的间距，单位是细胞
+const ARROW_SPACING: f32 = 16.0;
+
+const PI: f32 = 3.141592653589793;
+
+struct DisplayParams {
+    mode: u32,
+    overlay: u32,
+    // 色带两端对应的值
+    range: f32,
+    padding: f32,
+}
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var screen_sampler: sampler;
+@group(0) @binding(2) var field: texture_2d<f32>;
+@group(0) @binding(3) var<uniform> params: DisplayParams;
+
+// 颜色和不透明度，叠加时按不透明度与细胞混合
+struct Shade {
+    color: vec3<f32>,
+    alpha: f32,
+}
+
+fn load_field(uv: vec2<f32>) -> vec4<f32> {
+    return textureLoad(field, grid_uv_to_texel(uv, textureDimensions(field)), 0);
+}
+
+// 发散色带：-range 蓝，0 白，+range 红
+fn signed_ramp(v: f32) -> Shade {
+    let t = clamp(v / params.range, -1.0, 1.0);
+    let white = vec3<f32>(0.95, 0.95, 0.95);
+    let color = select(
+        mix(white, vec3<f32>(0.23, 0.30, 0.75), -t),
+        mix(white, vec3<f32>(0.71, 0.02, 0.15), t),
+        t >= 0.0,
+    );
+    return Shade(color, abs(t));
+}
+
+// 顺序色带：0 黑，经红、黄到 range 白
+fn heat_ramp(v: f32) -> Shade {
+    let t = clamp(v / params.range, 0.0, 1.0);
+    let color = clamp(vec3<f32>(3.0 * t, 3.0 * t - 1.0, 3.0 * t - 2.0), vec3<f32>(0.0), vec3<f32>(1.0));
+    return Shade(color, t);
+}
+
+fn hue(h: f32) -> vec3<f32> {
+    let k = abs(fract(h + vec3<f32>(0.0, 2.0 / 3.0, 1.0 / 3.0)) * 6.0 - 3.0) - 1.0;
+    return clamp(k, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// 方向决定色相（向右是青色，向左是红色），大小决定亮度
+fn direction_color(v: vec2<f32>) -> vec3<f32> {
+    return hue(atan2(v.y, v.x) / (2.0 * PI) + 0.5);
+}
+
+fn velocity_ramp(v: vec2<f32>) -> Shade {
+    let t = clamp(length(v) / params.range, 0.0, 1.0);
+    return Shade(direction_color(v) * t, t);
+}
+
+fn segment_distance(p: vec2<f32>, a: vec2<f32>, b: vec2<f32>) -> f32 {
+    let ab = b - a;
+    let h = clamp(dot(p - a, ab) / max(dot(ab, ab), 1e-6), 0.0, 1.0);
+    return length(p - a - ab * h);
+}
+
+// 每 ARROW_SPACING 个细胞一个箭头，取格子中心的速度，长度按量程缩放，最长占满格子
+fn velocity_arrows(uv: vec2<f32>) -> Shade {
+    let size = vec2<f32>(textureDimensions(field));
+    let p = uv * size;
+    let center = (floor(p / ARROW_SPACING) + 0.5) * ARROW_SPACING;
+    let v = load_field(center / size).xy;
+    let t = clamp(length(v) / params.range, 0.0, 1.0);
+    if (t < 0.01) {
+        return Shade(vec3<f32>(0.0), 0.0);
+    }
+    let dir = normalize(v);
+    let half_length = t * ARROW_SPACING * 0.45;
+    let tail = center - dir * half_length;
+    let tip = center + dir * half_length;
+    // 箭头两翼与箭杆成 150 度
+    let head = half_length * 0.5;
+    let wing = vec2<f32>(-dir.y, dir.x);
+    let left = tip + (-dir * 0.866 + wing * 0.5) * head;
+    let right = tip + (-dir * 0.866 - wing * 0.5) * head;
+    let d = min(segment_distance(p, tail, tip), min(segment_distance(p, tip, left), segment_distance(p, tip, right)));
+    let alpha = 1.0 - smoothstep(0.5, 1.5, d);
+    return Shade(direction_color(v), alpha);
+}
+
+fn field_shade(uv: vec2<f32>) -> Shade {
+    let mode = params.mode;
+    if (mode == MODE_DENSITY) {
+        return heat_ramp(length(load_field(uv).rgb));
+    }
+    if (mode == MODE_VELOCITY) {
+        return velocity_ramp(load_field(uv).xy);
+    }
+    if (mode == MODE_VELOCITY_ARROWS) {
+        return velocity_arrows(uv);
+    }
+    if (mode == MODE_PRESSURE || mode == MODE_CURL || mode == MODE_DIVERGENCE) {
+        return signed_ramp(load_field(uv).x);
+    }
+    if (mode == MODE_BURNS) {
+        // burns 的四个通道都是非负的强度（见 wind.wgsl），取最大的一个
+        let b = load_field(uv);
+        return heat_ramp(max(max(b.x, b.y), max(b.z, b.w)));
+    }
+    return Shade(vec3<f32>(0.0), 0.0);
+}
+
 @fragment
-fn fragment(input: FullscreenVertexOutput) ->@location(0) vec4<f32>  {
-    // 应用90度旋转（交换并反转坐标）
-    let uv = vec2<f32>(1.0 - input.uv.y, input.uv.x);
-
-    // 纹理采样
-    var color = textureSample(uTexture, sampler_linear, uv).rgb * 0.1;
-
-    // 颜色处理
-    color *= 0.5;
-    color = min(color.rgb, vec3(0.9));
-    color = vec3<f32>(1.0) - color;
-    color *= vec3<f32>(0.95, 0.9, 0.9);
-
-    // 输出颜色
-//    return vec4<f32>(color,1.0);
-// 显示节点输出纯绿色
-//如果 DisplayNode 先执行：屏幕会是红色（因为后执行的 CellMaterial
-//覆盖了 DisplayNode 的绿色输出）
-    return vec4<f32>(0.0, 1.0, 0.0, 1.0);
-}
\ No newline at end of file
+fn fragment(input: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let uv = input.uv;
+    let cells = textureSample(screen_texture, screen_sampler, uv);
+    if (params.mode == MODE_CELLS) {
+        return cells;
+    }
+    let shade = field_shade(uv);
+    if (params.overlay == 0u) {
+        // 箭头画在黑底上
+        return vec4<f32>(shade.color * select(1.0, shade.alpha, params.mode == MODE_VELOCITY_ARROWS), 1.0);
+    }
+    return vec4<f32>(mix(cells.rgb, shade.color, shade.alpha), cells.a);
+}
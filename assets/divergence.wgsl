@@ -1,6 +1,15 @@
 // 散度计算着色器
+#import sand::boundary::{Boundary, neighbour_velocity}
+
 @group(0) @binding(0) var velocity: texture_2d<f32>;
+// 输出格式由 FluidFormats::output_def 选择，没有定义时是原来的 rgba8unorm
+#ifdef OUTPUT_R32FLOAT
+@group(0) @binding(1) var output: texture_storage_2d<r32float, write>;
+#else ifdef OUTPUT_R16FLOAT
+@group(0) @binding(1) var output: texture_storage_2d<r16float, write>;
+#else
 @group(0) @binding(1) var output: texture_storage_2d<rgba8unorm, write>;
+#endif
 @group(0) @binding(2) var sampler_linear: sampler;
 
 struct DivergenceUniforms {
@@ -8,11 +17,9 @@ struct DivergenceUniforms {
 };
 
 @group(0) @binding(3) var<uniform> divergence_uniforms: DivergenceUniforms;
+@group(0) @binding(4) var cells: texture_2d<f32>;
+@group(0) @binding(5) var<uniform> boundary: Boundary;
 
-// 计算相邻像素坐标
-fn get_neighbor_uv(uv: vec2<f32>, direction: vec2<i32>) -> vec2<f32> {
-    return uv + vec2<f32>(direction) * divergence_uniforms.texel_size;
-}
 //  divergence
 @compute @workgroup_size(8, 8)
 fn divergence_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
@@ -21,18 +28,13 @@ fn divergence_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         return;
     }
 
-    let uv = vec2<f32>(global_id.xy) / vec2<f32>(size);
-
-    // 采样相邻像素速度
-    let vL = get_neighbor_uv(uv, vec2(-1, 0));  // 左
-    let vR = get_neighbor_uv(uv, vec2(1, 0));   // 右
-    let vT = get_neighbor_uv(uv, vec2(0, 1));   // 上
-    let vB = get_neighbor_uv(uv, vec2(0, -1));  // 下
-
-    let vel_L = textureSampleLevel(velocity, sampler_linear, vL,0.).x;
-    let vel_R = textureSampleLevel(velocity, sampler_linear, vR,0.).x;
-    let vel_T = textureSampleLevel(velocity, sampler_linear, vT,0.).y;
-    let vel_B = textureSampleLevel(velocity, sampler_linear, vB,0.).y;
+    // 相邻格的速度按边界方式取值（见 boundary.wgsl），与 FluidSolver::compute_divergence 相同
+    let p = vec2<i32>(global_id.xy);
+    let center = textureLoad(velocity, p, 0).xy;
+    let vel_L = neighbour_velocity(boundary, velocity, cells, p, vec2(-1, 0), center).x;  // 左
+    let vel_R = neighbour_velocity(boundary, velocity, cells, p, vec2(1, 0), center).x;   // 右
+    let vel_T = neighbour_velocity(boundary, velocity, cells, p, vec2(0, 1), center).y;   // 上
+    let vel_B = neighbour_velocity(boundary, velocity, cells, p, vec2(0, -1), center).y;  // 下
 
     // 计算散度
     let divergence = 0.5 * (vel_R - vel_L + vel_T - vel_B);
@@ -40,4 +42,4 @@ fn divergence_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     // 输出结果（归一化到0-1范围）
     let color = vec4<f32>(divergence, 0.0, 0.0, 1.0);
     textureStore(output, vec2<i32>(global_id.xy), color);
-}
\ No newline at end of file
+}
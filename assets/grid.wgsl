@@ -0,0 +1,16 @@
+#define_import_path sand::grid
+
+// 与 src/universe/coords.rs 相同的坐标约定：
+// 纹素 (x, y) 就是细胞 (x, y)，y 向下，纹理第 0 行是网格顶部
+// 网格 uv 的原点在左上角，窗口坐标和全屏三角形的 uv 本来就是这样，可以直接使用
+
+// 裁剪空间坐标（y 向上，范围 [-1, 1]）到网格 uv
+fn clip_to_grid_uv(clip: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(clip.x + 1.0, 1.0 - clip.y) * 0.5;
+}
+
+// 网格 uv 落在哪个纹素上，超出网格的夹到边上
+fn grid_uv_to_texel(uv: vec2<f32>, size: vec2<u32>) -> vec2<i32> {
+    let texel = vec2<i32>(floor(uv * vec2<f32>(size)));
+    return clamp(texel, vec2<i32>(0), vec2<i32>(size) - 1);
+}
@@ -0,0 +1,136 @@
+// 多重网格压力求解（V 循环），与 FluidSolver::solve_pressure_multigrid 一一对应
+// 金字塔的压力和右端项都是 r32float，每层两张压力纹理来回读写，读写目标由 multigrid.rs 的 encode_multigrid 安排
+// 算子是 5 点拉普拉斯 L + R + T + B - 4C，邻居按 FluidConfig 的边界方式取（见 boundary.wgsl），与 pressure.wgsl 相同
+// 固体只在第 0 层处理，粗层只有网格四周的边界，与 FluidSolver 的 Walls::coarse 一致
+
+#import sand::boundary::{Boundary, neighbour_pressure}
+
+// 与 fluid_solver.rs 中的 MULTIGRID_RELAXATION 一致
+const RELAXATION: f32 = 0.8;
+
+fn load_clamped(tex: texture_2d<f32>, p: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(tex));
+    return textureLoad(tex, clamp(p, vec2<i32>(0), size - 1), 0).x;
+}
+
+// cells 与第 0 层同样大小，粗层不看固体
+fn level_boundary(b: Boundary, cells: texture_2d<f32>, tex: texture_2d<f32>) -> Boundary {
+    var level = b;
+    if (any(textureDimensions(cells) != textureDimensions(tex))) {
+        level.solid_mask = 0u;
+    }
+    return level;
+}
+
+fn laplacian(b: Boundary, tex: texture_2d<f32>, cells: texture_2d<f32>, p: vec2<i32>) -> f32 {
+    let level = level_boundary(b, cells, tex);
+    let C = load_clamped(tex, p);
+    let L = neighbour_pressure(level, tex, cells, p, vec2(-1, 0), C);
+    let R = neighbour_pressure(level, tex, cells, p, vec2(1, 0), C);
+    let T = neighbour_pressure(level, tex, cells, p, vec2(0, 1), C);
+    let B = neighbour_pressure(level, tex, cells, p, vec2(0, -1), C);
+    return L + R + T + B - 4.0 * C;
+}
+
+// load：clear 之后的 pressure.1 作为初值，divergence 作为第 0 层的右端项
+@group(0) @binding(0) var load_pressure: texture_2d<f32>;
+@group(0) @binding(1) var load_divergence: texture_2d<f32>;
+@group(0) @binding(2) var load_p_out: texture_storage_2d<r32float, write>;
+@group(0) @binding(3) var load_rhs_out: texture_storage_2d<r32float, write>;
+
+@compute @workgroup_size(8, 8)
+fn load_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = textureDimensions(load_pressure);
+    if (global_id.x >= size.x || global_id.y >= size.y) {
+        return;
+    }
+    let p = vec2<i32>(global_id.xy);
+    textureStore(load_p_out, p, vec4<f32>(textureLoad(load_pressure, p, 0).x, 0.0, 0.0, 1.0));
+    textureStore(load_rhs_out, p, vec4<f32>(textureLoad(load_divergence, p, 0).x, 0.0, 0.0, 1.0));
+}
+
+// smooth：一次带松弛的 Jacobi，p = (1 - ω) p + ω (L + R + T + B - rhs) / 4
+@group(0) @binding(0) var smooth_p: texture_2d<f32>;
+@group(0) @binding(1) var smooth_rhs: texture_2d<f32>;
+@group(0) @binding(2) var smooth_p_out: texture_storage_2d<r32float, write>;
+@group(0) @binding(3) var smooth_cells: texture_2d<f32>;
+@group(0) @binding(4) var<uniform> smooth_boundary: Boundary;
+
+@compute @workgroup_size(8, 8)
+fn smooth_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = textureDimensions(smooth_p);
+    if (global_id.x >= size.x || global_id.y >= size.y) {
+        return;
+    }
+    let p = vec2<i32>(global_id.xy);
+    let C = load_clamped(smooth_p, p);
+    let jacobi = (laplacian(smooth_boundary, smooth_p, smooth_cells, p) + 4.0 * C - load_clamped(smooth_rhs, p)) * 0.25;
+    textureStore(smooth_p_out, p, vec4<f32>(C + RELAXATION * (jacobi - C), 0.0, 0.0, 1.0));
+}
+
+// restrict：粗一层的右端项是 2x2 子格残差的平均乘 4（格距加倍），粗一层的压力从 0 开始
+@group(0) @binding(0) var restrict_p: texture_2d<f32>;
+@group(0) @binding(1) var restrict_rhs: texture_2d<f32>;
+@group(0) @binding(2) var restrict_rhs_out: texture_storage_2d<r32float, write>;
+@group(0) @binding(3) var restrict_p_out: texture_storage_2d<r32float, write>;
+@group(0) @binding(4) var restrict_cells: texture_2d<f32>;
+@group(0) @binding(5) var<uniform> restrict_boundary: Boundary;
+
+@compute @workgroup_size(8, 8)
+fn restrict_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let coarse_size = textureDimensions(restrict_rhs_out);
+    if (global_id.x >= coarse_size.x || global_id.y >= coarse_size.y) {
+        return;
+    }
+    let fine_size = vec2<i32>(textureDimensions(restrict_p));
+    let coarse = vec2<i32>(global_id.xy);
+    var sum = 0.0;
+    var count = 0.0;
+    for (var dy = 0; dy < 2; dy++) {
+        for (var dx = 0; dx < 2; dx++) {
+            let fine = coarse * 2 + vec2(dx, dy);
+            if (fine.x < fine_size.x && fine.y < fine_size.y) {
+                sum += load_clamped(restrict_rhs, fine) - laplacian(restrict_boundary, restrict_p, restrict_cells, fine);
+                count += 1.0;
+            }
+        }
+    }
+    textureStore(restrict_rhs_out, coarse, vec4<f32>(4.0 * sum / count, 0.0, 0.0, 1.0));
+    textureStore(restrict_p_out, coarse, vec4<f32>(0.0, 0.0, 0.0, 1.0));
+}
+
+// prolong：把粗一层的修正加到对应的 2x2 子格上
+@group(0) @binding(0) var prolong_p: texture_2d<f32>;
+@group(0) @binding(1) var prolong_correction: texture_2d<f32>;
+@group(0) @binding(2) var prolong_p_out: texture_storage_2d<r32float, write>;
+
+@compute @workgroup_size(8, 8)
+fn prolong_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = textureDimensions(prolong_p);
+    if (global_id.x >= size.x || global_id.y >= size.y) {
+        return;
+    }
+    let p = vec2<i32>(global_id.xy);
+    let e = load_clamped(prolong_correction, p / 2);
+    textureStore(prolong_p_out, p, vec4<f32>(load_clamped(prolong_p, p) + e, 0.0, 0.0, 1.0));
+}
+
+// store：第 0 层的结果写回 pressure.0，后续的 pass 照常读取，输出格式与 pressure.wgsl 相同
+@group(0) @binding(0) var store_p: texture_2d<f32>;
+#ifdef OUTPUT_R32FLOAT
+@group(0) @binding(1) var store_output: texture_storage_2d<r32float, write>;
+#else ifdef OUTPUT_R16FLOAT
+@group(0) @binding(1) var store_output: texture_storage_2d<r16float, write>;
+#else
+@group(0) @binding(1) var store_output: texture_storage_2d<rgba8unorm, write>;
+#endif
+
+@compute @workgroup_size(8, 8)
+fn store_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = textureDimensions(store_p);
+    if (global_id.x >= size.x || global_id.y >= size.y) {
+        return;
+    }
+    let p = vec2<i32>(global_id.xy);
+    textureStore(store_output, p, vec4<f32>(textureLoad(store_p, p, 0).x, 0.0, 0.0, 1.0));
+}
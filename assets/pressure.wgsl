@@ -1,7 +1,16 @@
-// 压力求解着色器（Gauss-Seidel迭代法）
+// 压力求解着色器（Jacobi 迭代，每次 dispatch 一次迭代，读写目标由 PressureComputeNode 交替）
+#import sand::boundary::{Boundary, neighbour_pressure}
+
 @group(0) @binding(0) var pressure: texture_2d<f32>;
 @group(0) @binding(1) var divergence: texture_2d<f32>;
+// 输出格式由 FluidFormats::output_def 选择，没有定义时是原来的 rgba8unorm
+#ifdef OUTPUT_R32FLOAT
+@group(0) @binding(2) var output: texture_storage_2d<r32float, write>;
+#else ifdef OUTPUT_R16FLOAT
+@group(0) @binding(2) var output: texture_storage_2d<r16float, write>;
+#else
 @group(0) @binding(2) var output: texture_storage_2d<rgba8unorm, write>;
+#endif
 @group(0) @binding(3) var sampler_pressure: sampler;
 @group(0) @binding(4) var sampler_divergence: sampler;
 
@@ -12,15 +21,18 @@ struct PressureUniforms {
 };
 
 @group(0) @binding(5) var<uniform> pressure_uniforms: PressureUniforms;
+// residual_main 每个工作组的 |残差| 之和，下标是工作组在网格里按行排列的序号，pressure_main 不使用
+@group(0) @binding(6) var<storage, read_write> residual_partials: array<f32>;
+@group(0) @binding(7) var cells: texture_2d<f32>;
+@group(0) @binding(8) var<uniform> boundary: Boundary;
 
-// 边界处理函数
-fn boundary(uv: vec2<f32>) -> vec2<f32> {
-    return clamp(uv, vec2<f32>(0.0),vec2<f32>( 1.0));
-}
-
-// 计算相邻像素坐标
-fn get_neighbor_uv(uv: vec2<f32>, direction: vec2<i32>) -> vec2<f32> {
-    return boundary(uv + vec2<f32>(direction) * pressure_uniforms.texel_size);
+// 上下左右四个邻居的压力之和，按边界方式取值（见 boundary.wgsl），与 multigrid.wgsl 的算子相同
+fn neighbour_sum(p: vec2<i32>, center: f32) -> f32 {
+    let L = neighbour_pressure(boundary, pressure, cells, p, vec2(-1, 0), center);
+    let R = neighbour_pressure(boundary, pressure, cells, p, vec2(1, 0), center);
+    let T = neighbour_pressure(boundary, pressure, cells, p, vec2(0, 1), center);
+    let B = neighbour_pressure(boundary, pressure, cells, p, vec2(0, -1), center);
+    return L + R + T + B;
 }
 //  pressure
 @compute @workgroup_size(8, 8)
@@ -30,13 +42,9 @@ fn pressure_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         return;
     }
 
-    let uv = vec2<f32>(global_id.xy) / vec2<f32>(size);
-
-    // 采样相邻压力值
-    let L = textureSampleLevel(pressure, sampler_pressure, get_neighbor_uv(uv, vec2(-1, 0)),0.).x;
-    let R = textureSampleLevel(pressure, sampler_pressure, get_neighbor_uv(uv, vec2(1, 0)),0.).x;
-    let T = textureSampleLevel(pressure, sampler_pressure, get_neighbor_uv(uv, vec2(0, 1)),0.).x;
-    let B = textureSampleLevel(pressure, sampler_pressure, get_neighbor_uv(uv, vec2(0, -1)),0.).x;
+    // 取像素中心，线性采样正好落在纹素上
+    let uv = (vec2<f32>(global_id.xy) + 0.5) / vec2<f32>(size);
+    let p = vec2<i32>(global_id.xy);
 
     // 当前压力值和散度
     let C = textureSampleLevel(pressure, sampler_pressure, uv,0.).x;
@@ -45,8 +53,46 @@ fn pressure_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     // 压力方程求解（Gauss-Seidel迭代）
     // p = (neighbors_sum - divergence) * 0.25
     // 或更通用形式：p = (neighbors_sum + alpha * div) * reciprocal_beta
-    let pressure = (L + R + B + T - div) * 0.25;
+    let pressure = (neighbour_sum(p, C) - div) * 0.25;
 
 //    textureStore(output, vec2<i32>(global_id.xy), vec4<f32>(0.0, 0.0, 0.0, 1.0));
     textureStore(output, vec2<i32>(global_id.xy), vec4<f32>(pressure, 0.0, 0.0, 1.0));
-}
\ No newline at end of file
+}
+
+// residual_main 的工作组内归约，8x8 个线程各占一格
+var<workgroup> partial: array<f32, 64>;
+
+// 统计 pressure 的残差：泊松方程 L + R + T + B - 4C = div 两边之差的绝对值，邻居和散度的取法与 pressure_main 相同
+// 工作组内先折半求和，再由第一个线程写出本组的部分和，CPU 读回后把各组加起来
+@compute @workgroup_size(8, 8)
+fn residual_main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let size = textureDimensions(pressure);
+    // 越界的线程记 0，不能提前返回：下面的 workgroupBarrier 要求组内所有线程都到达
+    var residual = 0.0;
+    if (global_id.x < size.x && global_id.y < size.y) {
+        let uv = (vec2<f32>(global_id.xy) + 0.5) / vec2<f32>(size);
+        let p = vec2<i32>(global_id.xy);
+
+        let C = textureLoad(pressure, p, 0).x;
+        let div = textureSampleLevel(divergence, sampler_divergence, uv,0.).x;
+
+        residual = abs(neighbour_sum(p, C) - 4.0 * C - div);
+    }
+
+    partial[local_index] = residual;
+    workgroupBarrier();
+    for (var stride = 32u; stride > 0u; stride = stride / 2u) {
+        if (local_index < stride) {
+            partial[local_index] += partial[local_index + stride];
+        }
+        workgroupBarrier();
+    }
+    if (local_index == 0u) {
+        residual_partials[group_id.y * groups.x + group_id.x] = partial[0];
+    }
+}
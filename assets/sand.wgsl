@@ -1,4 +1,6 @@
 
+#import sand::grid::{clip_to_grid_uv, grid_uv_to_texel}
+
 // 顶点着色器 vertex.wgsl
 struct VertexInput {
     @location(0) position: vec2f,
@@ -22,6 +24,7 @@ fn vertex(input: VertexInput) -> VertexOutput {
 
 
 // 片段着色器 fragment.wgsl
+// 配色逻辑在 src/universe/render.rs 有一份CPU实现（无窗口模式出图用），改动时两边一起改
 struct ShaderParams {
     time: f32,
     dpi: f32,
@@ -34,6 +37,21 @@ struct ShaderParams {
 @group(2) @binding(1) var data_tex: texture_2d<f32>;
 @group(2) @binding(2) var tex_sampler: sampler;
 
+// 物质调色板，由 SpeciesRegistry::palette 生成，每种物质占四项：
+//   [0] 色相系数 (常数, ra, rb, time)
+//   [1] 饱和度系数 (常数, ra, rb, time)
+//   [2] 亮度系数 (常数, ra, rb, noise3)
+//   [3] x = alpha, y = 着色方式（ColourStyle）
+struct SpeciesPalette {
+    entries: array<vec4f, 128>,
+};
+@group(2) @binding(3) var<uniform> palette: SpeciesPalette;
+
+const STYLE_BACKGROUND: i32 = 1;
+const STYLE_SHIMMER: i32 = 2;
+const STYLE_GLOW: i32 = 3;
+const STYLE_WRAPPED: i32 = 4;
+
 const PI: f32 = 3.141592653589793;
 
 fn hsv2rgb(hsv: vec3<f32>) -> vec3<f32> {
@@ -80,148 +98,59 @@ fn snoise3(pos: vec3f) -> f32 {
 }
 
 @fragment
-fn fragment(@location(0) uv: vec2<f32>) -> @location(0) vec4f {// 修改text_coord计算（原代码存在坐标翻转问题）
-
-
-   let text_coord = vec2f(
-      (uv.x + 1.0) * 0.5,
-             (uv.y + 1.0) * 0.5 // Y轴翻转
-   );
-    // 精确采样
-    let tex_size = textureDimensions(data_tex);
-    let coord = vec2<i32>(text_coord * vec2<f32>(tex_size));
+fn fragment(@location(0) uv: vec2<f32>) -> @location(0) vec4f {
+    // uv 是裁剪空间坐标（y 向上），网格的第 0 行画在屏幕顶部
+    let coord = grid_uv_to_texel(clip_to_grid_uv(uv), textureDimensions(data_tex));
     let data = textureLoad(data_tex, coord, 0);
 
     // 类型转换
     let type_id = i32(round(data.r * 255.0));
 
-    var hue: f32 = 0.0;
-    var saturation: f32 = 0.6;
-    var lightness: f32 = 0.3 + data.g * 0.5;
-    var a: f32 = 0.6;
-
     // 计算公共噪声值
     let pixel_pos = floor(uv * params.resolution / params.dpi);
     let noise3 = snoise3(vec3f(pixel_pos, params.time * 0.05));
     let noise2 = snoise2(pixel_pos);
 
-    switch(type_id) {
-        case 0: { // Empty
-            hue = 0.0;
-            saturation = 0.1;
-            lightness = 0.1;
-            a = 0.1;
+    // 超出调色板范围的 id 按未注册处理（白色）
+    if (type_id < 0 || type_id >= 32) {
+        return vec4f(hsv2rgb(vec3f(0.0, 1.0, 1.0)), 1.0);
+    }
+    let base = type_id * 4;
+    let h = palette.entries[base];
+    let s = palette.entries[base + 1];
+    let l = palette.entries[base + 2];
+    let extra = palette.entries[base + 3];
+    let style = i32(round(extra.y));
+
+    var hue: f32 = h.x + h.y * data.g + h.z * data.b + h.w * params.time;
+    var saturation: f32 = s.x + s.y * data.g + s.z * data.b + s.w * params.time;
+    var lightness: f32 = l.x + l.y * data.g + l.z * data.b + l.w * noise3;
+    var a: f32 = extra.x;
+
+    switch(style) {
+        case STYLE_BACKGROUND: {
             if (params.is_snapshot != 0u) {
                 saturation = 0.05;
                 lightness = 1.01;
                 a = 1.0;
             }
         }
-        case 1: { // Wall
-            hue = 0.1;
-            saturation = 0.1;
-            lightness = 0.4;
-        }
-        case 2: { // Sand
-            hue = 0.1;
-            saturation = 0.5;
-            lightness += 0.3;
-        }
-        case 3: { // Water
-            hue = 0.6;
-            lightness = 0.7 + data.g * 0.25 + noise3 * 0.1;
+        case STYLE_SHIMMER: {
             let polarity = i32(data.g * 255.0) % 2;
             if (polarity == 0) {
                 lightness += 0.01;
             }
         }
-        case 4: { // Gas
-            hue = 0.0;
-            lightness += 0.4;
-            saturation = 0.2 + data.b * 1.5;
-        }
-        case 5: { // Cloner
-            hue = 0.9;
-            saturation = 0.3;
-        }
-        case 6: { // Fire
-            hue =  0.0;
-            saturation = 0.9;
-            lightness = 0.7 + data.g * 0.3;
+        case STYLE_GLOW: {
             if (params.is_snapshot != 0u) {
                 lightness -= 0.2;
             }
             return vec4f(hsv2rgb(vec3f(hue, saturation, lightness)), 1.0);
         }
-        case 7: { // Wood
-            hue = data.g * 0.1;
-            saturation = 0.3;
-            lightness = 0.3 + data.g * 0.3;
-        }
-        case 8: { // Lava
-            hue = data.g * 0.1;
-            lightness = 0.7 + data.g * 0.25 + noise3 * 0.1;
-        }
-        case 9: { // Ice
-            hue = 0.6;
-            saturation = 0.4;
-            lightness = 0.7 + data.g * 0.5;
-        }
-        case 10: { // Sink
-            hue = 0.9;
-            saturation = 0.4;
-            lightness = 1.0;
-        }
-        case 11: { // Plant
-            hue = 0.4;
-            saturation = 0.4;
-        }
-        case 12: { // Acid
-            hue = 0.18;
-            saturation = 0.9;
-            lightness = 0.8 + data.g * 0.2 + noise3 * 0.05;
-        }
-        case 13: { // Stone
-            hue = -0.4 + data.g * 0.5;
-            saturation = 0.1;
-        }
-        case 14: { // Dust
-            hue = data.g * 2.0 + params.time * 0.0008;
-            saturation = 0.4;
-            lightness = 0.8;
-        }
-        case 15: { // Mite
-            hue = 0.8;
-            saturation = 0.9;
-            lightness = 0.8;
-        }
-        case 16: { // Oil
-            hue = data.g * 5.0 + params.time * 0.008;
-            saturation = 0.2;
-            lightness = 0.3;
-        }
-        case 17: { // Rocket
-            hue = 0.0;
-            saturation = 0.4 + data.b;
-            lightness = 0.9;
-        }
-        case 18: { // Fungus
-            hue = data.g * 0.15 - 0.1;
-            saturation = data.g * 0.8 - 0.05;
-            lightness = 1.5 - data.g * 0.2;
-        }
-        case 19: { // Seed
-            hue = fract(fract(data.b * 2.0) * 0.5) - 0.3;
-            saturation = 0.7 * (data.g + 0.4) + data.b * 0.2;
-            lightness = 0.9 * (data.g + 0.9);
-        }
-        default: {
-            // 处理未知类型
-            hue = 0.0;
-            saturation = 1.0;
-            lightness = 1.0;
-            a = 1.0;
+        case STYLE_WRAPPED: {
+            hue = h.x + fract(fract(h.y * data.g + h.z * data.b + h.w * params.time) * 0.5);
         }
+        default: {}
     }
 
     // 动态噪声效果
@@ -1,7 +1,14 @@
 // 涡度应用着色器（添加涡度约束）
 @group(0) @binding(0) var velocity: texture_2d<f32>;
 @group(0) @binding(1) var curl: texture_2d<f32>;
+// 输出格式由 FluidFormats::output_def 选择，没有定义时是原来的 rgba8unorm
+#ifdef OUTPUT_RG16FLOAT
+@group(0) @binding(2) var output: texture_storage_2d<rg16float, write>;
+#else ifdef OUTPUT_RG32FLOAT
+@group(0) @binding(2) var output: texture_storage_2d<rg32float, write>;
+#else
 @group(0) @binding(2) var output: texture_storage_2d<rgba8unorm, write>;
+#endif
 @group(0) @binding(3) var sampler_velocity: sampler;
 @group(0) @binding(4) var sampler_curl: sampler;
 
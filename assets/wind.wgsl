@@ -0,0 +1,34 @@
+#define_import_path sand::wind
+
+// CellGrid 的 Wind 字节（burns、winds，经 Rgba8Unorm 纹理读写）与有符号浮点流体场之间的编解码
+// 与 src/fluid_solver.rs 中的 decode_burns、encode_wind、decode_wind 一一对应
+//
+//   burns：物质通过 set_fluid 写入的发射强度，四个通道都是非负的，按 unorm 原样当作 [0, 1] 的强度
+//   winds：velocity_out 的输出，速度限幅到 [WIND_MIN, WIND_MAX] 后除以 WIND_SCALE，按字节加在 WIND_ZERO 上，
+//          字节 WIND_ZERO 是静止，与 blow_wind 的零点相同（src/universe.rs 的 WIND_ZERO）；压力只缩放不偏移
+//          解码先取回整数字节再减 WIND_ZERO，静止解码出来正好是 0
+
+const WIND_MIN: f32 = -250.0;
+const WIND_MAX: f32 = 250.0;
+const WIND_SCALE: f32 = 500.0;
+const WIND_ZERO: f32 = 126.0;
+
+struct Burns {
+    velocity: vec2<f32>,
+    pressure: f32,
+    density: f32,
+}
+
+fn decode_burns(texel: vec4<f32>) -> Burns {
+    return Burns(texel.xy, texel.z, texel.w);
+}
+
+// 速度和压力编码成 Wind 的 (dx, dy, pressure)，density 通道由 readback 从密度纹理单独取
+fn encode_wind(velocity: vec2<f32>, pressure: f32) -> vec4<f32> {
+    let vp = clamp(vec3<f32>(velocity, pressure), vec3<f32>(WIND_MIN), vec3<f32>(WIND_MAX)) / WIND_SCALE;
+    return vec4<f32>((vp.xy * 255.0 + WIND_ZERO) / 255.0, vp.z, 0.0);
+}
+
+fn decode_wind_velocity(texel: vec4<f32>) -> vec2<f32> {
+    return (round(texel.xy * 255.0) - WIND_ZERO) / 255.0 * WIND_SCALE;
+}
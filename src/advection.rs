@@ -5,32 +5,32 @@ use bevy::render::render_asset::RenderAssets;
 use bevy::render::render_resource::*;
 use bevy::render::render_resource::binding_types::{sampler, texture_2d, texture_storage_2d, uniform_buffer};
 use bevy::render::{render_graph, Render, RenderApp, RenderSet};
-use bevy::render::render_graph::{NodeRunError, RenderGraph, RenderGraphContext, RenderLabel};
-use bevy::render::renderer::{RenderContext, RenderDevice};
-use crate::{FluidConfig, FluidTextures, HEIGHT, WIDTH, WORKGROUP_SIZE};
-use crate::universe::CellGrid;
-//      速度平流计算 -> 交换速度缓冲区 -> 更新纹理 -> 密度平流计算 -> 交换密度缓冲区
+use bevy::render::render_graph::{NodeRunError, RenderGraphContext, RenderLabel};
+use bevy::render::renderer::{RenderContext, RenderDevice, RenderQueue};
+use crate::{update_texture_data, FluidConfig, FluidFormats, FluidTextures, SimulationSize};
+use crate::universe::{CellGrid, TexelRect};
+//      更新纹理 -> 速度平流计算 -> 密度平流计算，.1 到 .0 的复制在 VelocityOutComputeNode 里
 pub struct AdvectionPlugin;
 impl Plugin for AdvectionPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(ExtractResourcePlugin::<VelocityAdvectionImage>::default())
         .add_plugins(ExtractResourcePlugin::<DensityAdvectionImage>::default())
+        .add_plugins(ExtractResourcePlugin::<CellTextureUpload>::default())
+            .init_resource::<CellTextureUpload>()
             .add_systems(Update,
-                         (
-                             swap_buffers, // 在帧开始时交换上一帧的结果
-                             update_burns_and_cells_textures.after(swap_buffers),
-                             swap_velocity_buffer, // 速度平流后的交换
-                             swap_density_buffer,  // 密度平流后的交换
-            ));
-        ;
+                         update_burns_and_cells_textures
+                             .after(update_texture_data), // tick 之后上传本帧的 burns
+            );
         let render_app = app.sub_app_mut(RenderApp);
         render_app.add_systems(
             Render,
             (
+                upload_cell_textures.in_set(RenderSet::PrepareResources),
                 prepare_velocity_bind_group.in_set(RenderSet::PrepareBindGroups),
                 prepare_density_bind_group.in_set(RenderSet::PrepareBindGroups),
             )
         )
+        .init_resource::<CellTextureMirror>()
         ;
 
 
@@ -42,13 +42,6 @@ impl Plugin for AdvectionPlugin {
     }
 }
 
-// 定义渲染标签
-#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
-struct VelocityAdvectionLabel;
-
-#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
-struct DensityAdvectionLabel;
-
 // 平流计算所需的uniform数据
 #[repr(C)]
 // #[derive(Debug, Copy, Clone, ShaderType)]
@@ -60,83 +53,76 @@ pub struct AdvectionUniforms {
 }
 
 // 存储平流计算管线的资源
+// 速度和密度共用 advection.wgsl，但输出格式不同（FluidFormats::velocity 和 Rgba8Unorm），各有一套布局和管线
 #[derive(Resource)]
 pub struct AdvectionPipeline {
-    pub(crate) pipeline: CachedComputePipelineId,
-    bind_group_layout: BindGroupLayout,
+    pub(crate) velocity_pipeline: CachedComputePipelineId,
+    pub(crate) density_pipeline: CachedComputePipelineId,
+    velocity_layout: BindGroupLayout,
+    density_layout: BindGroupLayout,
 }
 
 impl FromWorld for AdvectionPipeline {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
+        let formats = *world.resource::<FluidFormats>();
         // let bind_group_layout = AdvectionImage::bind_group_layout(render_device);
-        let bind_group_layout = render_device.create_bind_group_layout(
-            "advection_bind_group_layout",
-            &BindGroupLayoutEntries::sequential(
-                ShaderStages::COMPUTE,
-                (
-                    texture_2d(TextureSampleType::Float { filterable: true }),
-                    texture_2d(TextureSampleType::Float { filterable: true }),
-                    texture_2d(TextureSampleType::Float { filterable: true }),
-                    texture_storage_2d(TextureFormat::Rgba8Unorm, StorageTextureAccess::WriteOnly),
-                    sampler(SamplerBindingType::Filtering),
-                    sampler(SamplerBindingType::Filtering),
-                    sampler(SamplerBindingType::Filtering),
-                    uniform_buffer::<AdvectionUniforms>(false)
-                )
-        ));
-
+        let create_layout = |label: &str, output: TextureFormat| {
+            render_device.create_bind_group_layout(
+                label,
+                &BindGroupLayoutEntries::sequential(
+                    ShaderStages::COMPUTE,
+                    (
+                        texture_2d(TextureSampleType::Float { filterable: true }),
+                        texture_2d(TextureSampleType::Float { filterable: true }),
+                        texture_2d(TextureSampleType::Float { filterable: true }),
+                        texture_storage_2d(output, StorageTextureAccess::WriteOnly),
+                        sampler(SamplerBindingType::Filtering),
+                        sampler(SamplerBindingType::Filtering),
+                        sampler(SamplerBindingType::Filtering),
+                        uniform_buffer::<AdvectionUniforms>(false)
+                    )
+            ))
+        };
+        let velocity_layout = create_layout("velocity_advection_bind_group_layout", formats.velocity);
+        let density_layout = create_layout("density_advection_bind_group_layout", TextureFormat::Rgba8Unorm);
 
         let shader = world
             .resource::<AssetServer>()
             .load("advection.wgsl");
         let pipeline_cache = world.resource::<PipelineCache>();
 
-
-        let pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-            label: None,
-            layout: vec![bind_group_layout.clone()],
-            push_constant_ranges: Vec::new(),
-            shader: shader.clone(),
-            shader_defs: vec![],
-            entry_point: Cow::from("advection_main"),
-        });
-
-
+        let queue = |label: &'static str, layout: &BindGroupLayout, output: TextureFormat| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(Cow::from(label)),
+                layout: vec![layout.clone()],
+                push_constant_ranges: Vec::new(),
+                shader: shader.clone(),
+                shader_defs: vec![FluidFormats::output_def(output)],
+                entry_point: Cow::from("advection_main"),
+            })
+        };
 
         AdvectionPipeline {
-            pipeline,
-            bind_group_layout
+            velocity_pipeline: queue("velocity_advection_pipeline", &velocity_layout, formats.velocity),
+            density_pipeline: queue("density_advection_pipeline", &density_layout, TextureFormat::Rgba8Unorm),
+            velocity_layout,
+            density_layout,
         }
     }
 }
-#[derive(Resource, Clone, ExtractResource, AsBindGroup)]
+#[derive(Resource, Clone, ExtractResource)]
 pub struct VelocityAdvectionImage {
-    #[texture(0, visibility(compute))]
-    #[sampler(4)]
     pub(crate) velocity_tex: Handle<Image>,
-    #[texture(1, visibility(compute))]
-    #[sampler(5)]
     pub(crate) source_tex: Handle<Image>,
-    #[texture(2, visibility(compute))]
-    #[sampler(6)]
     pub(crate) wind_tex: Handle<Image>,
-    #[storage_texture(3, image_format = Rgba8Unorm, access = WriteOnly)]
     pub(crate) output_tex: Handle<Image>,
 }
-#[derive(Resource, Clone, ExtractResource, AsBindGroup)]
+#[derive(Resource, Clone, ExtractResource)]
 pub struct DensityAdvectionImage {
-    #[texture(0, visibility(compute))]
-    #[sampler(4)]
     pub(crate) burns_tex: Handle<Image>,
-    #[texture(1, visibility(compute))]
-    #[sampler(5)]
     pub(crate) velocity_tex: Handle<Image>,
-    #[texture(2, visibility(compute))]
-    #[sampler(6)]
     pub(crate) density_tex: Handle<Image>,
-
-    #[storage_texture(3, image_format = Rgba8Unorm, access = WriteOnly)]
     pub(crate) output_tex: Handle<Image>,
 }
 #[derive(Resource)]
@@ -152,10 +138,10 @@ fn prepare_velocity_bind_group(
     advection_pipeline: Res<AdvectionPipeline>,
     time: Res<Time>,
     fluid_config: Res<FluidConfig>,
+    size: Res<SimulationSize>,
 )
 {
     let velocity_tex_view = gpu_images.get(&advection_image.velocity_tex).unwrap();
-    let source_tex_view = gpu_images.get(&advection_image.source_tex).unwrap();
     let wind_tex_view = gpu_images.get(&advection_image.wind_tex).unwrap();
     let output_tex_view = gpu_images.get(&advection_image.output_tex).unwrap();
 
@@ -168,7 +154,7 @@ fn prepare_velocity_bind_group(
     let dt = time.delta_seconds().min(0.016);
     let dissipation = fluid_config.velocity_dissipation;
     let uniforms = AdvectionUniforms {
-        texel_size: [1.0 / WIDTH as f32, 1.0 / HEIGHT as f32],
+        texel_size: size.texel_size(),
         dt,
         dissipation,
     };
@@ -179,7 +165,7 @@ fn prepare_velocity_bind_group(
     });
     let bind_group = render_device.create_bind_group(
         Some("advection_bind_group"),
-        &advection_pipeline.bind_group_layout,
+        &advection_pipeline.velocity_layout,
         &BindGroupEntries::sequential
             (
                 (
@@ -221,6 +207,7 @@ fn prepare_density_bind_group(
     advection_pipeline: Res<AdvectionPipeline>,
     time: Res<Time>,
     fluid_config: Res<FluidConfig>,
+    size: Res<SimulationSize>,
 ) {
 
     let u_wind_view  = gpu_images.get(&advection_image.burns_tex).unwrap();
@@ -240,9 +227,9 @@ fn prepare_density_bind_group(
     let source_sampler  =sampler_create("source_sampler  ",&render_device);
 
     let dt = time.delta_seconds().min(0.016);
-    let dissipation = fluid_config.velocity_dissipation;
+    let dissipation = fluid_config.density_dissipation;
     let uniforms = AdvectionUniforms {
-        texel_size: [1.0 / WIDTH as f32, 1.0 / HEIGHT as f32],
+        texel_size: size.texel_size(),
         dt,
         dissipation,
     };
@@ -253,17 +240,18 @@ fn prepare_density_bind_group(
     });
     let bind_group = render_device.create_bind_group(
         Some("advection_bind_group"),
-        &advection_pipeline.bind_group_layout,
+        &advection_pipeline.density_layout,
         &BindGroupEntries::sequential
             (
                 (
-                    &u_wind_view.texture_view,
+                    // 顺序与 advection.wgsl 的 velocity、source、wind 绑定一致
                     &u_velocity_view.texture_view,
                     &u_source_view.texture_view,
+                    &u_wind_view.texture_view,
                     &output_tex_view.texture_view,
-                    &wind_sampler,
                     &velocity_sampler,
                     &source_sampler,
+                    &wind_sampler,
                     BindingResource::Buffer(BufferBinding {
                         buffer: &uniform_buffer,
                         offset: 0,
@@ -289,11 +277,10 @@ pub(crate) struct DensityAdvectionComputeLabel;
 impl render_graph::Node for VelocityAdvectionComputeNode {
     fn run(
         &self,
-        graph: &mut RenderGraphContext,
+        _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        println!("VelocityAdvectionComputeNode run");
         let pipeline_cache = world.resource::<PipelineCache>();
         let advection_pipeline = world.resource::<AdvectionPipeline>();
         let advection_bind_group = world.resource::<VelocityAdvectionBindGroup>();
@@ -305,16 +292,15 @@ impl render_graph::Node for VelocityAdvectionComputeNode {
                 ..default()});
         pass.set_bind_group(0, &advection_bind_group.0, &[]);
         if let CachedPipelineState::Ok(_) =
-            pipeline_cache.get_compute_pipeline_state(advection_pipeline.pipeline)
+            pipeline_cache.get_compute_pipeline_state(advection_pipeline.velocity_pipeline)
         {
 
             let update_pipeline = pipeline_cache
-                .get_compute_pipeline(advection_pipeline.pipeline)
+                .get_compute_pipeline(advection_pipeline.velocity_pipeline)
                 .unwrap();
             pass.set_pipeline(update_pipeline);
-            pass.dispatch_workgroups(WIDTH / WORKGROUP_SIZE, HEIGHT / WORKGROUP_SIZE, 1);
-        }else {
-
+            let (workgroups_x, workgroups_y) = world.resource::<SimulationSize>().workgroups();
+            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
         }
        Ok(())
     }
@@ -324,7 +310,7 @@ impl render_graph::Node for VelocityAdvectionComputeNode {
 impl render_graph::Node for DensityAdvectionComputeNode {
     fn run(
         &self,
-        graph: &mut RenderGraphContext,
+        _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
@@ -342,11 +328,12 @@ impl render_graph::Node for DensityAdvectionComputeNode {
         pass.set_bind_group(0, &advection_bind_group.0, &[]);
 
         if let CachedPipelineState::Ok(_) =
-            pipeline_cache.get_compute_pipeline_state(advection_pipeline.pipeline)
+            pipeline_cache.get_compute_pipeline_state(advection_pipeline.density_pipeline)
         {
-            if let Some(update_pipeline) = pipeline_cache.get_compute_pipeline(advection_pipeline.pipeline) {
+            if let Some(update_pipeline) = pipeline_cache.get_compute_pipeline(advection_pipeline.density_pipeline) {
                 pass.set_pipeline(update_pipeline);
-                pass.dispatch_workgroups(WIDTH / WORKGROUP_SIZE, HEIGHT / WORKGROUP_SIZE, 1);
+                let (workgroups_x, workgroups_y) = world.resource::<SimulationSize>().workgroups();
+                pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
             } else {
                 warn!("Compute pipeline not found in cache");
             }
@@ -361,105 +348,134 @@ impl render_graph::Node for DensityAdvectionComputeNode {
 
 // 更新燃烧和细胞纹理的系统
 fn update_burns_and_cells_textures(
-    mut images: ResMut<Assets<Image>>,
-    fluid_textures: Res<FluidTextures>,
-    cell_grid: Res<CellGrid>,
+    mut cell_grid: ResMut<CellGrid>,
+    mut upload: ResMut<CellTextureUpload>,
 ) {
-     // 更新燃烧纹理
-    if let Some(image) = images.get_mut(&fluid_textures.burns) {
-        let pixels = image.data.as_mut_slice();
-        // println!("update_burns_and_cells_textures:{:?}",pixels.len());
-        for (i, wind) in cell_grid.winds.iter().enumerate() {
-            let idx = i * 4;
-            pixels[idx] = wind.dx;
-            pixels[idx + 1] = wind.dy;
-            pixels[idx + 2] = wind.pressure;
-            pixels[idx + 3] = wind.density;
-
-        }
-    }
-
-    // 更新细胞纹理
-    if let Some(image) = images.get_mut(&fluid_textures.cells) {
-        let pixels = image.data.as_mut_slice();
-        for (i, cell) in cell_grid.cells.iter().enumerate() {
-            let idx = i * 4;
-            pixels[idx] = cell.species as u8;
-            pixels[idx + 1] = cell.ra;
-            pixels[idx + 2] = cell.rb;
-            pixels[idx + 3] = cell.clock;
-        }
-    }
+    // 只上传本帧更新过或被修改过的块（休眠的块不变），而不是每帧重写整张纹理
+    let rects = cell_grid.take_dirty_rects();
+    upload.width = cell_grid.width() as u32;
+    upload.height = cell_grid.height() as u32;
+    upload.patches = rects
+        .into_iter()
+        .map(|rect| TexturePatch {
+            rect,
+            cells: cell_grid.cells_rgba_rect(rect),
+            burns: cell_grid.burns_rgba_rect(rect),
+        })
+        .collect();
 }
-fn check_density_texture(
-    images: Res<Assets<Image>>,
-    fluid_textures: Res<FluidTextures>,
-    frame_count: Local<u32>,
-) {
-    let frame = *frame_count;
-    if frame % 60 == 0 { // 每秒检查一次
-        if let Some(image) = images.get(&fluid_textures.density.0) {
-            let sample_pos = 0; // 检查第一个像素
-            if image.data.len() >= 4 {
-                let r = image.data[sample_pos];
-                let g = image.data[sample_pos + 1];
-                let b = image.data[sample_pos + 2];
-                let a = image.data[sample_pos + 3];
-                info!("Density texture sample: R:{}, G:{}, B:{}, A:{}", r, g, b, a);
-            }
-        }
 
-        if let Some(image) = images.get(&fluid_textures.density.1) {
-            let sample_pos = 0;
-            if image.data.len() >= 4 {
-                let r = image.data[sample_pos];
-                let g = image.data[sample_pos + 1];
-                let b = image.data[sample_pos + 2];
-                let a = image.data[sample_pos + 3];
-                info!("Density texture (back buffer) sample: R:{}, G:{}, B:{}, A:{}", r, g, b, a);
-            }
-        }
-    }
+// 一帧要上传的细胞、燃烧纹理的脏区域，每个像素 4 字节，按纹理的行排列
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct CellTextureUpload {
+    width: u32,
+    height: u32,
+    patches: Vec<TexturePatch>,
 }
-// 假设的数据资源
-#[derive(Resource)]
-pub struct BurnsData(pub Vec<u8>);
-
-#[derive(Resource)]
-pub struct CellsData(pub Vec<u8>);
-// 交换速度缓冲区
-fn swap_velocity_buffer(mut fluid_textures: ResMut<FluidTextures>) {
-    let velocity = &mut fluid_textures.velocity;
-    std::mem::swap(&mut velocity.0, &mut velocity.1);
-    }
 
-// 交换密度缓冲区
-fn swap_density_buffer(mut fluid_textures: ResMut<FluidTextures>) {
-    let density = &mut fluid_textures.density;
-    std::mem::swap(&mut density.0, &mut density.1);
+#[derive(Clone)]
+struct TexturePatch {
+    rect: TexelRect,
+    cells: Vec<u8>,
+    burns: Vec<u8>,
 }
 
+// 渲染端保存一份完整的纹理内容：纹理重新创建（初始化、改变尺寸）时整张上传，平时只写脏区域
+#[derive(Resource, Default)]
+struct CellTextureMirror {
+    width: u32,
+    height: u32,
+    cells: Vec<u8>,
+    burns: Vec<u8>,
+    // 上一次写入的 GPU 纹理，变了说明纹理被重新创建
+    textures: Option<(TextureId, TextureId)>,
+}
 
-
-fn swap_buffers(
-    images: Res<Assets<Image>>,
-    mut fluid_textures: ResMut<FluidTextures>) {
-    {
-        println!("swap_buffers");
-        fluid_textures.log(&images);
+fn upload_cell_textures(
+    upload: Res<CellTextureUpload>,
+    fluid_textures: Res<FluidTextures>,
+    gpu_images: Res<RenderAssets<Image>>,
+    render_queue: Res<RenderQueue>,
+    mut mirror: ResMut<CellTextureMirror>,
+) {
+    if upload.width == 0 || upload.height == 0 {
+        return;
     }
-    {
-        let  velocity = &mut fluid_textures.velocity;
-        std::mem::swap(&mut velocity.0, &mut velocity.1);
-        let density = &mut fluid_textures.density;
-        std::mem::swap(&mut density.0, &mut density.1);
+    if (mirror.width, mirror.height) != (upload.width, upload.height) {
+        let len = (upload.width * upload.height * 4) as usize;
+        *mirror = CellTextureMirror {
+            width: upload.width,
+            height: upload.height,
+            cells: vec![0; len],
+            burns: vec![0; len],
+            textures: None,
+        };
     }
-    {
-        println!("swap_buffers1");
-        fluid_textures.log(&images);
+    let width = mirror.width;
+    for patch in upload.patches.iter() {
+        copy_patch(&mut mirror.cells, width, patch.rect, &patch.cells);
+        copy_patch(&mut mirror.burns, width, patch.rect, &patch.burns);
     }
 
+    let (Some(cells), Some(burns)) = (
+        gpu_images.get(&fluid_textures.cells),
+        gpu_images.get(&fluid_textures.burns),
+    ) else {
+        return;
+    };
+    if cells.size != Vec2::new(mirror.width as f32, mirror.height as f32) {
+        return;
+    }
+    let textures = (cells.texture.id(), burns.texture.id());
+    if mirror.textures != Some(textures) {
+        let full = TexelRect {
+            x: 0,
+            y: 0,
+            width: mirror.width,
+            height: mirror.height,
+        };
+        write_rect(&render_queue, &cells.texture, full, &mirror.cells);
+        write_rect(&render_queue, &burns.texture, full, &mirror.burns);
+        mirror.textures = Some(textures);
+        return;
+    }
+    for patch in upload.patches.iter() {
+        write_rect(&render_queue, &cells.texture, patch.rect, &patch.cells);
+        write_rect(&render_queue, &burns.texture, patch.rect, &patch.burns);
+    }
+}
 
+fn copy_patch(target: &mut [u8], width: u32, rect: TexelRect, data: &[u8]) {
+    let row_bytes = (rect.width * 4) as usize;
+    for (row, src) in data.chunks_exact(row_bytes).enumerate() {
+        let start = (((rect.y + row as u32) * width + rect.x) * 4) as usize;
+        target[start..start + row_bytes].copy_from_slice(src);
+    }
+}
 
+fn write_rect(render_queue: &RenderQueue, texture: &Texture, rect: TexelRect, data: &[u8]) {
+    render_queue.write_texture(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d {
+                x: rect.x,
+                y: rect.y,
+                z: 0,
+            },
+            aspect: TextureAspect::All,
+        },
+        data,
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(rect.width * 4),
+            rows_per_image: None,
+        },
+        Extent3d {
+            width: rect.width,
+            height: rect.height,
+            depth_or_array_layers: 1,
+        },
+    );
 }
 
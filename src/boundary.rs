@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::render_resource::{Buffer, BufferInitDescriptor, BufferUsages, ShaderType};
+use bevy::render::renderer::RenderDevice;
+use crate::universe::CellGrid;
+use crate::FluidConfig;
+
+// 流体边界：divergence、pressure、gradient_subtract 和 multigrid 各自绑定一份 BoundaryUniforms，
+// 着色器通过 #import sand::boundary 使用相同的处理（见 assets/boundary.wgsl）
+// 边界方式来自 FluidConfig，哪些物质是固体、哪些不衰减风来自物质注册表，注册表变化时更新 FluidSpecies
+pub struct BoundaryPlugin;
+
+impl Plugin for BoundaryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FluidSpecies>()
+            .add_plugins(ExtractResourcePlugin::<FluidSpecies>::default())
+            .add_systems(Update, update_fluid_species);
+    }
+}
+
+// SpeciesRegistry::solid_mask 和 undamped_mask
+#[derive(Resource, ExtractResource, Clone, Copy, Default)]
+pub(crate) struct FluidSpecies {
+    pub(crate) solid_mask: u32,
+    pub(crate) undamped_mask: u32,
+}
+
+fn update_fluid_species(
+    cell_grid: Res<CellGrid>,
+    mut fluid_species: ResMut<FluidSpecies>,
+    mut revision: Local<Option<u32>>,
+) {
+    let current = cell_grid.registry().revision();
+    if *revision == Some(current) {
+        return;
+    }
+    *revision = Some(current);
+    fluid_species.solid_mask = cell_grid.registry().solid_mask();
+    fluid_species.undamped_mask = cell_grid.registry().undamped_mask();
+}
+
+// 与 boundary.wgsl 的 Boundary 一致
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, ShaderType)]
+pub(crate) struct BoundaryUniforms {
+    edge: u32,
+    solid: u32,
+    solid_mask: u32,
+    undamped_mask: u32,
+}
+
+impl BoundaryUniforms {
+    pub(crate) fn new(fluid_config: &FluidConfig, fluid_species: FluidSpecies) -> Self {
+        Self {
+            edge: fluid_config.edge_boundary as u32,
+            solid: fluid_config.solid_boundary as u32,
+            solid_mask: fluid_species.solid_mask,
+            undamped_mask: fluid_species.undamped_mask,
+        }
+    }
+}
+
+pub(crate) fn boundary_buffer(
+    render_device: &RenderDevice,
+    fluid_config: &FluidConfig,
+    fluid_species: FluidSpecies,
+) -> Buffer {
+    render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("boundary_uniform_buffer"),
+        contents: bytemuck::cast_slice(&[BoundaryUniforms::new(fluid_config, fluid_species)]),
+        usage: BufferUsages::UNIFORM,
+    })
+}
@@ -0,0 +1,340 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::thread::{self, JoinHandle};
+use bevy::prelude::Resource;
+use crossbeam_channel::{bounded, Receiver, Sender};
+use crate::universe::{render_cells_rgba, CellGrid, RenderParams, RgbaImage};
+
+mod gif;
+
+use gif::GifWriter;
+
+// 把模拟录成 PNG 序列或 GIF 动画，每 every 代取一帧，颜色来自 render_cells_rgba（与 sand.wgsl 相同的配色，白底）
+// 窗口模式和无窗口模式共用：窗口模式下细胞纹理就是 CellGrid::cells_rgba 的镜像，直接从 CellGrid 出图，不用从 GPU 读回
+// 所以录下来的只有细胞层，不含流体显示模式和面板；主线程只负责出图，放大、编码和写文件都在每段录制自己的编码线程里
+//   --capture PATH          开始录制；.gif 结尾写 GIF，否则写 PNG 序列，路径里的 {} 换成帧号，没有 {} 时帧号加在扩展名前
+//   --capture-every N       每 N 代一帧，默认 1
+//   --capture-scale N       每个细胞放大成 N×N 个像素，默认 1
+//   --capture-frames A..B   只录 tick 计数在 [A, B) 之间的代，两端都可以省略
+// 窗口模式下 R 键开始或停止录制，没给 --capture 时写到 DEFAULT_CAPTURE_PATH；
+// 停止后再开始是新的一段，文件名加上 -2、-3 以免覆盖
+
+pub(crate) const DEFAULT_CAPTURE_PATH: &str = "capture.gif";
+
+// 窗口模式每秒 60 代，GIF 的停留时间按这个换算
+const TICKS_PER_SECOND: u32 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CaptureOptions {
+    pub(crate) path: String,
+    pub(crate) every: u32,
+    pub(crate) scale: u32,
+    pub(crate) first: u64,
+    pub(crate) end: Option<u64>,
+    // 给了 --capture，启动时就开始录制
+    pub(crate) start: bool,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_CAPTURE_PATH.to_string(),
+            every: 1,
+            scale: 1,
+            first: 0,
+            end: None,
+            start: false,
+        }
+    }
+}
+
+impl CaptureOptions {
+    pub(crate) fn from_args(args: &[String]) -> Self {
+        let mut options = CaptureOptions::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--capture" => match iter.next() {
+                    Some(path) => {
+                        options.path = path.clone();
+                        options.start = true;
+                    }
+                    None => eprintln!("--capture needs a path"),
+                },
+                "--capture-every" => {
+                    if let Some(n) = iter.next().and_then(|v| v.parse::<u32>().ok()) {
+                        options.every = n.max(1);
+                    }
+                }
+                "--capture-scale" => {
+                    if let Some(n) = iter.next().and_then(|v| v.parse::<u32>().ok()) {
+                        options.scale = n.max(1);
+                    }
+                }
+                "--capture-frames" => match iter.next().and_then(|v| parse_range(v)) {
+                    Some((first, end)) => {
+                        options.first = first;
+                        options.end = end;
+                    }
+                    None => eprintln!("--capture-frames expects A..B"),
+                },
+                _ => {}
+            }
+        }
+        options
+    }
+
+    fn is_gif(&self) -> bool {
+        Path::new(&self.path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"))
+    }
+
+    // tick 这一代是否要录
+    fn wants(&self, tick: u64) -> bool {
+        tick >= self.first && (tick - self.first) % self.every as u64 == 0 && !self.is_over(tick)
+    }
+
+    fn is_over(&self, tick: u64) -> bool {
+        self.end.is_some_and(|end| tick >= end)
+    }
+
+    // 第 take 段录制的路径，第一段就是 path
+    fn take_path(&self, take: u32) -> String {
+        if take <= 1 {
+            return self.path.clone();
+        }
+        insert_before_extension(&self.path, &format!("-{}", take))
+    }
+
+    // PNG 序列第 frame 帧的路径
+    fn frame_path(&self, take: u32, frame: u32) -> String {
+        let path = self.take_path(take);
+        let number = format!("{:05}", frame);
+        if path.contains("{}") {
+            path.replacen("{}", &number, 1)
+        } else {
+            insert_before_extension(&path, &format!("_{}", number))
+        }
+    }
+}
+
+// "A..B"、"A.."、"..B"
+fn parse_range(text: &str) -> Option<(u64, Option<u64>)> {
+    let (first, end) = text.split_once("..")?;
+    let first = if first.is_empty() { 0 } else { first.parse().ok()? };
+    let end = if end.is_empty() { None } else { Some(end.parse().ok()?) };
+    Some((first, end))
+}
+
+fn insert_before_extension(path: &str, suffix: &str) -> String {
+    let file_start = path.rfind(['/', '\\']).map_or(0, |i| i + 1);
+    match path[file_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = file_start + dot;
+            format!("{}{}{}", &path[..dot], suffix, &path[dot..])
+        }
+        _ => format!("{}{}", path, suffix),
+    }
+}
+
+// 编码线程前面最多排队的帧数，排满时主线程等编码线程跟上，不丢帧
+const QUEUED_FRAMES: usize = 8;
+
+// 一段录制的编码线程，按顺序写 GIF 或 PNG 序列；出错时提前退出，错误由 finish 取回
+struct Encoder {
+    frames: Sender<RgbaImage>,
+    worker: JoinHandle<io::Result<()>>,
+}
+
+impl Encoder {
+    fn spawn(options: CaptureOptions, take: u32) -> Self {
+        let (frames, receiver) = bounded(QUEUED_FRAMES);
+        let worker = thread::spawn(move || encode_take(&options, take, receiver));
+        Self { frames, worker }
+    }
+
+    // 编码线程已经退出时返回 false
+    fn send(&self, image: RgbaImage) -> bool {
+        self.frames.send(image).is_ok()
+    }
+
+    // 等排队的帧写完，GIF 在这里写入结尾
+    fn finish(self) -> io::Result<()> {
+        drop(self.frames);
+        self.worker
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("capture encoder panicked")))
+    }
+}
+
+fn encode_take(options: &CaptureOptions, take: u32, frames: Receiver<RgbaImage>) -> io::Result<()> {
+    let mut gif = None;
+    for (frame, mut image) in frames.iter().enumerate() {
+        image.flatten([255, 255, 255]);
+        let image = upscale(image, options.scale);
+        if options.is_gif() {
+            if gif.is_none() {
+                let path = options.take_path(take);
+                create_parent(&path)?;
+                let delay = (options.every * 100 / TICKS_PER_SECOND).max(2) as u16;
+                let file = BufWriter::new(File::create(&path)?);
+                gif = Some(GifWriter::new(file, image.width, image.height, delay)?);
+            }
+            if let Some(gif) = &mut gif {
+                gif.write_frame(&image.data)?;
+            }
+        } else {
+            let path = options.frame_path(take, frame as u32);
+            create_parent(&path)?;
+            image.save_png(path)?;
+        }
+    }
+    if let Some(gif) = gif {
+        gif.finish()?;
+    }
+    Ok(())
+}
+
+// 每个像素放大成 scale×scale 的方块
+fn upscale(image: RgbaImage, scale: u32) -> RgbaImage {
+    if scale <= 1 {
+        return image;
+    }
+    let width = image.width * scale;
+    let height = image.height * scale;
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            data.extend(image.pixel(x / scale, y / scale));
+        }
+    }
+    RgbaImage { width, height, data }
+}
+
+#[derive(Resource)]
+pub(crate) struct Recorder {
+    options: CaptureOptions,
+    active: bool,
+    // 已经开始过的段数
+    take: u32,
+    // 当前这一段已交给编码线程的帧数
+    frames: u32,
+    encoder: Option<Encoder>,
+}
+
+impl Recorder {
+    pub(crate) fn new(options: CaptureOptions) -> Self {
+        let mut recorder = Self {
+            active: false,
+            take: 0,
+            frames: 0,
+            encoder: None,
+            options,
+        };
+        if recorder.options.start {
+            recorder.start();
+        }
+        recorder
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub(crate) fn start(&mut self) {
+        if self.active {
+            return;
+        }
+        self.active = true;
+        self.take += 1;
+        self.frames = 0;
+        self.encoder = Some(Encoder::spawn(self.options.clone(), self.take));
+        println!("capture started: {}", self.options.take_path(self.take));
+    }
+
+    // 结束当前这一段，等编码线程写完
+    pub(crate) fn stop(&mut self) -> io::Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        self.active = false;
+        if let Some(encoder) = self.encoder.take() {
+            encoder.finish()?;
+        }
+        println!("capture stopped after {} frames", self.frames);
+        Ok(())
+    }
+
+    // 每代 tick 之后调用，按 every 和帧范围决定是否写一帧；超出范围时自动停止
+    pub(crate) fn capture(&mut self, cell_grid: &CellGrid) -> io::Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        let tick = cell_grid.ticks();
+        if self.options.is_over(tick) {
+            return self.stop();
+        }
+        if !self.options.wants(tick) {
+            return Ok(());
+        }
+        let image = render_cells_rgba(cell_grid, RenderParams::default());
+        let sent = self.encoder.as_ref().is_some_and(|encoder| encoder.send(image));
+        if !sent {
+            // 编码线程出错退出了，stop 取回它的错误
+            self.stop()?;
+            return Err(io::Error::other("capture encoder stopped"));
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+// 没有经过 stop 的 GIF（例如无窗口模式提前退出）也写上结尾
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            eprintln!("failed to finish capture: {}", e);
+        }
+    }
+}
+
+fn create_parent(path: &str) -> io::Result<()> {
+    match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn frame_paths_number_frames_and_takes() {
+        let options = CaptureOptions::from_args(&args("--capture out/run.png"));
+        assert_eq!(options.frame_path(1, 7), "out/run_00007.png");
+        assert_eq!(options.frame_path(2, 0), "out/run-2_00000.png");
+        let options = CaptureOptions::from_args(&args("--capture frames/{}.png"));
+        assert_eq!(options.frame_path(1, 12), "frames/00012.png");
+        let options = CaptureOptions::from_args(&args("--capture ./run.v1/clip.GIF"));
+        assert!(options.is_gif());
+        assert_eq!(options.take_path(3), "./run.v1/clip-3.GIF");
+    }
+
+    #[test]
+    fn every_and_range_select_ticks() {
+        let options = CaptureOptions::from_args(&args("--capture a.gif --capture-every 3 --capture-frames 10..20"));
+        let ticks: Vec<u64> = (0..30).filter(|&tick| options.wants(tick)).collect();
+        assert_eq!(ticks, vec![10, 13, 16, 19]);
+        assert!(options.is_over(20));
+        let options = CaptureOptions::from_args(&args("--capture-frames ..5"));
+        assert!(!options.start);
+        assert_eq!((options.first, options.end), (0, Some(5)));
+    }
+}
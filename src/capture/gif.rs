@@ -0,0 +1,123 @@
+use std::io::{self, ErrorKind, Write};
+use gif::{EncodingError, Encoder, Frame, Repeat};
+
+// GIF 编码交给 gif crate：每帧用 NeuQuant 量化出自己的局部调色板，无限循环播放
+// 模拟的颜色来自 HSV 和噪声，逐帧量化比固定的颜色立方体少很多色带；不超过 256 色的帧原样保留
+
+// NeuQuant 的采样间隔，1 最准最慢，30 最快；边录边写，取 gif crate 推荐的 10
+const QUANTIZE_SPEED: i32 = 10;
+
+pub(crate) struct GifWriter<W: Write> {
+    encoder: Encoder<W>,
+    width: u16,
+    height: u16,
+    // 每帧的停留时间，单位是 1/100 秒
+    delay: u16,
+}
+
+fn encoding_error(e: EncodingError) -> io::Error {
+    match e {
+        EncodingError::Io(e) => e,
+        EncodingError::Format(e) => io::Error::new(ErrorKind::InvalidInput, e.to_string()),
+    }
+}
+
+impl<W: Write> GifWriter<W> {
+    pub(crate) fn new(out: W, width: u32, height: u32, delay: u16) -> io::Result<Self> {
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "GIF frames are at most 65535 pixels wide"));
+        };
+        let mut encoder = Encoder::new(out, width, height, &[]).map_err(encoding_error)?;
+        encoder.set_repeat(Repeat::Infinite).map_err(encoding_error)?;
+        Ok(Self { encoder, width, height, delay })
+    }
+
+    // rgba 是行优先的 RGBA8 像素，alpha 为 0 的像素写成透明色
+    pub(crate) fn write_frame(&mut self, rgba: &[u8]) -> io::Result<()> {
+        let pixels = self.width as usize * self.height as usize;
+        if rgba.len() != pixels * 4 {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "frame size does not match the GIF"));
+        }
+        let mut rgba = rgba.to_vec();
+        let mut frame = Frame::from_rgba_speed(self.width, self.height, &mut rgba, QUANTIZE_SPEED);
+        frame.delay = self.delay;
+        self.encoder.write_frame(&frame).map_err(encoding_error)
+    }
+
+    pub(crate) fn finish(self) -> io::Result<W> {
+        let mut out = self.encoder.into_inner()?;
+        out.flush()?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gif::{ColorOutput, DecodeOptions};
+
+    // (停留时间, RGBA 像素)
+    type DecodedFrame = (u16, Vec<u8>);
+
+    // 读回整个文件：循环方式和每一帧，测试用
+    fn read_gif(bytes: &[u8]) -> io::Result<(Repeat, Vec<DecodedFrame>)> {
+        let invalid = |e: gif::DecodingError| io::Error::new(ErrorKind::InvalidData, e.to_string());
+        let mut options = DecodeOptions::new();
+        options.set_color_output(ColorOutput::RGBA);
+        let mut decoder = options.read_info(bytes).map_err(invalid)?;
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().map_err(invalid)? {
+            frames.push((frame.delay, frame.buffer.to_vec()));
+        }
+        Ok((decoder.repeat(), frames))
+    }
+
+    fn rgba(pixels: &[[u8; 3]]) -> Vec<u8> {
+        pixels.iter().flat_map(|&[r, g, b]| [r, g, b, 255]).collect()
+    }
+
+    #[test]
+    fn frames_are_quantised_separately() {
+        let (width, height) = (160, 120);
+        // 伪随机颜色远多于 256 种，要量化
+        let mut state = 0x2545_f491u32;
+        let noise: Vec<[u8; 3]> = (0..width * height)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                let [_, r, g, b] = state.to_be_bytes();
+                [r, g, b]
+            })
+            .collect();
+        // 两种颜色，各自的调色板里原样保留；固定的颜色立方体里 (200, 40, 0) 会偏色
+        let flat: Vec<[u8; 3]> = (0..width * height)
+            .map(|i| if i % width < width / 2 { [255, 255, 255] } else { [200, 40, 0] })
+            .collect();
+
+        let mut gif = GifWriter::new(Vec::new(), width as u32, height as u32, 5).unwrap();
+        gif.write_frame(&rgba(&noise)).unwrap();
+        gif.write_frame(&rgba(&flat)).unwrap();
+        gif.write_frame(&rgba(&[])).unwrap_err();
+        let bytes = gif.finish().unwrap();
+
+        let (repeat, frames) = read_gif(&bytes).unwrap();
+        assert_eq!(repeat, Repeat::Infinite);
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|(delay, _)| *delay == 5));
+        assert_eq!(frames[1].1, rgba(&flat));
+
+        // 量化后每个分量的平均误差在几级以内
+        let error: u64 = frames[0]
+            .1
+            .iter()
+            .zip(rgba(&noise))
+            .map(|(a, b)| (*a as i32 - b as i32).unsigned_abs() as u64)
+            .sum();
+        let mean = error as f64 / (width * height * 3) as f64;
+        assert!(mean < 24.0, "mean error {}", mean);
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        assert!(GifWriter::new(Vec::new(), 70_000, 10, 5).is_err());
+    }
+}
@@ -5,9 +5,9 @@ use bevy::render::render_asset::RenderAssets;
 use bevy::render::render_resource::*;
 use bevy::render::render_resource::binding_types::{sampler, texture_2d, texture_storage_2d, uniform_buffer};
 use bevy::render::{render_graph, Render, RenderApp, RenderSet};
-use bevy::render::render_graph::{NodeRunError, RenderGraph, RenderGraphContext, RenderLabel};
+use bevy::render::render_graph::{NodeRunError, RenderGraphContext, RenderLabel};
 use bevy::render::renderer::{RenderContext, RenderDevice};
-use crate::{FluidConfig, HEIGHT, WIDTH, WORKGROUP_SIZE};
+use crate::{FluidConfig, FluidFormats, SimulationSize};
 
 
 pub struct ClearPlugin;
@@ -46,6 +46,7 @@ impl FromWorld for ClearPipeline {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
         // let bind_group_layout = AdvectionImage::bind_group_layout(render_device);
+        let formats = *world.resource::<FluidFormats>();
         let bind_group_layout = render_device.create_bind_group_layout(
             "clear_bind_group_layout",
             &BindGroupLayoutEntries::sequential(
@@ -53,7 +54,7 @@ impl FromWorld for ClearPipeline {
                 (
                     texture_2d(TextureSampleType::Float { filterable: true }),
                     texture_2d(TextureSampleType::Float { filterable: true }),
-                    texture_storage_2d(TextureFormat::Rgba8Unorm, StorageTextureAccess::WriteOnly),
+                    texture_storage_2d(formats.scalar, StorageTextureAccess::WriteOnly),
                     sampler(SamplerBindingType::Filtering),
                     sampler(SamplerBindingType::Filtering),
                     uniform_buffer::<ClearUniforms>(false)
@@ -68,7 +69,7 @@ impl FromWorld for ClearPipeline {
             layout: vec![bind_group_layout.clone()],
             push_constant_ranges: Vec::new(),
             shader: shader.clone(),
-            shader_defs: vec![],
+            shader_defs: vec![FluidFormats::output_def(formats.scalar)],
             entry_point: Cow::from("clear_main"),
         });
         ClearPipeline {
@@ -78,15 +79,10 @@ impl FromWorld for ClearPipeline {
     }
 }
 
-#[derive(Resource, Clone, ExtractResource, AsBindGroup)]
+#[derive(Resource, Clone, ExtractResource)]
 pub struct ClearImage {
-    #[texture(0, visibility(compute))]
-    #[sampler(3)]
     pub(crate) u_texture_tex: Handle<Image>,
-    #[texture(1, visibility(compute))]
-    #[sampler(4)]
     pub(crate) u_wind_tex: Handle<Image>,
-    #[storage_texture(2, image_format = Rgba8Unorm, access = WriteOnly)]
     pub(crate) output_tex: Handle<Image>,
 }
 
@@ -99,7 +95,7 @@ fn prepare_bind_group(
     advection_image: Res<ClearImage>,
     render_device: Res<RenderDevice>,
     advection_pipeline: Res<ClearPipeline>,
-    time: Res<Time>,
+    _time: Res<Time>,
     fluid_config: Res<FluidConfig>,
 ) {
     let u_texture_tex_view = gpu_images.get(&advection_image.u_texture_tex).unwrap();
@@ -121,8 +117,9 @@ fn prepare_bind_group(
         min_filter: FilterMode::Linear,
         ..Default::default()
     });
+    // 上一帧的压力按 pressure_dissipation 衰减，与 FluidSolver::clear_pressure 相同
     let uniforms = ClearUniforms {
-        value: 0.8
+        value: fluid_config.pressure_dissipation
     };
     let uniform_buffer=render_device.create_buffer_with_data(&BufferInitDescriptor {
         label:  Some("clear_uniform_buffer"),
@@ -160,13 +157,13 @@ pub(crate) struct ClearComputeNode;
 impl render_graph::Node for ClearComputeNode {
     fn run(
         &self,
-        graph: &mut RenderGraphContext,
+        _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
         let pipeline_cache = world.resource::<PipelineCache>();
-        let Clear_pipeline = world.resource::<ClearPipeline>();
-        let Clear_bind_group = world.resource::<ClearBindGroup>();
+        let clear_pipeline = world.resource::<ClearPipeline>();
+        let clear_bind_group = world.resource::<ClearBindGroup>();
 
         // println!("Clear Compute Pass");
         let mut pass = render_context
@@ -176,10 +173,11 @@ impl render_graph::Node for ClearComputeNode {
                 ..Default::default()
             });
 
-        if let Some(pipeline) = pipeline_cache.get_compute_pipeline(Clear_pipeline.pipeline) {
+        if let Some(pipeline) = pipeline_cache.get_compute_pipeline(clear_pipeline.pipeline) {
             pass.set_pipeline(pipeline);
-            pass.set_bind_group(0, &Clear_bind_group.0, &[]);
-            pass.dispatch_workgroups(WIDTH / WORKGROUP_SIZE, HEIGHT / WORKGROUP_SIZE, 1);
+            pass.set_bind_group(0, &clear_bind_group.0, &[]);
+            let (workgroups_x, workgroups_y) = world.resource::<SimulationSize>().workgroups();
+            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
         }
 
         Ok(())
@@ -1,4 +1,3 @@
-use std::borrow::Cow;
 use bevy::{
     core_pipeline::{
         fullscreen_vertex_shader::fullscreen_shader_vertex_state,
@@ -6,70 +5,194 @@ use bevy::{
     ecs::query::QueryItem,
     prelude::*,
     render::{
-        extract_component::{ExtractComponent},
         render_graph::{
-            NodeRunError, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
+            NodeRunError, RenderGraphContext, RenderLabel, ViewNode,
         },
         render_resource::*,
         view::ViewTarget,
-        Render, RenderApp, RenderSet,
+        RenderApp,
     },
 };
-use bevy::render::extract_component::ExtractComponentPlugin;
 use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
 use bevy::render::render_asset::RenderAssets;
-use bevy::render::render_graph::RenderGraph;
-use bevy::render::render_resource::binding_types::{sampler, texture_2d};
+use bevy::render::render_resource::binding_types::{sampler, texture_2d, uniform_buffer};
 use bevy::render::renderer::{RenderContext, RenderDevice};
 use bevy::render::texture::BevyDefault;
-use crate::FluidTextures;
 
+// 调试显示：在 CellMaterial 画出的细胞之上显示一个流体场
+// V 切换显示模式（Shift+V 反向），O 切换叠加和单独显示，- 和 = 把当前模式的量程减半或加倍
+// 有符号的场（压力、旋度、散度）用蓝-白-红的发散色带，速度按方向取色相、按大小取亮度，或者画成箭头
+// 叠加时场越弱越透明，值为 0 的地方能看到下面的细胞
 pub struct DisplayPlugin;
 
 impl Plugin for DisplayPlugin {
     fn build(&self, app: &mut App) {
-       app.add_plugins(ExtractResourcePlugin::<DisplayTarget>::default())
-           // .add_systems(Update,display_log)
-       ;
-
-        let render_app = app.sub_app_mut(RenderApp);
-        render_app
-            // .init_resource::<DisplayPipeline>()
-            // .add_systems(Startup, setup_display_pipeline)
-            ;
+        app.init_resource::<DisplaySettings>()
+            .add_plugins(ExtractResourcePlugin::<DisplayTarget>::default())
+            .add_plugins(ExtractResourcePlugin::<DisplaySettings>::default())
+            .add_systems(Update, display_shortcuts);
     }
     fn finish(&self, app: &mut App) {
-                let render_app = app.sub_app_mut(RenderApp);
-                render_app.init_resource::<DisplayPipeline>()
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app.init_resource::<DisplayPipeline>();
+    }
+}
 
-                ;
-            }
+// 与 display.wgsl 的 MODE_* 一致
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum DisplayMode {
+    // 只显示细胞，不画流体场
+    #[default]
+    Cells = 0,
+    Density = 1,
+    Velocity = 2,
+    VelocityArrows = 3,
+    Pressure = 4,
+    Curl = 5,
+    Divergence = 6,
+    Burns = 7,
 }
-fn display_log(
-    mut fluid_textures: ResMut<FluidTextures>,
-    images: Res<Assets<Image>>,
-){
-    println!("display_log");
-    fluid_textures.log(&images);
+
+impl DisplayMode {
+    const ALL: [DisplayMode; 8] = [
+        DisplayMode::Cells,
+        DisplayMode::Density,
+        DisplayMode::Velocity,
+        DisplayMode::VelocityArrows,
+        DisplayMode::Pressure,
+        DisplayMode::Curl,
+        DisplayMode::Divergence,
+        DisplayMode::Burns,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            DisplayMode::Cells => "cells",
+            DisplayMode::Density => "density",
+            DisplayMode::Velocity => "velocity",
+            DisplayMode::VelocityArrows => "velocity arrows",
+            DisplayMode::Pressure => "pressure",
+            DisplayMode::Curl => "curl",
+            DisplayMode::Divergence => "divergence",
+            DisplayMode::Burns => "burns",
+        }
+    }
+
+    // 色带两端对应的值，可以用 - 和 = 调整
+    fn default_range(self) -> f32 {
+        match self {
+            DisplayMode::Cells => 1.0,
+            DisplayMode::Density | DisplayMode::Burns => 1.0,
+            DisplayMode::Velocity | DisplayMode::VelocityArrows => 100.0,
+            DisplayMode::Pressure => 50.0,
+            DisplayMode::Curl => 10.0,
+            DisplayMode::Divergence => 1.0,
+        }
+    }
+
+    fn step(self, forward: bool) -> DisplayMode {
+        let n = DisplayMode::ALL.len();
+        let i = self as usize;
+        DisplayMode::ALL[if forward { (i + 1) % n } else { (i + n - 1) % n }]
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone, Debug)]
+pub(crate) struct DisplaySettings {
+    pub(crate) mode: DisplayMode,
+    // true 时半透明地叠加在细胞上，false 时只显示流体场
+    pub(crate) overlay: bool,
+    // 每个模式的量程，下标是 DisplayMode 的判别值
+    pub(crate) ranges: [f32; 8],
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            mode: DisplayMode::Cells,
+            overlay: true,
+            ranges: DisplayMode::ALL.map(DisplayMode::default_range),
+        }
+    }
+}
+
+impl DisplaySettings {
+    pub(crate) fn range(&self) -> f32 {
+        self.ranges[self.mode as usize]
+    }
 }
+
+fn display_shortcuts(keys: Res<ButtonInput<KeyCode>>, mut settings: ResMut<DisplaySettings>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl {
+        return;
+    }
+    if keys.just_pressed(KeyCode::KeyV) {
+        settings.mode = settings.mode.step(!shift);
+        info!("display: {}", settings.mode.name());
+    }
+    if keys.just_pressed(KeyCode::KeyO) {
+        settings.overlay = !settings.overlay;
+        info!("display overlay: {}", settings.overlay);
+    }
+    let scale = if keys.just_pressed(KeyCode::Minus) {
+        0.5
+    } else if keys.just_pressed(KeyCode::Equal) {
+        2.0
+    } else {
+        return;
+    };
+    let mode = settings.mode;
+    settings.ranges[mode as usize] *= scale;
+    info!("display {} range: {}", mode.name(), settings.range());
+}
+
 #[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
 pub(crate) struct DisplayLabel;
 
-// Component, Clone, ExtractComponent
-#[derive(Resource,Clone,ExtractResource)]
+// 各个模式显示的纹理，由 insert_pass_images 在启动和窗口缩放时填写
+// 都是一帧里最后写入的那一份：速度取梯度减法的输出，密度取平流的输出，压力取求解的结果
+#[derive(Resource, Clone, ExtractResource)]
 pub struct DisplayTarget {
-    pub(crate) image: Handle<Image>,
+    pub(crate) density: Handle<Image>,
+    pub(crate) velocity: Handle<Image>,
+    pub(crate) pressure: Handle<Image>,
+    pub(crate) curl: Handle<Image>,
+    pub(crate) divergence: Handle<Image>,
+    pub(crate) burns: Handle<Image>,
+}
+
+impl DisplayTarget {
+    fn image(&self, mode: DisplayMode) -> Option<&Handle<Image>> {
+        match mode {
+            DisplayMode::Cells => None,
+            DisplayMode::Density => Some(&self.density),
+            DisplayMode::Velocity | DisplayMode::VelocityArrows => Some(&self.velocity),
+            DisplayMode::Pressure => Some(&self.pressure),
+            DisplayMode::Curl => Some(&self.curl),
+            DisplayMode::Divergence => Some(&self.divergence),
+            DisplayMode::Burns => Some(&self.burns),
+        }
+    }
+}
+
+// 与 display.wgsl 的 DisplayParams 一致
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, ShaderType)]
+struct DisplayParams {
+    mode: u32,
+    overlay: u32,
+    range: f32,
+    padding: f32,
 }
 
 #[derive(Default)]
 pub(crate) struct DisplayNode;
 
 impl ViewNode for DisplayNode {
-    type ViewQuery =
-    &'static ViewTarget
-        // &'static DisplayTarget
-
-     ;
+    type ViewQuery = &'static ViewTarget;
 
     fn run(
         &self,
@@ -78,6 +201,18 @@ impl ViewNode for DisplayNode {
         view_target: QueryItem<Self::ViewQuery>,
         world: &World,
     ) -> Result<(), NodeRunError> {
+        let settings = world.resource::<DisplaySettings>();
+        let Some(display_target) = world.get_resource::<DisplayTarget>() else {
+            return Ok(());
+        };
+        // 只显示细胞时 CellMaterial 的输出原样保留
+        let Some(field) = display_target.image(settings.mode) else {
+            return Ok(());
+        };
+        let gpu_images = world.resource::<RenderAssets<Image>>();
+        let Some(field) = gpu_images.get(field) else {
+            return Ok(());
+        };
 
         let pipeline_cache = world.resource::<PipelineCache>();
         let render_device = world.resource::<RenderDevice>();
@@ -87,28 +222,28 @@ impl ViewNode for DisplayNode {
             return Ok(());
         };
 
+        let params = DisplayParams {
+            mode: settings.mode as u32,
+            overlay: settings.overlay as u32,
+            range: settings.range(),
+            padding: 0.0,
+        };
+        let params_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("display_params_buffer"),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        // 输入是已经画好细胞的主纹理
         let post_process = view_target.post_process_write();
-        let sampler = render_device.create_sampler(&SamplerDescriptor {
-                    address_mode_u: AddressMode::ClampToEdge,
-                    address_mode_v: AddressMode::ClampToEdge,
-                    mag_filter: FilterMode::Linear,
-                    min_filter: FilterMode::Linear,
-                    ..Default::default()
-                });
-        // let gpu_images = world.resource::<RenderAssets<Image>>();
-        // let Some(output_tex) = gpu_images.get(&display_target.image) else {
-        //     return Ok(());
-        // };
         let bind_group = render_context.render_device().create_bind_group(
-            "post_process_bind_group",
+            "display_bind_group",
             &display_pipeline.layout,
-            // It's important for this to match the BindGroupLayout defined in the PostProcessPipeline
             &BindGroupEntries::sequential((
-                // 使用主渲染纹理作为输入
                 post_process.source,
-                // view_target.main_texture_view(),
-                // Use the sampler created for the pipeline
-                &sampler
+                &display_pipeline.sampler,
+                &field.texture_view,
+                params_buffer.as_entire_binding(),
             )),
         );
 
@@ -116,8 +251,6 @@ impl ViewNode for DisplayNode {
             label: Some("display_pass"),
             color_attachments: &[Some(RenderPassColorAttachment {
                 view: post_process.destination,
-                // 输出到独立纹理
-                // view:  &output_tex.texture_view,
                 resolve_target: None,
                 ops: Operations::default(),
             })],
@@ -137,42 +270,46 @@ impl ViewNode for DisplayNode {
 #[derive(Resource)]
 struct DisplayPipeline {
     layout: BindGroupLayout,
+    sampler: Sampler,
     pipeline_id: CachedRenderPipelineId,
 }
 
 impl FromWorld for DisplayPipeline {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
-        // let bind_group_layout = AdvectionImage::bind_group_layout(render_device);
+        // 流体场用 textureLoad 按纹素读取，r32float 之类不可过滤的格式也能绑定
         let bind_group_layout = render_device.create_bind_group_layout(
             "display_bind_group_layout",
             &BindGroupLayoutEntries::sequential(
                 ShaderStages::FRAGMENT,
                 (
                     texture_2d(TextureSampleType::Float { filterable: true }),
-                    sampler(SamplerBindingType::Filtering)
+                    sampler(SamplerBindingType::Filtering),
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    uniform_buffer::<DisplayParams>(false),
                 )
             ));
-        //
-        // let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
 
         let shader = world
             .resource::<AssetServer>()
             .load("display.wgsl");
 
-        let pipeline =world
+        let pipeline = world
             .resource_mut::<PipelineCache>()
-            // This will add the pipeline to the cache and queue it's creation
             .queue_render_pipeline(RenderPipelineDescriptor {
-                label: Some("post_process_pipeline".into()),
+                label: Some("display_pipeline".into()),
                 layout: vec![bind_group_layout.clone()],
-                // This will setup a fullscreen triangle for the vertex state
                 vertex: fullscreen_shader_vertex_state(),
                 fragment: Some(FragmentState {
                     shader,
                     shader_defs: vec![],
-                    // Make sure this matches the entry point of your shader.
-                    // It can be anything as long as it matches here and in the shader.
                     entry_point: "fragment".into(),
                     targets: vec![Some(ColorTargetState {
                         format: TextureFormat::bevy_default(),
@@ -180,8 +317,6 @@ impl FromWorld for DisplayPipeline {
                         write_mask: ColorWrites::ALL,
                     })],
                 }),
-                // All of the following properties are not important for this effect so just use the default values.
-                // This struct doesn't have the Default trait implemented because not all field can have a default value.
                 primitive: PrimitiveState::default(),
                 depth_stencil: None,
                 multisample: MultisampleState::default(),
@@ -190,8 +325,8 @@ impl FromWorld for DisplayPipeline {
 
         DisplayPipeline {
             layout: bind_group_layout,
-            pipeline_id:pipeline,
+            sampler,
+            pipeline_id: pipeline,
         }
     }
 }
-
@@ -4,10 +4,11 @@ use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
 use bevy::render::render_resource::*;
 use bevy::render::{render_graph, Render, RenderApp, RenderSet};
 use bevy::render::render_asset::RenderAssets;
-use bevy::render::render_graph::{NodeRunError, RenderGraph, RenderGraphContext, RenderLabel};
+use bevy::render::render_graph::{NodeRunError, RenderGraphContext, RenderLabel};
 use bevy::render::render_resource::binding_types::{sampler, texture_2d, texture_storage_2d, uniform_buffer};
 use bevy::render::renderer::{RenderContext, RenderDevice};
-use crate::{HEIGHT, WIDTH, WORKGROUP_SIZE};
+use crate::boundary::{boundary_buffer, BoundaryUniforms, FluidSpecies};
+use crate::{FluidConfig, FluidFormats, SimulationSize};
 
 pub struct DivergencePlugin;
 
@@ -47,15 +48,19 @@ impl FromWorld for DivergencePipeline {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
 
+        let formats = *world.resource::<FluidFormats>();
+
         let bind_group_layout = render_device.create_bind_group_layout(
             "divergence_bind_group_layout",
             &BindGroupLayoutEntries::sequential(
                 ShaderStages::COMPUTE,
                 (
                     texture_2d(TextureSampleType::Float { filterable: true }),
-                    texture_storage_2d(TextureFormat::Rgba8Unorm, StorageTextureAccess::WriteOnly),
+                    texture_storage_2d(formats.scalar, StorageTextureAccess::WriteOnly),
                     sampler(SamplerBindingType::Filtering),
-                    uniform_buffer::<DivergenceUniforms>(false)
+                    uniform_buffer::<DivergenceUniforms>(false),
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    uniform_buffer::<BoundaryUniforms>(false),
                 )
             ));
         let shader = world
@@ -67,7 +72,7 @@ impl FromWorld for DivergencePipeline {
             layout: vec![bind_group_layout.clone()],
             push_constant_ranges: Vec::new(),
             shader: shader.clone(),
-            shader_defs: vec![],
+            shader_defs: vec![FluidFormats::output_def(formats.scalar)],
             entry_point: Cow::from("divergence_main"),
         });
         DivergencePipeline {
@@ -76,12 +81,12 @@ impl FromWorld for DivergencePipeline {
         }
     }
 }
-#[derive(Resource, Clone, ExtractResource, AsBindGroup)]
+#[derive(Resource, Clone, ExtractResource)]
 pub struct DivergenceImage{
-    #[texture(0, visibility(compute))]
     pub(crate) velocity_tex: Handle<Image>,
-    #[storage_texture(1, image_format = Rgba8Unorm, access = WriteOnly)]
     pub(crate) output_tex: Handle<Image>,
+    // 判断固体用的细胞纹理
+    pub(crate) cells_tex: Handle<Image>,
 }
 
 #[derive(Resource)]
@@ -93,9 +98,13 @@ fn prepare_bind_group(
     divergence_image: Res<DivergenceImage>,
     render_device: Res<RenderDevice>,
     divergence_pipeline: Res<DivergencePipeline>,
+    size: Res<SimulationSize>,
+    fluid_config: Res<FluidConfig>,
+    fluid_species: Res<FluidSpecies>,
 ) {
     let velocity_tex_view = gpu_images.get(&divergence_image.velocity_tex).unwrap();
     let output_tex_view = gpu_images.get(&divergence_image.output_tex).unwrap();
+    let cells_tex_view = gpu_images.get(&divergence_image.cells_tex).unwrap();
 
     let sampler = render_device.create_sampler(&SamplerDescriptor {
         address_mode_u: AddressMode::ClampToEdge,
@@ -106,7 +115,7 @@ fn prepare_bind_group(
     });
 
     let uniforms = DivergenceUniforms {
-        texel_size: [1.0 / WIDTH as f32, 1.0 / HEIGHT as f32],
+        texel_size: size.texel_size(),
     };
 
     let uniform_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
@@ -115,6 +124,8 @@ fn prepare_bind_group(
         usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
     });
 
+    let boundary = boundary_buffer(&render_device, &fluid_config, *fluid_species);
+
     let bind_group = render_device.create_bind_group(
         Some("divergence_bind_group"),
         &divergence_pipeline.bind_group_layout,
@@ -128,7 +139,9 @@ fn prepare_bind_group(
                         buffer: &uniform_buffer,
                         offset: 0,
                         size: None,
-                    })
+                    }),
+                    &cells_tex_view.texture_view,
+                    boundary.as_entire_binding(),
                 )
             )
     );
@@ -145,7 +158,7 @@ pub(crate) struct DivergenceComputeNode;
 impl render_graph::Node for DivergenceComputeNode {
     fn run(
         &self,
-        graph: &mut RenderGraphContext,
+        _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
@@ -163,7 +176,8 @@ impl render_graph::Node for DivergenceComputeNode {
         if let Some(pipeline) = pipeline_cache.get_compute_pipeline(divergence_pipeline.pipeline) {
             pass.set_pipeline(pipeline);
             pass.set_bind_group(0, &divergence_bind_group.0, &[]);
-            pass.dispatch_workgroups(WIDTH / WORKGROUP_SIZE, HEIGHT / WORKGROUP_SIZE, 1);
+            let (workgroups_x, workgroups_y) = world.resource::<SimulationSize>().workgroups();
+            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
         }
 
         Ok(())
@@ -0,0 +1,263 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::{EdgeBoundary, FluidConfig, PressureSolver, SolidBoundary};
+
+// FluidConfig 的配置文件，TOML 格式，顶层每个键是一个字段，由 toml crate 按 FluidConfig 的 Deserialize 读入
+// 文件里没写的字段取 FluidConfig::default（tuned 的值），未知字段和无法解析的值报错并指出行号
+//   velocity_dissipation = 0.99
+//   pressure_iterations = 20
+//   pressure_solver = "multigrid"
+//   edge_boundary = "periodic"
+// 窗口模式下文件修改后自动重新加载，面板上调好的值用 Ctrl+S 写回（见 inspector.rs）
+
+pub(crate) const DEFAULT_CONFIG_PATH: &str = "assets/fluid_config.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FieldKind {
+    Float,
+    Integer,
+    // 枚举，按 f32 读写时是判别值，文件和面板上写名字，名字按判别值的顺序排列
+    Choice(&'static [&'static str]),
+}
+
+// 一个可调字段：文件里的键名、面板滑块的范围，以及按 f32 读写 FluidConfig 的函数
+pub(crate) struct ConfigField {
+    pub(crate) name: &'static str,
+    pub(crate) kind: FieldKind,
+    pub(crate) min: f32,
+    pub(crate) max: f32,
+    pub(crate) get: fn(&FluidConfig) -> f32,
+    pub(crate) set: fn(&mut FluidConfig, f32),
+}
+
+// FluidConfig 的全部字段，文件和面板都按这个顺序
+pub(crate) const FIELDS: [ConfigField; 9] = [
+    ConfigField {
+        name: "velocity_dissipation",
+        kind: FieldKind::Float,
+        min: 0.9,
+        max: 1.0,
+        get: |c| c.velocity_dissipation,
+        set: |c, v| c.velocity_dissipation = v,
+    },
+    ConfigField {
+        name: "density_dissipation",
+        kind: FieldKind::Float,
+        min: 0.9,
+        max: 1.0,
+        get: |c| c.density_dissipation,
+        set: |c, v| c.density_dissipation = v,
+    },
+    ConfigField {
+        name: "curl_strength",
+        kind: FieldKind::Float,
+        min: 0.0,
+        max: 10.0,
+        get: |c| c.curl_strength,
+        set: |c, v| c.curl_strength = v,
+    },
+    ConfigField {
+        name: "pressure_dissipation",
+        kind: FieldKind::Float,
+        min: 0.0,
+        max: 1.0,
+        get: |c| c.pressure_dissipation,
+        set: |c, v| c.pressure_dissipation = v,
+    },
+    ConfigField {
+        name: "pressure_iterations",
+        kind: FieldKind::Integer,
+        min: 0.0,
+        max: 100.0,
+        get: |c| c.pressure_iterations as f32,
+        set: |c, v| c.pressure_iterations = v as u32,
+    },
+    ConfigField {
+        name: "pressure_solver",
+        kind: FieldKind::Choice(&["jacobi", "multigrid"]),
+        min: 0.0,
+        max: 1.0,
+        get: |c| c.pressure_solver as u32 as f32,
+        set: |c, v| {
+            c.pressure_solver = if v >= 0.5 { PressureSolver::Multigrid } else { PressureSolver::Jacobi }
+        },
+    },
+    ConfigField {
+        name: "multigrid_cycles",
+        kind: FieldKind::Integer,
+        min: 0.0,
+        max: 8.0,
+        get: |c| c.multigrid_cycles as f32,
+        set: |c, v| c.multigrid_cycles = v as u32,
+    },
+    ConfigField {
+        name: "edge_boundary",
+        kind: FieldKind::Choice(&["closed", "open", "periodic"]),
+        min: 0.0,
+        max: 2.0,
+        get: |c| c.edge_boundary as u32 as f32,
+        set: |c, v| {
+            c.edge_boundary = match v as u32 {
+                0 => EdgeBoundary::Closed,
+                1 => EdgeBoundary::Open,
+                _ => EdgeBoundary::Periodic,
+            }
+        },
+    },
+    ConfigField {
+        name: "solid_boundary",
+        kind: FieldKind::Choice(&["no_slip", "free_slip"]),
+        min: 0.0,
+        max: 1.0,
+        get: |c| c.solid_boundary as u32 as f32,
+        set: |c, v| {
+            c.solid_boundary = if v >= 0.5 { SolidBoundary::FreeSlip } else { SolidBoundary::NoSlip }
+        },
+    },
+];
+
+impl ConfigField {
+    // 滑块位置 t ∈ [0, 1] 对应的值，整数和枚举取整
+    pub(crate) fn value_at(&self, t: f32) -> f32 {
+        let value = self.min + t.clamp(0.0, 1.0) * (self.max - self.min);
+        match self.kind {
+            FieldKind::Float => value,
+            FieldKind::Integer | FieldKind::Choice(_) => value.round(),
+        }
+    }
+
+    // 当前值在滑块上的位置，文件里超出范围的值贴在两端
+    pub(crate) fn fraction(&self, config: &FluidConfig) -> f32 {
+        (((self.get)(config) - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+
+    // 面板上显示的值
+    pub(crate) fn display(&self, config: &FluidConfig) -> String {
+        match self.kind {
+            FieldKind::Float => format!("{:.3}", (self.get)(config)),
+            FieldKind::Integer => format!("{}", (self.get)(config) as u32),
+            FieldKind::Choice(names) => names[(self.get)(config) as usize].to_string(),
+        }
+    }
+
+    // 文件里写出的值，浮点数用 {:?} 保证带小数点且能原样读回
+    fn to_toml(&self, config: &FluidConfig) -> String {
+        match self.kind {
+            FieldKind::Float => format!("{:?}", (self.get)(config)),
+            FieldKind::Integer => format!("{}", (self.get)(config) as u32),
+            FieldKind::Choice(names) => format!("\"{}\"", names[(self.get)(config) as usize]),
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl FluidConfig {
+    pub(crate) fn parse(text: &str) -> io::Result<FluidConfig> {
+        let config: FluidConfig = toml::from_str(text).map_err(|e| invalid(&e.to_string()))?;
+        // TOML 允许 nan 和 inf，流体参数里没有意义
+        match FIELDS.iter().find(|field| field.kind == FieldKind::Float && !(field.get)(&config).is_finite()) {
+            Some(field) => Err(invalid(&format!("{} must be finite", field.name))),
+            None => Ok(config),
+        }
+    }
+
+    pub(crate) fn to_toml(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# FluidConfig，窗口模式下修改后自动重新加载");
+        for field in FIELDS.iter() {
+            let _ = writeln!(out, "{} = {}", field.name, field.to_toml(self));
+        }
+        out
+    }
+
+    pub(crate) fn load<P: AsRef<Path>>(path: P) -> io::Result<FluidConfig> {
+        FluidConfig::parse(&fs::read_to_string(path)?)
+    }
+
+    pub(crate) fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_toml())
+    }
+}
+
+// --fluid-config PATH
+pub(crate) fn config_path(args: &[String]) -> Option<String> {
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--fluid-config" {
+            match iter.next() {
+                Some(value) => path = Some(value.clone()),
+                None => eprintln!("--fluid-config needs a path"),
+            }
+        }
+    }
+    path
+}
+
+// 启动时的配置：读取配置文件，没有文件或读不到时用 FluidConfig::tuned；
+// 命令行给了 --pressure-solver 时以命令行为准
+pub(crate) fn startup_config(path: Option<&str>, args: &[String]) -> FluidConfig {
+    let mut config = match path {
+        Some(path) => FluidConfig::load(path).unwrap_or_else(|e| {
+            eprintln!("failed to load {}: {}, using built-in fluid config", path, e);
+            FluidConfig::tuned()
+        }),
+        None => FluidConfig::tuned(),
+    };
+    if args.iter().any(|arg| arg == "--pressure-solver") {
+        config.pressure_solver = PressureSolver::from_args(args);
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_file_round_trips() {
+        let mut config = FluidConfig::tuned();
+        config.curl_strength = 4.25;
+        config.pressure_solver = PressureSolver::Multigrid;
+        config.multigrid_cycles = 3;
+        config.edge_boundary = EdgeBoundary::Periodic;
+        config.solid_boundary = SolidBoundary::FreeSlip;
+        let loaded = FluidConfig::parse(&config.to_toml()).unwrap();
+        for field in FIELDS.iter() {
+            assert_eq!((field.get)(&loaded), (field.get)(&config), "{}", field.name);
+        }
+    }
+
+    #[test]
+    fn missing_fields_keep_the_defaults_and_errors_name_the_line() {
+        let text = "# 只改密度\ndensity_dissipation = 0.95 # 烟散得快一些\ncurl_strength = 2\n";
+        let config = FluidConfig::parse(text).unwrap();
+        assert_eq!(config.density_dissipation, 0.95);
+        assert_eq!(config.curl_strength, 2.0);
+        assert_eq!(config.velocity_dissipation, FluidConfig::tuned().velocity_dissipation);
+        assert_eq!(config.solid_boundary, FluidConfig::tuned().solid_boundary);
+
+        let err = FluidConfig::parse("curl_strength = 2\nvorticity = 1\n").map(|_| ()).unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+        assert!(FluidConfig::parse("pressure_iterations = -1\n").is_err());
+        assert!(FluidConfig::parse("pressure_solver = \"sor\"\n").is_err());
+        assert!(FluidConfig::parse("curl_strength = nan\n").is_err());
+        assert_eq!(FluidConfig::parse("edge_boundary = \"periodic\"\n").unwrap().edge_boundary, EdgeBoundary::Periodic);
+    }
+
+    #[test]
+    fn slider_values_snap_for_integer_fields() {
+        let iterations = FIELDS.iter().find(|f| f.name == "pressure_iterations").unwrap();
+        assert_eq!(iterations.value_at(0.204), 20.0);
+        let solver = FIELDS.iter().find(|f| f.name == "pressure_solver").unwrap();
+        let mut config = FluidConfig::tuned();
+        (solver.set)(&mut config, solver.value_at(0.9));
+        assert_eq!(config.pressure_solver, PressureSolver::Multigrid);
+        assert_eq!(solver.fraction(&config), 1.0);
+    }
+}
@@ -0,0 +1,821 @@
+use bevy::prelude::Resource;
+use crate::{EdgeBoundary, FluidConfig, PressureSolver, SolidBoundary};
+use crate::universe::{Cell, CellGrid, SpeciesRegistry, Wind, WIND_ZERO};
+
+// 纯CPU的流体求解器，逐个对应GPU上的计算着色器：
+// advection.wgsl -> curl.wgsl -> vorticity.wgsl -> divergence.wgsl -> clear.wgsl -> pressure.wgsl -> gradient_subtract.wgsl
+// 最后按 velocity_out.wgsl 的编码把结果写回 CellGrid::winds。
+// 没有GPU的机器（CI、无窗口模式）可以用它跑风场耦合，也可以用来校验GPU各个pass的输出。
+//
+// 每个场都按纹理的行优先布局存放，第 i 个元素对应纹理的第 i 个像素，
+// 也就是 cells[i]/winds[i] 所在的细胞（坐标约定见 universe::coords）。
+
+// 梯度减法的风力系数和阻尼，与 gradient_subtract.rs 中的 uniform 一致
+const WIND_STRENGTH: f32 = -25.0;
+const DAMPING: f32 = 0.95;
+// wind.wgsl 的范围限制和缩放（WIND_MIN、WIND_MAX、WIND_SCALE），速度的零点是 WIND_ZERO
+const VELOCITY_OUT_MIN: f32 = -250.0;
+const VELOCITY_OUT_MAX: f32 = 250.0;
+const VELOCITY_OUT_SCALE: f32 = 500.0;
+
+// burns/winds 纹理的通道
+const WIND_DX: usize = 0;
+const WIND_DY: usize = 1;
+const WIND_PRESSURE: usize = 2;
+const WIND_DENSITY: usize = 3;
+
+// 单通道的标量场
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) data: Vec<f32>,
+}
+
+impl Field {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0.0; width * height],
+        }
+    }
+
+    // 越界坐标按 ClampToEdge 处理
+    pub fn get(&self, x: i32, y: i32) -> f32 {
+        let x = x.clamp(0, self.width as i32 - 1) as usize;
+        let y = y.clamp(0, self.height as i32 - 1) as usize;
+        self.data[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, v: f32) {
+        self.data[y * self.width + x] = v;
+    }
+
+    #[cfg(test)]
+    pub fn fill(&mut self, v: f32) {
+        self.data.iter_mut().for_each(|d| *d = v);
+    }
+
+    // 对应 textureSampleLevel(tex, sampler, uv, 0.0)，采样器为 Linear + ClampToEdge
+    pub fn sample(&self, u: f32, v: f32) -> f32 {
+        let tx = u * self.width as f32 - 0.5;
+        let ty = v * self.height as f32 - 0.5;
+        let x0 = tx.floor();
+        let y0 = ty.floor();
+        let fx = tx - x0;
+        let fy = ty - y0;
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let a = self.get(x0, y0);
+        let b = self.get(x0 + 1, y0);
+        let c = self.get(x0, y0 + 1);
+        let d = self.get(x0 + 1, y0 + 1);
+        let top = a + (b - a) * fx;
+        let bottom = c + (d - c) * fx;
+        top + (bottom - top) * fy
+    }
+}
+
+// 多重网格压力求解，与 multigrid.wgsl 一一对应
+// 第 0 层是原网格，每往下一层宽高减半（向上取整），最短边不超过 MULTIGRID_COARSEST 时停止
+// 算子是 5 点拉普拉斯 L + R + T + B - 4C，邻居按 Walls 的边界方式取值（与 pressure.wgsl 相同）；
+// 固体只在第 0 层处理，粗层把固体当作流体，得到的修正由第 0 层的平滑收拾
+// 平滑用带松弛的 Jacobi；限制取 2x2 子格残差的平均，格距加倍所以再乘 4；延拓把粗格的修正加到对应的 2x2 子格上
+pub const MULTIGRID_COARSEST: usize = 8;
+pub const MULTIGRID_PRE_SWEEPS: u32 = 2;
+pub const MULTIGRID_POST_SWEEPS: u32 = 2;
+pub const MULTIGRID_COARSE_SWEEPS: u32 = 32;
+pub const MULTIGRID_RELAXATION: f32 = 0.8;
+
+// 各层的尺寸，第 0 层是 (width, height)
+pub fn multigrid_levels(width: usize, height: usize) -> Vec<(usize, usize)> {
+    let mut levels = vec![(width, height)];
+    let (mut w, mut h) = (width, height);
+    while w.min(h) > MULTIGRID_COARSEST {
+        w = w.div_ceil(2);
+        h = h.div_ceil(2);
+        levels.push((w, h));
+    }
+    levels
+}
+
+// 一次 V 循环的工作量，按各层更新的纹素数累加：除最粗层外每层平滑 PRE + POST 次，
+// 限制在细层每个纹素算一次残差，延拓给细层每个纹素加一次修正；最粗层平滑 COARSE_SWEEPS 次。
+// 一次 Jacobi 迭代的工作量是 width * height
+pub fn multigrid_cycle_work(width: usize, height: usize) -> usize {
+    let levels = multigrid_levels(width, height);
+    let (coarsest, finer) = levels.split_last().unwrap();
+    let smooth = (MULTIGRID_PRE_SWEEPS + MULTIGRID_POST_SWEEPS) as usize;
+    let finer: usize = finer.iter().map(|(w, h)| w * h * (smooth + 2)).sum();
+    finer + coarsest.0 * coarsest.1 * MULTIGRID_COARSE_SWEEPS as usize
+}
+
+// 边界处理，与 assets/boundary.wgsl 一一对应
+// solids 是逐格的固体标记（SpeciesDef::solid），多重网格的粗层传空切片
+#[derive(Clone, Copy)]
+struct Walls<'a> {
+    edge: EdgeBoundary,
+    solid: SolidBoundary,
+    solids: &'a [bool],
+}
+
+impl Walls<'_> {
+    // boundary.wgsl 的 neighbour：periodic 时绕回对边，否则落在域外时返回 None
+    fn neighbour(&self, width: usize, height: usize, x: usize, y: usize, dx: i32, dy: i32) -> Option<(usize, usize)> {
+        let (w, h) = (width as i32, height as i32);
+        let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+        if self.edge == EdgeBoundary::Periodic {
+            return Some((nx.rem_euclid(w) as usize, ny.rem_euclid(h) as usize));
+        }
+        if nx < 0 || ny < 0 || nx >= w || ny >= h {
+            return None;
+        }
+        Some((nx as usize, ny as usize))
+    }
+
+    fn is_solid(&self, width: usize, x: usize, y: usize) -> bool {
+        self.solids.get(y * width + x).copied().unwrap_or(false)
+    }
+
+    // boundary.wgsl 的 neighbour_pressure：固体和封闭的边取本格的值（法向梯度为 0），开放的边为 0
+    fn pressure(&self, p: &Field, x: usize, y: usize, dx: i32, dy: i32) -> f32 {
+        let c = p.data[y * p.width + x];
+        match self.neighbour(p.width, p.height, x, y, dx, dy) {
+            None if self.edge == EdgeBoundary::Open => 0.0,
+            None => c,
+            Some((nx, ny)) if self.is_solid(p.width, nx, ny) => c,
+            Some((nx, ny)) => p.data[ny * p.width + nx],
+        }
+    }
+
+    // boundary.wgsl 的 neighbour_velocity：固体和封闭的边取本格速度的相反数，使边界上的法向速度为 0，
+    // 开放的边取本格的值
+    fn velocity(&self, v: &Field, x: usize, y: usize, dx: i32, dy: i32) -> f32 {
+        let c = v.data[y * v.width + x];
+        match self.neighbour(v.width, v.height, x, y, dx, dy) {
+            None if self.edge == EdgeBoundary::Open => c,
+            None => -c,
+            Some((nx, ny)) if self.is_solid(v.width, nx, ny) => -c,
+            Some((nx, ny)) => v.data[ny * v.width + nx],
+        }
+    }
+
+    // boundary.wgsl 的 blocked：邻居是固体或封闭的边
+    fn blocked(&self, width: usize, height: usize, x: usize, y: usize, dx: i32, dy: i32) -> bool {
+        match self.neighbour(width, height, x, y, dx, dy) {
+            None => self.edge == EdgeBoundary::Closed,
+            Some((nx, ny)) => self.is_solid(width, nx, ny),
+        }
+    }
+
+    // boundary.wgsl 的 wall_velocity：贴着固体或封闭边的流体格，free-slip 去掉朝向边界的分量，no-slip 速度为 0
+    fn wall_velocity(&self, width: usize, height: usize, x: usize, y: usize, vx: f32, vy: f32) -> (f32, f32) {
+        let x_blocked = self.blocked(width, height, x, y, -1, 0) || self.blocked(width, height, x, y, 1, 0);
+        let y_blocked = self.blocked(width, height, x, y, 0, -1) || self.blocked(width, height, x, y, 0, 1);
+        if self.solid == SolidBoundary::NoSlip && (x_blocked || y_blocked) {
+            return (0.0, 0.0);
+        }
+        (if x_blocked { 0.0 } else { vx }, if y_blocked { 0.0 } else { vy })
+    }
+
+    fn laplacian(&self, p: &Field, x: usize, y: usize) -> f32 {
+        self.pressure(p, x, y, -1, 0)
+            + self.pressure(p, x, y, 1, 0)
+            + self.pressure(p, x, y, 0, -1)
+            + self.pressure(p, x, y, 0, 1)
+            - 4.0 * p.data[y * p.width + x]
+    }
+
+    // 粗层没有固体
+    fn coarse(&self) -> Self {
+        Walls { solids: &[], ..*self }
+    }
+}
+
+// multigrid.wgsl 的 smooth_main：p = (1 - ω) p + ω (L + R + T + B - rhs) / 4
+fn relax(p: &Field, rhs: &Field, walls: Walls, sweeps: u32) -> Field {
+    let mut p = p.clone();
+    for _ in 0..sweeps {
+        let mut out = Field::new(p.width, p.height);
+        for y in 0..p.height {
+            for x in 0..p.width {
+                let c = p.get(x as i32, y as i32);
+                let jacobi = (walls.laplacian(&p, x, y) + 4.0 * c - rhs.get(x as i32, y as i32)) * 0.25;
+                out.set(x, y, c + MULTIGRID_RELAXATION * (jacobi - c));
+            }
+        }
+        p = out;
+    }
+    p
+}
+
+// multigrid.wgsl 的 restrict_main：粗一层的右端项
+fn restrict_residual(p: &Field, rhs: &Field, walls: Walls, width: usize, height: usize) -> Field {
+    let mut coarse = Field::new(width, height);
+    for cy in 0..height {
+        for cx in 0..width {
+            let mut sum = 0.0;
+            let mut count = 0.0;
+            for y in cy * 2..(cy * 2 + 2).min(p.height) {
+                for x in cx * 2..(cx * 2 + 2).min(p.width) {
+                    sum += rhs.get(x as i32, y as i32) - walls.laplacian(p, x, y);
+                    count += 1.0;
+                }
+            }
+            coarse.set(cx, cy, 4.0 * sum / count);
+        }
+    }
+    coarse
+}
+
+// multigrid.wgsl 的 prolongate_main
+fn prolongate(p: &mut Field, correction: &Field) {
+    for y in 0..p.height {
+        for x in 0..p.width {
+            let e = correction.get((x / 2) as i32, (y / 2) as i32);
+            p.data[y * p.width + x] += e;
+        }
+    }
+}
+
+fn v_cycle(p: &Field, rhs: &Field, levels: &[(usize, usize)], walls: Walls) -> Field {
+    let Some(&(width, height)) = levels.get(1) else {
+        return relax(p, rhs, walls, MULTIGRID_COARSE_SWEEPS);
+    };
+    let mut p = relax(p, rhs, walls, MULTIGRID_PRE_SWEEPS);
+    let coarse_rhs = restrict_residual(&p, rhs, walls, width, height);
+    let correction = v_cycle(&Field::new(width, height), &coarse_rhs, &levels[1..], walls.coarse());
+    prolongate(&mut p, &correction);
+    relax(&p, rhs, walls, MULTIGRID_POST_SWEEPS)
+}
+
+// rgba8unorm 写入时的量化
+fn to_unorm8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Wind 字节与浮点场之间的编解码，与 assets/wind.wgsl 一一对应
+// burns 是非负的发射强度，按 unorm 原样读入
+pub fn decode_burns(wind: Wind) -> [f32; 4] {
+    [wind.dx, wind.dy, wind.pressure, wind.density].map(|b| b as f32 / 255.0)
+}
+
+// velocity_out 的编码：速度限幅缩放后加在 WIND_ZERO 上，静止正好是 WIND_ZERO；压力只做缩放，密度原样
+pub fn encode_wind(vx: f32, vy: f32, pressure: f32, density: f32) -> Wind {
+    let encode = |v: f32| v.clamp(VELOCITY_OUT_MIN, VELOCITY_OUT_MAX) / VELOCITY_OUT_SCALE;
+    let zero = WIND_ZERO as f32 / 255.0;
+    Wind {
+        dx: to_unorm8(encode(vx) + zero),
+        dy: to_unorm8(encode(vy) + zero),
+        pressure: to_unorm8(encode(pressure)),
+        density: to_unorm8(density),
+    }
+}
+
+// encode_wind 的逆，精度受字节量化限制（约 2 个单位），WIND_ZERO 解码成 0
+#[cfg(test)]
+pub fn decode_wind(wind: Wind) -> (f32, f32) {
+    let decode = |b: u8| (b as f32 - WIND_ZERO as f32) / 255.0 * VELOCITY_OUT_SCALE;
+    (decode(wind.dx), decode(wind.dy))
+}
+
+fn clamp_uv(v: f32) -> f32 {
+    v.clamp(0.0, 1.0)
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct FluidSolver {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    // 速度的 x、y 分量
+    pub(crate) velocity: (Field, Field),
+    pub(crate) density: Field,
+    pub(crate) pressure: Field,
+    pub(crate) curl: Field,
+    pub(crate) divergence: Field,
+    // 每步从 CellGrid 载入的输入：burns 的四个通道
+    burns: [Field; 4],
+    // 每步从 FluidConfig 和物质注册表取的边界方式，solids[i] 是第 i 格的物质是否为固体
+    // undamped[i] 是第 i 格的物质是否保持风速（SpeciesDef::undamped）
+    edge_boundary: EdgeBoundary,
+    solid_boundary: SolidBoundary,
+    solids: Vec<bool>,
+    undamped: Vec<bool>,
+    // 最近一次压力求解后的残差，见 pressure_residual
+    pub(crate) residual: f32,
+}
+
+impl FluidSolver {
+    pub fn new(width: usize, height: usize) -> Self {
+        let field = Field::new(width, height);
+        Self {
+            width,
+            height,
+            velocity: (field.clone(), field.clone()),
+            density: field.clone(),
+            pressure: field.clone(),
+            curl: field.clone(),
+            divergence: field.clone(),
+            burns: [field.clone(), field.clone(), field.clone(), field],
+            edge_boundary: EdgeBoundary::default(),
+            solid_boundary: SolidBoundary::default(),
+            solids: vec![false; width * height],
+            undamped: vec![false; width * height],
+            residual: 0.0,
+        }
+    }
+
+    fn texel_size(&self) -> (f32, f32) {
+        (1.0 / self.width as f32, 1.0 / self.height as f32)
+    }
+
+    fn uv(&self, x: usize, y: usize) -> (f32, f32) {
+        (x as f32 / self.width as f32, y as f32 / self.height as f32)
+    }
+
+    fn walls(&self) -> Walls<'_> {
+        Walls {
+            edge: self.edge_boundary,
+            solid: self.solid_boundary,
+            solids: &self.solids,
+        }
+    }
+
+    // 与上传到 burns 纹理和 cells 纹理的数据相同，逐格的标记按 boundary.wgsl 的方式从两个掩码取
+    pub fn load_inputs(&mut self, burns: &[Wind], cells: &[Cell], registry: &SpeciesRegistry) {
+        assert_eq!(burns.len(), self.width * self.height);
+        assert_eq!(cells.len(), self.width * self.height);
+        for (i, wind) in burns.iter().enumerate() {
+            for (channel, value) in decode_burns(*wind).into_iter().enumerate() {
+                self.burns[channel].data[i] = value;
+            }
+        }
+        let in_mask = |mask: u32, cell: &Cell| mask.checked_shr(cell.species.index() as u32).is_some_and(|m| m & 1 != 0);
+        let (solid_mask, undamped_mask) = (registry.solid_mask(), registry.undamped_mask());
+        for (i, cell) in cells.iter().enumerate() {
+            self.solids[i] = in_mask(solid_mask, cell);
+            self.undamped[i] = in_mask(undamped_mask, cell);
+        }
+    }
+
+    // 完整的一步，顺序与渲染图中的计算节点一致
+    pub fn step(&mut self, config: &FluidConfig, dt: f32, burns: &[Wind], cells: &[Cell], registry: &SpeciesRegistry) {
+        self.load_inputs(burns, cells, registry);
+        self.edge_boundary = config.edge_boundary;
+        self.solid_boundary = config.solid_boundary;
+        self.advect_velocity(dt, config.velocity_dissipation);
+        self.advect_density(dt, config.density_dissipation);
+        self.compute_curl();
+        self.apply_vorticity(dt, config.curl_strength);
+        self.compute_divergence();
+        self.clear_pressure(config.pressure_dissipation);
+        match config.pressure_solver {
+            PressureSolver::Jacobi => self.solve_pressure_iterations(config.pressure_iterations),
+            PressureSolver::Multigrid => self.solve_pressure_multigrid(config.multigrid_cycles),
+        }
+        self.subtract_gradient();
+    }
+
+    // 用 CellGrid 的 burns 驱动一步，再把结果写回 winds
+    pub fn step_grid(&mut self, config: &FluidConfig, dt: f32, grid: &mut CellGrid) {
+        self.step(config, dt, &grid.burns, &grid.cells, grid.registry());
+        self.write_winds(&mut grid.winds);
+        grid.refresh_windy_chunks();
+    }
+
+    // advection.wgsl：沿速度回溯采样，并加上 burns 的密度
+    pub fn advect_velocity(&mut self, dt: f32, dissipation: f32) {
+        let (tx, ty) = self.texel_size();
+        let mut out_x = Field::new(self.width, self.height);
+        let mut out_y = Field::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (u, v) = self.uv(x, y);
+                let vx = self.velocity.0.sample(u, v);
+                let vy = self.velocity.1.sample(u, v);
+                let (cu, cv) = (u - dt * vx * tx, v - dt * vy * ty);
+                let density = self.burns[WIND_DENSITY].sample(u, v);
+                out_x.set(x, y, dissipation * (self.velocity.0.sample(cu, cv) + density));
+                out_y.set(x, y, dissipation * self.velocity.1.sample(cu, cv));
+            }
+        }
+        self.velocity = (out_x, out_y);
+    }
+
+    pub fn advect_density(&mut self, dt: f32, dissipation: f32) {
+        let (tx, ty) = self.texel_size();
+        let mut out = Field::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (u, v) = self.uv(x, y);
+                let vx = self.velocity.0.sample(u, v);
+                let vy = self.velocity.1.sample(u, v);
+                let (cu, cv) = (u - dt * vx * tx, v - dt * vy * ty);
+                let density = self.burns[WIND_DENSITY].sample(u, v);
+                out.set(x, y, dissipation * (self.density.sample(cu, cv) + density));
+            }
+        }
+        self.density = out;
+    }
+
+    // curl.wgsl
+    pub fn compute_curl(&mut self) {
+        let (tx, ty) = self.texel_size();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (u, v) = self.uv(x, y);
+                let l = self.velocity.1.sample(u - tx, v);
+                let r = self.velocity.1.sample(u + tx, v);
+                let t = self.velocity.0.sample(u, v - ty);
+                let b = self.velocity.0.sample(u, v + ty);
+                self.curl.set(x, y, r - l - t + b);
+            }
+        }
+    }
+
+    // vorticity.wgsl：涡度约束力
+    pub fn apply_vorticity(&mut self, dt: f32, curl_strength: f32) {
+        let (_, ty) = self.texel_size();
+        let mut out_x = Field::new(self.width, self.height);
+        let mut out_y = Field::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (u, v) = self.uv(x, y);
+                let t = self.curl.sample(u, clamp_uv(v + ty));
+                let b = self.curl.sample(u, clamp_uv(v - ty));
+                let c = self.curl.sample(u, v);
+
+                let mut force = t.abs() - b.abs();
+                let length_force = force.abs() + 0.00001;
+                force *= (curl_strength * c) / length_force;
+
+                out_x.set(x, y, self.velocity.0.sample(u, v) + force * dt);
+                out_y.set(x, y, self.velocity.1.sample(u, v));
+            }
+        }
+        self.velocity = (out_x, out_y);
+    }
+
+    // divergence.wgsl
+    pub fn compute_divergence(&mut self) {
+        let walls = self.walls();
+        let mut out = Field::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let l = walls.velocity(&self.velocity.0, x, y, -1, 0);
+                let r = walls.velocity(&self.velocity.0, x, y, 1, 0);
+                let t = walls.velocity(&self.velocity.1, x, y, 0, 1);
+                let b = walls.velocity(&self.velocity.1, x, y, 0, -1);
+                out.set(x, y, 0.5 * (r - l + t - b));
+            }
+        }
+        self.divergence = out;
+    }
+
+    // clear.wgsl：衰减上一帧的压力，并叠加 burns 的压力
+    pub fn clear_pressure(&mut self, value: f32) {
+        let mut out = Field::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (u, v) = self.uv(x, y);
+                let mut pressure = self.burns[WIND_PRESSURE].sample(u, v);
+                pressure *= 512.0;
+                pressure *= pressure;
+                out.set(x, y, value * (self.pressure.sample(u, v) + pressure));
+            }
+        }
+        self.pressure = out;
+    }
+
+    // pressure.wgsl：一次 Jacobi 迭代
+    pub fn solve_pressure(&mut self) {
+        let walls = self.walls();
+        let mut out = Field::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let l = walls.pressure(&self.pressure, x, y, -1, 0);
+                let r = walls.pressure(&self.pressure, x, y, 1, 0);
+                let t = walls.pressure(&self.pressure, x, y, 0, 1);
+                let b = walls.pressure(&self.pressure, x, y, 0, -1);
+                let div = self.divergence.get(x as i32, y as i32);
+                out.set(x, y, (l + r + b + t - div) * 0.25);
+            }
+        }
+        self.pressure = out;
+    }
+
+    // 与 PressureComputeNode 相同：做 iterations 次 Jacobi 迭代，然后记录残差
+    pub fn solve_pressure_iterations(&mut self, iterations: u32) {
+        for _ in 0..iterations {
+            self.solve_pressure();
+        }
+        self.residual = self.pressure_residual();
+    }
+
+    // 与 encode_multigrid 相同：从当前压力出发做 cycles 次 V 循环，然后记录残差
+    // 散度直接当作第 0 层的右端项
+    pub fn solve_pressure_multigrid(&mut self, cycles: u32) {
+        let levels = multigrid_levels(self.width, self.height);
+        for _ in 0..cycles {
+            self.pressure = v_cycle(&self.pressure, &self.divergence, &levels, self.walls());
+        }
+        self.residual = self.pressure_residual();
+    }
+
+    // pressure.wgsl 的 residual_main：每个像素 |L + R + T + B - 4C - div| 的平均值，迭代收敛时趋向 0
+    // L、R、T、B 按 solve_pressure 的边界方式取值
+    pub fn pressure_residual(&self) -> f32 {
+        let walls = self.walls();
+        let mut sum = 0.0;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let div = self.divergence.get(x as i32, y as i32);
+                sum += (walls.laplacian(&self.pressure, x, y) - div).abs();
+            }
+        }
+        sum / (self.width * self.height) as f32
+    }
+
+    // gradient_subtract.wgsl：减去压力梯度，加上风力，并按格里的物质和边界方式处理速度
+    pub fn subtract_gradient(&mut self) {
+        let walls = self.walls();
+        let mut out_x = Field::new(self.width, self.height);
+        let mut out_y = Field::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (u, v) = self.uv(x, y);
+                let l = walls.pressure(&self.pressure, x, y, -1, 0);
+                let r = walls.pressure(&self.pressure, x, y, 1, 0);
+                let t = walls.pressure(&self.pressure, x, y, 0, 1);
+                let b = walls.pressure(&self.pressure, x, y, 0, -1);
+
+                let mut vx = self.velocity.0.sample(u, v) - (r - l);
+                let mut vy = self.velocity.1.sample(u, v) - (t - b);
+                vx += self.burns[WIND_DX].sample(u, v) * WIND_STRENGTH;
+                vy += self.burns[WIND_DY].sample(u, v) * WIND_STRENGTH;
+
+                if walls.is_solid(self.width, x, y) {
+                    vx = 0.0;
+                    vy = 0.0;
+                } else {
+                    if !self.undamped[y * self.width + x] {
+                        vx *= DAMPING;
+                        vy *= DAMPING;
+                    }
+                    (vx, vy) = walls.wall_velocity(self.width, self.height, x, y, vx, vy);
+                }
+                out_x.set(x, y, vx);
+                out_y.set(x, y, vy);
+            }
+        }
+        self.velocity = (out_x, out_y);
+    }
+
+    // velocity_out.wgsl 的编码：速度限幅后偏移到 [0, 1]，压力只做缩放
+    // 密度通道在GPU上没有输出，这里顺带写入密度场
+    pub fn write_winds(&self, winds: &mut [Wind]) {
+        assert_eq!(winds.len(), self.width * self.height);
+        for (i, wind) in winds.iter_mut().enumerate() {
+            *wind = encode_wind(
+                self.velocity.0.data[i],
+                self.velocity.1.data[i],
+                self.pressure.data[i],
+                self.density.data[i],
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::universe::Species;
+
+    // 一正一负两块散度，总和为 0，压力从 0 开始迭代
+    fn solver_with_divergence() -> FluidSolver {
+        let mut solver = FluidSolver::new(32, 32);
+        for y in 8..14 {
+            for x in 8..14 {
+                solver.divergence.set(x, y, 0.5);
+                solver.divergence.set(x + 10, y + 10, -0.5);
+            }
+        }
+        solver
+    }
+
+    // 在像素中心取邻居时算子是封闭边界下真正的 5 点拉普拉斯，Jacobi 最慢的模态每次只衰减 cos(π/32) ≈ 0.995，
+    // 缩小 10 倍要近 500 次迭代，所以这里从第 20 次跑到第 1000 次
+    #[test]
+    fn jacobi_iterations_reduce_the_residual() {
+        let mut solver = solver_with_divergence();
+        let initial = solver.pressure_residual();
+        solver.solve_pressure_iterations(20);
+        let after_20 = solver.residual;
+        solver.solve_pressure_iterations(980);
+        assert!(after_20 < initial, "{} -> {}", initial, after_20);
+        assert!(solver.residual < after_20 * 0.1, "{} -> {}", after_20, solver.residual);
+    }
+
+    // 32x32 上一次 V 循环的工作量是 9728 个纹素更新（见 multigrid_cycle_work），4 个 V 循环约合 38 次 Jacobi
+    #[test]
+    fn multigrid_beats_jacobi_at_equal_work() {
+        let cycles = 4;
+        let iterations = (cycles * multigrid_cycle_work(32, 32)).div_ceil(32 * 32) as u32;
+        assert_eq!(iterations, 38);
+        let mut jacobi = solver_with_divergence();
+        jacobi.solve_pressure_iterations(iterations);
+        let mut multigrid = solver_with_divergence();
+        multigrid.solve_pressure_multigrid(cycles as u32);
+        assert!(
+            multigrid.residual < jacobi.residual * 0.1,
+            "multigrid {} jacobi {}",
+            multigrid.residual,
+            jacobi.residual
+        );
+    }
+
+    // 负的速度编码到 WIND_ZERO 以下，解码后符号不变，blow_wind 看到的方向也一致
+    #[test]
+    fn wind_encoding_keeps_the_sign_of_velocity() {
+        let wind = encode_wind(-100.0, 60.0, 0.0, 0.0);
+        assert!(wind.dx < WIND_ZERO && wind.dy > WIND_ZERO, "{:?}", wind);
+        let (vx, vy) = decode_wind(wind);
+        assert!((vx + 100.0).abs() < 2.0, "{}", vx);
+        assert!((vy - 60.0).abs() < 2.0, "{}", vy);
+        // WIND_ZERO 不在正中，负方向在字节 0 处饱和，比 VELOCITY_OUT_MIN 少几个单位
+        let wind = encode_wind(-1000.0, 1000.0, 0.0, 0.0);
+        assert_eq!(wind.dx, 0);
+        let (vx, vy) = decode_wind(wind);
+        assert!((vx - VELOCITY_OUT_MIN).abs() < 4.0 && (vy - VELOCITY_OUT_MAX).abs() < 2.0, "{} {}", vx, vy);
+    }
+
+    // 静止的风来回编解码都是精确的 0，velocity_out -> gradient_subtract 的反馈里不会积累偏差
+    #[test]
+    fn calm_wind_round_trips_to_zero() {
+        let wind = encode_wind(0.0, 0.0, 0.0, 0.0);
+        assert_eq!((wind.dx, wind.dy), (WIND_ZERO, WIND_ZERO));
+        assert_eq!(decode_wind(wind), (0.0, 0.0));
+        assert_eq!(decode_wind(crate::universe::CALM_WIND), (0.0, 0.0));
+    }
+
+    #[test]
+    fn multigrid_levels_halve_down_to_the_coarsest() {
+        assert_eq!(multigrid_levels(32, 32), vec![(32, 32), (16, 16), (8, 8)]);
+        assert_eq!(multigrid_levels(600, 300), vec![(600, 300), (300, 150), (150, 75), (75, 38), (38, 19), (19, 10), (10, 5)]);
+        assert_eq!(multigrid_levels(8, 100), vec![(8, 100)]);
+    }
+
+    // 一列墙把网格分成两半，左边向右吹：墙里速度为 0，散度里墙面不透流；free-slip 时贴墙的格保留沿墙的分量
+    #[test]
+    fn solid_cells_block_the_flow() {
+        let mut solver = FluidSolver::new(16, 16);
+        for y in 0..16 {
+            solver.solids[y * 16 + 8] = true;
+        }
+        solver.solid_boundary = SolidBoundary::FreeSlip;
+        solver.velocity.0.fill(10.0);
+        solver.velocity.1.fill(3.0);
+        solver.compute_divergence();
+        // 墙左边的格：右邻居取 -10，散度为 0.5 * (-10 - 10)
+        assert_eq!(solver.divergence.get(7, 8), -10.0);
+        assert_eq!(solver.divergence.get(4, 8), 0.0);
+        solver.subtract_gradient();
+        assert_eq!((solver.velocity.0.get(8, 8), solver.velocity.1.get(8, 8)), (0.0, 0.0));
+        assert_eq!(solver.velocity.0.get(7, 8), 0.0);
+        assert!(solver.velocity.1.get(7, 8) != 0.0);
+
+        solver.solid_boundary = SolidBoundary::NoSlip;
+        solver.velocity.1.fill(3.0);
+        solver.subtract_gradient();
+        assert_eq!(solver.velocity.1.get(7, 8), 0.0);
+    }
+
+    // 开放的边压力为 0，封闭的边不约束压力的值；periodic 时左右两边是邻居
+    #[test]
+    fn edge_modes_change_the_pressure_neighbours() {
+        let mut solver = FluidSolver::new(16, 16);
+        solver.pressure.fill(1.0);
+        solver.pressure.set(15, 4, 5.0);
+        let walls = solver.walls();
+        assert_eq!(walls.pressure(&solver.pressure, 0, 4, -1, 0), 1.0);
+        solver.edge_boundary = EdgeBoundary::Open;
+        assert_eq!(solver.walls().pressure(&solver.pressure, 0, 4, -1, 0), 0.0);
+        solver.edge_boundary = EdgeBoundary::Periodic;
+        assert_eq!(solver.walls().pressure(&solver.pressure, 0, 4, -1, 0), 5.0);
+
+        // periodic 时均匀的流动没有散度，封闭时贴边的格有
+        solver.velocity.0.fill(10.0);
+        solver.compute_divergence();
+        assert!(solver.divergence.data.iter().all(|d| *d == 0.0));
+        solver.edge_boundary = EdgeBoundary::Closed;
+        solver.compute_divergence();
+        assert_eq!(solver.divergence.get(15, 4), -10.0);
+    }
+
+    // 只在第一步发射密度，之后的步从上一步的密度场平流，密度一直留在 winds 里
+    #[test]
+    fn emitted_density_persists_across_steps() {
+        let config = FluidConfig::tuned();
+        let mut grid = CellGrid::new(16, 16);
+        let mut solver = FluidSolver::new(16, 16);
+        grid.burns[8 * 16 + 8].density = 255;
+        solver.step_grid(&config, 1.0, &mut grid);
+        let emitted: f32 = solver.density.data.iter().sum();
+        assert!(emitted > 0.0);
+
+        grid.burns[8 * 16 + 8].density = 0;
+        for _ in 0..10 {
+            solver.step_grid(&config, 1.0, &mut grid);
+        }
+        let left: f32 = solver.density.data.iter().sum();
+        assert!(left > emitted * 0.5, "{} -> {}", emitted, left);
+        assert!(grid.winds.iter().any(|w| w.density > 0));
+    }
+
+    // 照 gradient_subtract.wgsl 逐行算一遍，与 subtract_gradient 比较：
+    // 物质按注册表的 solid_mask、undamped_mask 查 id，四周是封闭的边，固体按 free-slip 处理
+    #[test]
+    fn gradient_subtract_matches_the_shader_formula() {
+        const N: i32 = 8;
+        let mut grid = CellGrid::new(N, N);
+        for (x, y, species) in [(3, 2, Species::Wall), (1, 4, Species::Gas), (4, 4, Species::Sand), (2, 1, Species::Fire), (5, 5, Species::Water)] {
+            grid.cells[(y * N + x) as usize].species = species;
+        }
+        for (i, burn) in grid.burns.iter_mut().enumerate().step_by(3) {
+            burn.dx = (i * 37 % 256) as u8;
+            burn.dy = (i * 91 % 256) as u8;
+        }
+        let mut solver = FluidSolver::new(N as usize, N as usize);
+        solver.solid_boundary = SolidBoundary::FreeSlip;
+        solver.load_inputs(&grid.burns, &grid.cells, grid.registry());
+        for i in 0..(N * N) as usize {
+            solver.pressure.data[i] = (i * 7 % 11) as f32 * 0.3 - 1.0;
+            solver.velocity.0.data[i] = (i * 5 % 13) as f32 - 6.0;
+            solver.velocity.1.data[i] = (i * 3 % 7) as f32 - 3.0;
+        }
+        let (pressure, velocity) = (solver.pressure.clone(), solver.velocity.clone());
+        solver.subtract_gradient();
+
+        let registry = grid.registry();
+        let species_in = |mask: u32, x: i32, y: i32| {
+            let species = grid.cells[(y * N + x) as usize].species.id() as u32;
+            species < 32 && (mask >> species) & 1 != 0
+        };
+        let is_solid = |x: i32, y: i32| species_in(registry.solid_mask(), x, y);
+        let outside = |x: i32, y: i32| x < 0 || y < 0 || x >= N || y >= N;
+        // 线性采样在 uv = id / size 处正好落在四个纹素的公共角上，越界按 ClampToEdge
+        let sample = |values: &dyn Fn(i32, i32) -> f32, x: i32, y: i32| {
+            (values(x, y) + values((x - 1).max(0), y) + values(x, (y - 1).max(0)) + values((x - 1).max(0), (y - 1).max(0))) / 4.0
+        };
+        let burn = |x: i32, y: i32, channel: fn(&Wind) -> u8| channel(&grid.burns[(y * N + x) as usize]) as f32 / 255.0;
+        for y in 0..N {
+            for x in 0..N {
+                let load = |f: &Field, x: i32, y: i32| f.data[(y * N + x) as usize];
+                let c = load(&pressure, x, y);
+                let neighbour_pressure = |dx: i32, dy: i32| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if outside(nx, ny) || is_solid(nx, ny) { c } else { load(&pressure, nx, ny) }
+                };
+                let (l, r, t, b) = (neighbour_pressure(-1, 0), neighbour_pressure(1, 0), neighbour_pressure(0, 1), neighbour_pressure(0, -1));
+                let vel = (
+                    sample(&|x, y| load(&velocity.0, x, y), x, y),
+                    sample(&|x, y| load(&velocity.1, x, y), x, y),
+                );
+                let wind = (sample(&|x, y| burn(x, y, |w| w.dx), x, y), sample(&|x, y| burn(x, y, |w| w.dy), x, y));
+                let mut new_vel = (vel.0 - (r - l) + wind.0 * -25.0, vel.1 - (t - b) + wind.1 * -25.0);
+                if is_solid(x, y) {
+                    new_vel = (0.0, 0.0);
+                } else {
+                    if !species_in(registry.undamped_mask(), x, y) {
+                        new_vel = (new_vel.0 * 0.95, new_vel.1 * 0.95);
+                    }
+                    let blocked = |dx: i32, dy: i32| outside(x + dx, y + dy) || is_solid(x + dx, y + dy);
+                    if blocked(-1, 0) || blocked(1, 0) {
+                        new_vel.0 = 0.0;
+                    }
+                    if blocked(0, -1) || blocked(0, 1) {
+                        new_vel.1 = 0.0;
+                    }
+                }
+                let cpu = (solver.velocity.0.get(x, y), solver.velocity.1.get(x, y));
+                assert!(
+                    (cpu.0 - new_vel.0).abs() < 1e-4 && (cpu.1 - new_vel.1).abs() < 1e-4,
+                    "({}, {}): cpu {:?} shader {:?}",
+                    x,
+                    y,
+                    cpu,
+                    new_vel
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn zero_iterations_only_measure_the_residual() {
+        let mut solver = solver_with_divergence();
+        solver.solve_pressure_iterations(0);
+        assert!(solver.pressure.data.iter().all(|p| *p == 0.0));
+        assert!(solver.residual > 0.0);
+    }
+}
@@ -1,43 +1,55 @@
-use bevy::core_pipeline::core_2d;
 use bevy::core_pipeline::core_2d::graph::{Core2d, Node2d};
 use bevy::core_pipeline::core_2d::Transparent2d;
 use bevy::ecs::query::QueryItem;
 use bevy::prelude::*;
-use bevy::render::{Render, RenderApp, RenderSet};
-use bevy::render::camera::{ExtractedCamera, Viewport};
-use bevy::render::render_graph::{NodeRunError, RenderGraph, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner};
-use bevy::render::render_phase::{DrawFunctions, RenderPhase};
-use bevy::render::render_resource::{LoadOp, Operations, PipelineCache, RenderPassDescriptor, StoreOp};
-use bevy::render::renderer::RenderContext;
+use bevy::render::RenderApp;
+use bevy::render::camera::Viewport;
+use bevy::render::render_graph::{NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner};
+use bevy::render::render_phase::RenderPhase;
+use bevy::render::render_resource::RenderPassDescriptor;
+use bevy::render::renderer::{RenderAdapter, RenderContext, RenderDevice};
 use bevy::render::view::{ExtractedView, ViewTarget};
 use crate::advection::{AdvectionPlugin, DensityAdvectionComputeLabel, DensityAdvectionComputeNode, VelocityAdvectionComputeLabel, VelocityAdvectionComputeNode};
+use crate::boundary::BoundaryPlugin;
 use crate::clear::{ClearComputeLabel, ClearComputeNode, ClearPlugin};
 use crate::curl::{CurlComputeLabel, CurlComputeNode, CurlPlugin};
 use crate::display::{DisplayLabel, DisplayNode, DisplayPlugin};
 use crate::divergence::{DivergencComputeLabel, DivergenceComputeNode, DivergencePlugin};
 use crate::gradient_subtract::{GradientLabel, GradientSubtractComputeNode, GradientSubtractPlugin};
+use crate::multigrid::MultigridPlugin;
 use crate::pressure::{PressureComputeLabel, PressureComputeNode, PressurePlugin};
+use crate::readback::{WindReadbackLabel, WindReadbackNode, WindReadbackPlugin};
 use crate::velocity_out::{VelocityOutComputeNode, VelocityOutPlugin, VorticityOutLabel};
 use crate::vorticity::{VorticityComputeNode, VorticityLabel, VorticityPlugin};
+use crate::FluidFormats;
 
 pub struct FluidSimulationPlugin;
 impl Plugin for FluidSimulationPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((
+            BoundaryPlugin,
             AdvectionPlugin,
             CurlPlugin,
             VorticityPlugin,
             DivergencePlugin,
             ClearPlugin,
             PressurePlugin,
+            MultigridPlugin,
             VelocityOutPlugin,
             GradientSubtractPlugin,
             DisplayPlugin,
+            WindReadbackPlugin,
         ));
     }
 
     fn finish(&self, app: &mut App) {
+        // 设备在 RenderPlugin::finish 里才创建，这里先按设备换掉不支持的格式，再交给 setup 和子插件的 finish
+        // 各个 *Pipeline::from_world 在渲染世界里按 FluidFormats 创建布局，纹理格式在运行期间不变，直接复制一份
+        let formats = app.world.get_resource::<FluidFormats>().copied().unwrap_or_default();
+        let formats = formats.with_fallback(app.world.resource::<RenderAdapter>(), app.world.resource::<RenderDevice>());
+        app.insert_resource(formats);
         let render_app = app.sub_app_mut(RenderApp);
+        render_app.insert_resource(formats);
 
         render_app
             // 添加所有计算节点到Core2d子图
@@ -77,6 +89,10 @@ impl Plugin for FluidSimulationPlugin {
                 Core2d,
                 GradientLabel,
             )
+            .add_render_graph_node::<WindReadbackNode>(
+                Core2d,
+                WindReadbackLabel,
+            )
 
             // 设置计算节点顺序
             .add_render_graph_edges(
@@ -89,19 +105,21 @@ impl Plugin for FluidSimulationPlugin {
                     DivergencComputeLabel,
                     ClearComputeLabel,
                     PressureComputeLabel,
-                    VorticityOutLabel,
                     GradientLabel,
+                    // 最终速度和压力编码后读回CPU
+                    VorticityOutLabel,
+                    WindReadbackLabel,
                 ),
             )
 
             // 将计算节点链连接到主通道之前
             .add_render_graph_edge(
                 Core2d,
-                GradientLabel,
+                WindReadbackLabel,
                 Node2d::MainPass,
             )
 
-            // 添加显示节点（后处理） - 现在放在主通道之后，但在材质渲染之前
+            // 添加显示节点（后处理），在材质渲染之后，把调试显示的流体场叠加在细胞上
             .add_render_graph_node::<ViewNodeRunner<DisplayNode>>(
                 Core2d,
                 DisplayLabel,
@@ -115,14 +133,14 @@ impl Plugin for FluidSimulationPlugin {
 
             // 设置正确的执行顺序：
             // 1. 主通道 (Node2d::MainPass)
-            // 2. 显示节点 (DisplayLabel)
-            // 3. 材质渲染 (CellMaterialLabel)
+            // 2. 材质渲染 (CellMaterialLabel)
+            // 3. 显示节点 (DisplayLabel)
             .add_render_graph_edges(
                 Core2d,
                 (
                     Node2d::MainPass,
-                    DisplayLabel,
                     CellMaterialLabel,
+                    DisplayLabel,
                     Node2d::EndMainPassPostProcessing,
                 ),
             );
@@ -148,9 +166,6 @@ impl ViewNode for CellMaterialNode {
         (view_target, view, transparent_phase): QueryItem<Self::ViewQuery>,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        let draw_functions = world.resource::<DrawFunctions<Transparent2d>>();
-        let pipeline_cache = world.resource::<PipelineCache>();
-
         let pass_descriptor = RenderPassDescriptor {
             label: Some("cell_material_pass"),
             color_attachments: &[Some(view_target.get_color_attachment())],
@@ -3,12 +3,12 @@ use bevy::prelude::*;
 use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
 use bevy::render::render_asset::RenderAssets;
 use bevy::render::{render_graph, Render, RenderApp, RenderSet};
-use bevy::render::render_graph::{NodeRunError, RenderGraph, RenderGraphContext, RenderLabel};
+use bevy::render::render_graph::{NodeRunError, RenderGraphContext, RenderLabel};
 use bevy::render::render_resource::*;
 use bevy::render::render_resource::binding_types::{sampler, texture_2d, texture_storage_2d, uniform_buffer};
 use bevy::render::renderer::{RenderContext, RenderDevice};
-use crate::{FluidConfig, FluidTextures, HEIGHT, WIDTH, WORKGROUP_SIZE};
-use crate::universe::CellGrid;
+use crate::boundary::{boundary_buffer, BoundaryUniforms, FluidSpecies};
+use crate::{FluidConfig, FluidFormats, SimulationSize};
 // ... 原有代码 ...
 
 // 梯度减法所需的uniform数据
@@ -30,6 +30,8 @@ impl FromWorld for GradientSubtractPipeline {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
 
+        let formats = *world.resource::<FluidFormats>();
+
         let bind_group_layout = render_device.create_bind_group_layout(
             "gradient_subtract_bind_group_layout",
             &BindGroupLayoutEntries::sequential(
@@ -39,12 +41,13 @@ impl FromWorld for GradientSubtractPipeline {
                     texture_2d(TextureSampleType::Float { filterable: true }),
                     texture_2d(TextureSampleType::Float { filterable: true }),
                     texture_2d(TextureSampleType::Float { filterable: true }),
-                    texture_storage_2d(TextureFormat::Rgba8Unorm, StorageTextureAccess::WriteOnly),
+                    texture_storage_2d(formats.velocity, StorageTextureAccess::WriteOnly),
                     sampler(SamplerBindingType::Filtering),
                     sampler(SamplerBindingType::Filtering),
                     sampler(SamplerBindingType::Filtering),
                     sampler(SamplerBindingType::Filtering),
-                    uniform_buffer::<GradientSubtractUniforms>(false)
+                    uniform_buffer::<GradientSubtractUniforms>(false),
+                    uniform_buffer::<BoundaryUniforms>(false),
                 )
             ));
 
@@ -60,7 +63,7 @@ impl FromWorld for GradientSubtractPipeline {
             layout: vec![bind_group_layout.clone()],
             push_constant_ranges: Vec::new(),
             shader: shader.clone(),
-            shader_defs: vec![],
+            shader_defs: vec![FluidFormats::output_def(formats.velocity)],
             entry_point: Cow::from("gradient_subtract_main"),
         });
 
@@ -71,21 +74,12 @@ impl FromWorld for GradientSubtractPipeline {
     }
 }
 
-#[derive(Resource, Clone, ExtractResource, AsBindGroup)]
+#[derive(Resource, Clone, ExtractResource)]
 pub struct GradientSubtractImage {
-    #[texture(0, visibility(compute))]
-    #[sampler(5)]
     pub(crate) pressure_tex: Handle<Image>,
-    #[texture(1, visibility(compute))]
-    #[sampler(6)]
     pub(crate) velocity_tex: Handle<Image>,
-    #[texture(2, visibility(compute))]
-    #[sampler(7)]
     pub(crate) wind_tex: Handle<Image>,
-    #[texture(3, visibility(compute))]
-    #[sampler(8)]
     pub(crate) cells_tex: Handle<Image>,
-    #[storage_texture(4, image_format = Rgba8Unorm, access = WriteOnly)]
     pub(crate) output_tex: Handle<Image>,
 }
 
@@ -99,6 +93,8 @@ fn prepare_bind_group(
     render_device: Res<RenderDevice>,
     gradient_subtract_pipeline: Res<GradientSubtractPipeline>,
     fluid_config: Res<FluidConfig>,
+    size: Res<SimulationSize>,
+    fluid_species: Res<FluidSpecies>,
 ) {
     let pressure_tex_view = gpu_images.get(&gradient_subtract_image.pressure_tex).unwrap();
     let velocity_tex_view = gpu_images.get(&gradient_subtract_image.velocity_tex).unwrap();
@@ -136,7 +132,7 @@ fn prepare_bind_group(
     });
 
     let uniforms = GradientSubtractUniforms {
-        texel_size: [1.0 / WIDTH as f32, 1.0 / HEIGHT as f32],
+        texel_size: size.texel_size(),
         wind_strength: -25.0,  // 风力强度
         damping: 0.95,         // 阻尼系数
     };
@@ -146,6 +142,7 @@ fn prepare_bind_group(
         contents: bytemuck::cast_slice(&[uniforms]),
         usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
     });
+    let boundary = boundary_buffer(&render_device, &fluid_config, *fluid_species);
 
     let bind_group = render_device.create_bind_group(
         "gradient_subtract_bind_group",
@@ -166,7 +163,8 @@ fn prepare_bind_group(
                         buffer: &uniform_buffer,
                         offset: 0,
                         size: None,
-                    })
+                    }),
+                    boundary.as_entire_binding(),
                 )
             )
     );
@@ -180,7 +178,7 @@ pub(crate) struct GradientSubtractComputeNode;
 impl render_graph::Node for GradientSubtractComputeNode {
     fn run(
         &self,
-        graph: &mut RenderGraphContext,
+        _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
@@ -198,7 +196,8 @@ impl render_graph::Node for GradientSubtractComputeNode {
         if let Some(pipeline) = pipeline_cache.get_compute_pipeline(gradient_subtract_pipeline.pipeline) {
             pass.set_pipeline(pipeline);
             pass.set_bind_group(0, &gradient_subtract_bind_group.0, &[]);
-            pass.dispatch_workgroups(WIDTH / WORKGROUP_SIZE, HEIGHT / WORKGROUP_SIZE, 1);
+            let (workgroups_x, workgroups_y) = world.resource::<SimulationSize>().workgroups();
+            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
         }
 
         Ok(())
@@ -210,7 +209,6 @@ pub struct GradientSubtractPlugin;
 impl Plugin for GradientSubtractPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(ExtractResourcePlugin::<GradientSubtractImage>::default())
-            .add_systems(Update,swap_velocity_buffer)
         ;
 
         let render_app = app.sub_app_mut(RenderApp);
@@ -229,11 +227,6 @@ impl Plugin for GradientSubtractPlugin {
         render_app.init_resource::<GradientSubtractPipeline>();
     }
 }
-fn swap_velocity_buffer(mut fluid_textures: ResMut<FluidTextures>) {
-    let velocity = &mut fluid_textures.velocity;
-    std::mem::swap(&mut velocity.0, &mut velocity.1);
-
-}
 #[derive(Debug, Hash, PartialEq, Eq, Clone,RenderLabel)]
 pub(crate) struct GradientLabel;
 //
@@ -0,0 +1,440 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use bevy::app::{AppExit, ScheduleRunnerPlugin};
+use bevy::prelude::*;
+use crate::{apply_seed_positions, fluid_config, seed_scene, FluidConfig, PressureSolver, SeedFeeder, SimulationSize};
+use crate::capture::{CaptureOptions, Recorder};
+use crate::fluid_solver::{multigrid_cycle_work, FluidSolver};
+use crate::sim_command::{apply_sim_commands, SimCommandPlugin};
+use crate::universe::{render_cells_rgba, CellGrid, RenderParams, ReplayLog, UnknownSpecies};
+
+// 无窗口模式：只在CPU上推进CellGrid，不创建窗口，也不依赖RenderApp、Material2dPlugin和FluidSimulationPlugin
+// 用法: demo1 --headless [--size WxH] [--generations N] [--output cells.bin] [--fluid]
+//            [--load world.sand|level.png] [--save world.sand|world.png] [--unknown-species reject|empty]
+// --fluid 时用CPU流体求解器把 burns 耦合回 winds
+// --load 从存档继续（不再播种初始场景），--save 在结束时写出存档
+// 以 .png 结尾的路径按 Sandspiel 的 PNG 编码读写，-n 0 时只做格式转换，例如
+//   demo1 --headless --load level.png -n 0 --save level.sand
+// --record 把所有 paint、笔画、撤销重做、流体配置和每代校验和写入回放日志，--replay 按日志重放并在第一次不一致时报错，例如
+//   demo1 --headless --fluid --record run.replay
+//   demo1 --headless --replay run.replay --save final.sand
+// --threads 指定 tick 的线程数（默认取 CPU 核数），结果与线程数无关
+// --pressure-solver jacobi|multigrid 选择 --fluid 的压力求解方式，回放时沿用日志里记录的配置
+// --fluid-config 从配置文件读取 --fluid 的参数（格式见 fluid_config.rs），不给时用 FluidConfig::tuned
+// --render 在结束时按 sand.wgsl 的配色在CPU上渲染截图模式的图片（白底，见 universe/render.rs），例如
+//   demo1 --headless -n 300 --render thumbnail.png
+// --capture 把每 --capture-every 代写成 PNG 序列或 GIF（参数见 capture.rs），--replay 时也可以用，例如
+//   demo1 --headless -n 600 --capture run.gif --capture-every 4 --capture-scale 2
+// --bench-pressure 在 --size 的网格上用CPU求解器比较 Jacobi 和多重网格的残差与耗时，例如
+//   demo1 --headless --bench-pressure --size 600x600
+
+// 无窗口模式的运行参数
+#[derive(Debug, Clone)]
+pub struct HeadlessOptions {
+    pub(crate) generations: u32,
+    pub(crate) output: Option<String>,
+    pub(crate) fluid: bool,
+    pub(crate) load: Option<String>,
+    pub(crate) save: Option<String>,
+    pub(crate) render: Option<String>,
+    pub(crate) unknown_species: UnknownSpecies,
+    pub(crate) record: Option<String>,
+    pub(crate) replay: Option<String>,
+    pub(crate) threads: Option<usize>,
+    pub(crate) fluid_config: FluidConfig,
+    pub(crate) capture: CaptureOptions,
+    pub(crate) bench_pressure: bool,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            generations: 600,
+            output: None,
+            fluid: false,
+            load: None,
+            save: None,
+            render: None,
+            unknown_species: UnknownSpecies::Reject,
+            record: None,
+            replay: None,
+            threads: None,
+            fluid_config: FluidConfig::tuned(),
+            capture: CaptureOptions::default(),
+            bench_pressure: false,
+        }
+    }
+}
+
+impl HeadlessOptions {
+    pub fn from_args(args: &[String]) -> Self {
+        let mut options = HeadlessOptions::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--generations" | "-n" => {
+                    if let Some(n) = iter.next().and_then(|v| v.parse().ok()) {
+                        options.generations = n;
+                    }
+                }
+                "--output" | "-o" => {
+                    options.output = iter.next().cloned();
+                }
+                "--fluid" => {
+                    options.fluid = true;
+                }
+                "--load" => {
+                    options.load = iter.next().cloned();
+                }
+                "--save" => {
+                    options.save = iter.next().cloned();
+                }
+                "--render" => {
+                    options.render = iter.next().cloned();
+                }
+                "--record" => {
+                    options.record = iter.next().cloned();
+                }
+                "--replay" => {
+                    options.replay = iter.next().cloned();
+                }
+                "--threads" => {
+                    options.threads = iter.next().and_then(|v| v.parse().ok());
+                }
+                "--bench-pressure" => {
+                    options.bench_pressure = true;
+                }
+                "--unknown-species" => match iter.next().map(|v| v.as_str()) {
+                    Some("reject") => options.unknown_species = UnknownSpecies::Reject,
+                    Some("empty") => options.unknown_species = UnknownSpecies::Empty,
+                    _ => eprintln!("--unknown-species expects reject or empty"),
+                },
+                _ => {}
+            }
+        }
+        let path = fluid_config::config_path(args);
+        options.fluid_config = fluid_config::startup_config(path.as_deref(), args);
+        options.capture = CaptureOptions::from_args(args);
+        options
+    }
+}
+
+#[derive(Resource)]
+struct HeadlessRun {
+    remaining: u32,
+    options: HeadlessOptions,
+}
+
+pub fn run(size: SimulationSize, options: HeadlessOptions) {
+    if options.bench_pressure {
+        bench_pressure(size);
+        return;
+    }
+    if let Some(path) = &options.replay {
+        run_replay(path, &options);
+        return;
+    }
+    let (mut cell_grid, feeder) = match &options.load {
+        Some(path) => match load_world(path, options.unknown_species) {
+            // 存档里的网格已经播种过了，不再投放
+            Ok(mut cell_grid) => {
+                if options.record.is_some() {
+                    cell_grid.start_recording();
+                }
+                (cell_grid, SeedFeeder::default())
+            }
+            Err(e) => {
+                eprintln!("failed to load {}: {}", path, e);
+                return;
+            }
+        },
+        None => {
+            let mut cell_grid = CellGrid::new(size.width as i32, size.height as i32);
+            // 在播种之前开始录制，初始场景的 paint 也会进日志
+            if options.record.is_some() {
+                cell_grid.start_recording();
+            }
+            let feeder = seed_scene(&mut cell_grid);
+            (cell_grid, feeder)
+        }
+    };
+    if let Some(threads) = options.threads {
+        cell_grid.set_tick_threads(threads);
+    }
+    // 只转换格式，不推进
+    if options.generations == 0 {
+        write_recording(&mut cell_grid, &options);
+        dump(&cell_grid, &options);
+        return;
+    }
+    // 读档时以存档的尺寸为准
+    let size = SimulationSize::new(cell_grid.width() as u32, cell_grid.height() as u32);
+    let mut app = App::new();
+    if options.fluid {
+        app.insert_resource(FluidSolver::new(size.width as usize, size.height as usize));
+    }
+    if options.capture.start {
+        app.insert_resource(Recorder::new(options.capture.clone()));
+    }
+    app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)))
+        .insert_resource(size)
+        .insert_resource(cell_grid)
+        .insert_resource(options.fluid_config.clone())
+        .insert_resource(feeder)
+        .insert_resource(HeadlessRun {
+            remaining: options.generations,
+            options,
+        })
+        .add_plugins(SimCommandPlugin)
+        .add_systems(Update, (
+            apply_seed_positions,
+            step_cell_grid.after(apply_seed_positions).after(apply_sim_commands),
+        ))
+        .run();
+}
+
+// 每帧推进一次，跑完指定代数后输出结果并退出
+fn step_cell_grid(
+    mut cell_grid: ResMut<CellGrid>,
+    mut run: ResMut<HeadlessRun>,
+    mut exit: EventWriter<AppExit>,
+    mut fluid_solver: Option<ResMut<FluidSolver>>,
+    mut recorder: Option<ResMut<Recorder>>,
+    fluid_config: Res<FluidConfig>,
+) {
+    if run.remaining == 0 {
+        return;
+    }
+    cell_grid.tick();
+    if let Some(fluid_solver) = &mut fluid_solver {
+        // 与GPU路径相同的时间步长上限
+        fluid_solver.step_grid(&fluid_config, 0.016, &mut cell_grid);
+    }
+    if let Some(recorder) = &mut recorder {
+        capture_frame(recorder, &cell_grid);
+    }
+    run.remaining -= 1;
+    if run.remaining == 0 {
+        if let Some(fluid_solver) = &fluid_solver {
+            let work = match fluid_config.pressure_solver {
+                PressureSolver::Jacobi => format!("{} iterations", fluid_config.pressure_iterations),
+                PressureSolver::Multigrid => format!("{} V-cycles", fluid_config.multigrid_cycles),
+            };
+            println!("pressure residual: {:.6} after {}", fluid_solver.residual, work);
+        }
+        if let Some(recorder) = &mut recorder {
+            finish_capture(recorder);
+        }
+        write_recording(&mut cell_grid, &run.options);
+        dump(&cell_grid, &run.options);
+        exit.send(AppExit);
+    }
+}
+
+// 写失败时停止录制，模拟照常跑完
+fn capture_frame(recorder: &mut Recorder, cell_grid: &CellGrid) {
+    if let Err(e) = recorder.capture(cell_grid) {
+        eprintln!("capture failed: {}", e);
+        finish_capture(recorder);
+    }
+}
+
+fn finish_capture(recorder: &mut Recorder) {
+    if let Err(e) = recorder.stop() {
+        eprintln!("failed to finish capture: {}", e);
+    }
+}
+
+fn dump(cell_grid: &CellGrid, options: &HeadlessOptions) {
+    println!(
+        "headless: {} ticks on {}x{} grid",
+        options.generations,
+        cell_grid.width(),
+        cell_grid.height()
+    );
+    for (species, count) in cell_grid.species_counts() {
+        println!("{}: {}", cell_grid.registry().name(species), count);
+    }
+    if let Some(path) = &options.output {
+        // 与细胞纹理相同的RGBA字节布局
+        match fs::write(path, cell_grid.cells_rgba()) {
+            Ok(()) => println!("cells written to {}", path),
+            Err(e) => eprintln!("failed to write {}: {}", path, e),
+        }
+    }
+    if let Some(path) = &options.save {
+        match save_world(cell_grid, path) {
+            Ok(()) => println!("snapshot saved to {}", path),
+            Err(e) => eprintln!("failed to save {}: {}", path, e),
+        }
+    }
+    if let Some(path) = &options.render {
+        // 与窗口一样叠在白色的清屏颜色上
+        let mut image = render_cells_rgba(cell_grid, RenderParams::snapshot());
+        image.flatten([255, 255, 255]);
+        match image.save_png(path) {
+            Ok(()) => println!("image rendered to {}", path),
+            Err(e) => eprintln!("failed to write {}: {}", path, e),
+        }
+    }
+}
+
+fn write_recording(cell_grid: &mut CellGrid, options: &HeadlessOptions) {
+    let (Some(path), Some(mut log)) = (&options.record, cell_grid.take_recording()) else {
+        return;
+    };
+    log.load = options.load.clone();
+    log.fluid = options.fluid;
+    log.fluid_config = options.fluid.then(|| options.fluid_config.to_toml());
+    match log.save(path) {
+        Ok(()) => println!("replay log written to {}", path),
+        Err(e) => eprintln!("failed to write {}: {}", path, e),
+    }
+}
+
+// 按日志重放，不经过 App：投放线程的时序已经记录在日志里
+fn run_replay(path: &str, options: &HeadlessOptions) {
+    let log = match ReplayLog::load(path) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("failed to read {}: {}", path, e);
+            return;
+        }
+    };
+    let mut base = match &log.load {
+        Some(world) => match load_world(world, options.unknown_species) {
+            Ok(cell_grid) => cell_grid,
+            Err(e) => {
+                eprintln!("failed to load {}: {}", world, e);
+                return;
+            }
+        },
+        None => CellGrid::new(log.width, log.height),
+    };
+    // 回放时可以换一个线程数，校验和仍然应该一致
+    if let Some(threads) = options.threads {
+        base.set_tick_threads(threads);
+    }
+    // 按录制时的流体配置重放，命令行的 --fluid-config、--pressure-solver 不起作用；旧日志没有记录时才用命令行的
+    let fluid_config = match &log.fluid_config {
+        Some(text) => match FluidConfig::parse(text) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("bad fluid config in {}: {}", path, e);
+                return;
+            }
+        },
+        None => options.fluid_config.clone(),
+    };
+    let mut fluid_solver = log
+        .fluid
+        .then(|| FluidSolver::new(log.width as usize, log.height as usize));
+    let mut recorder = options.capture.start.then(|| Recorder::new(options.capture.clone()));
+    let result = log.replay(base, |cell_grid| {
+        if let Some(fluid_solver) = &mut fluid_solver {
+            fluid_solver.step_grid(&fluid_config, 0.016, cell_grid);
+        }
+        if let Some(recorder) = &mut recorder {
+            capture_frame(recorder, cell_grid);
+        }
+    });
+    if let Some(recorder) = &mut recorder {
+        finish_capture(recorder);
+    }
+    match result {
+        Ok(cell_grid) => {
+            println!(
+                "replay: {} ticks, {} checksums matched",
+                log.ticks,
+                log.checksums.len()
+            );
+            let options = HeadlessOptions {
+                generations: log.ticks as u32,
+                ..options.clone()
+            };
+            dump(&cell_grid, &options);
+        }
+        Err(divergence) => {
+            eprintln!(
+                "replay diverged at tick {}: expected {:016x}, got {:016x}",
+                divergence.tick, divergence.expected, divergence.actual
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+// 左上和右下各一块圆形散度，一正一负，总和近似为 0，压力从 0 开始求解
+fn bench_solver(size: SimulationSize) -> FluidSolver {
+    let (width, height) = (size.width as usize, size.height as usize);
+    let mut solver = FluidSolver::new(width, height);
+    let radius = (width.min(height) / 8).max(1) as i32;
+    for (cx, cy, value) in [(width / 3, height / 3, 0.5), (width * 2 / 3, height * 2 / 3, -0.5)] {
+        for y in 0..height {
+            for x in 0..width {
+                let (dx, dy) = (x as i32 - cx as i32, y as i32 - cy as i32);
+                if dx * dx + dy * dy <= radius * radius {
+                    solver.divergence.set(x, y, value);
+                }
+            }
+        }
+    }
+    solver
+}
+
+fn bench_pressure(size: SimulationSize) {
+    println!("pressure bench on {}x{} grid", size.width, size.height);
+    let initial = bench_solver(size).pressure_residual();
+    println!("initial residual: {:.6}", initial);
+    // 工作量按各层更新的纹素数累加，见 multigrid_cycle_work
+    let texels = size.width as usize * size.height as usize;
+    let cycle_work = multigrid_cycle_work(size.width as usize, size.height as usize);
+    for iterations in [20, 100, 500] {
+        let mut solver = bench_solver(size);
+        let start = Instant::now();
+        solver.solve_pressure_iterations(iterations);
+        println!(
+            "jacobi    {:>4} iterations: residual {:.6} in {:?}, {} texel updates",
+            iterations,
+            solver.residual,
+            start.elapsed(),
+            iterations as usize * texels
+        );
+    }
+    for cycles in [1, 2, 4, 8] {
+        let mut solver = bench_solver(size);
+        let start = Instant::now();
+        solver.solve_pressure_multigrid(cycles);
+        println!(
+            "multigrid {:>4} V-cycles:   residual {:.6} in {:?}, {} texel updates",
+            cycles,
+            solver.residual,
+            start.elapsed(),
+            cycles as usize * cycle_work
+        );
+    }
+}
+
+fn is_png(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+fn load_world(path: &str, unknown_species: UnknownSpecies) -> io::Result<CellGrid> {
+    if is_png(path) {
+        CellGrid::from_png(path, unknown_species)
+    } else {
+        CellGrid::load(path)
+    }
+}
+
+fn save_world(cell_grid: &CellGrid, path: &str) -> io::Result<()> {
+    if is_png(path) {
+        cell_grid.to_png(path)
+    } else {
+        cell_grid.save(path)
+    }
+}
@@ -0,0 +1,268 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use bevy::prelude::*;
+use bevy::ui::{FocusPolicy, RelativeCursorPosition};
+use crate::fluid_config::FIELDS;
+use crate::pressure::{PressureResidual, PressureResidualRequest};
+use crate::FluidConfig;
+
+// 流体参数面板：FluidConfig 的每个字段一个滑块，拖动后下一帧经 ExtractResource 进入对应的计算通道
+// Tab 显示或隐藏面板，Ctrl+S 把当前的值写回配置文件
+// 配置文件（格式见 fluid_config.rs）每半秒检查一次修改时间，变了就重新加载，面板跟着更新
+// 面板底部显示压力求解的残差，只在面板显示时请求 GPU 统计（见 PressureResidualRequest）
+pub struct InspectorPlugin {
+    pub(crate) config_path: String,
+}
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        let path = PathBuf::from(&self.config_path);
+        let modified = modified_time(&path);
+        app.insert_resource(FluidConfigFile {
+            path,
+            modified,
+            poll: Timer::from_seconds(0.5, TimerMode::Repeating),
+        })
+            .add_systems(Startup, spawn_panel)
+            .add_systems(Update, (
+                reload_fluid_config,
+                save_fluid_config,
+                toggle_panel,
+                drag_sliders.after(reload_fluid_config),
+                update_panel.after(drag_sliders),
+                request_residual.after(toggle_panel),
+                update_residual_label,
+            ));
+    }
+}
+
+#[derive(Resource)]
+struct FluidConfigFile {
+    path: PathBuf,
+    // 最后一次读取或写入时文件的修改时间
+    modified: Option<SystemTime>,
+    poll: Timer,
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// 光标在面板上时鼠标不绘制（见 painting.rs）
+#[derive(Component)]
+pub(crate) struct BlocksPainting;
+
+#[derive(Component)]
+struct InspectorPanel;
+
+// 滑块轨道、填充和文字，数字是 FIELDS 的下标
+#[derive(Component)]
+struct ConfigSlider(usize);
+
+#[derive(Component)]
+struct SliderFill(usize);
+
+#[derive(Component)]
+struct SliderLabel(usize);
+
+#[derive(Component)]
+struct ResidualLabel;
+
+const SLIDER_WIDTH: f32 = 180.0;
+const SLIDER_HEIGHT: f32 = 10.0;
+
+fn spawn_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(8.0),
+                    left: Val::Px(8.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                focus_policy: FocusPolicy::Block,
+                ..default()
+            },
+            Interaction::default(),
+            InspectorPanel,
+            BlocksPainting,
+        ))
+        .with_children(|panel| {
+            for (i, field) in FIELDS.iter().enumerate() {
+                panel.spawn((
+                    TextBundle::from_section(
+                        field.name,
+                        TextStyle {
+                            font_size: 14.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    SliderLabel(i),
+                ));
+                panel
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(SLIDER_WIDTH),
+                                height: Val::Px(SLIDER_HEIGHT),
+                                ..default()
+                            },
+                            background_color: Color::rgb(0.25, 0.25, 0.25).into(),
+                            ..default()
+                        },
+                        RelativeCursorPosition::default(),
+                        ConfigSlider(i),
+                        BlocksPainting,
+                    ))
+                    .with_children(|track| {
+                        track.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Percent(0.0),
+                                    height: Val::Percent(100.0),
+                                    ..default()
+                                },
+                                background_color: Color::rgb(0.9, 0.6, 0.2).into(),
+                                ..default()
+                            },
+                            SliderFill(i),
+                        ));
+                    });
+            }
+            panel.spawn((
+                TextBundle::from_section(
+                    "pressure residual = -",
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                ResidualLabel,
+            ));
+        });
+}
+
+// 按住滑块时跟随光标，拖出轨道以后贴在两端
+fn drag_sliders(
+    sliders: Query<(&Interaction, &RelativeCursorPosition, &ConfigSlider)>,
+    mut fluid_config: ResMut<FluidConfig>,
+) {
+    for (interaction, cursor, slider) in sliders.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(position) = cursor.normalized else {
+            continue;
+        };
+        let field = &FIELDS[slider.0];
+        let value = field.value_at(position.x);
+        // 值没变时不写，避免每帧触发提取
+        if (field.get)(&fluid_config) != value {
+            (field.set)(&mut fluid_config, value);
+        }
+    }
+}
+
+fn update_panel(
+    fluid_config: Res<FluidConfig>,
+    mut fills: Query<(&mut Style, &SliderFill)>,
+    mut labels: Query<(&mut Text, &SliderLabel)>,
+) {
+    if !fluid_config.is_changed() {
+        return;
+    }
+    for (mut style, fill) in fills.iter_mut() {
+        style.width = Val::Percent(FIELDS[fill.0].fraction(&fluid_config) * 100.0);
+    }
+    for (mut text, label) in labels.iter_mut() {
+        let field = &FIELDS[label.0];
+        text.sections[0].value = format!("{} = {}", field.name, field.display(&fluid_config));
+    }
+}
+
+fn toggle_panel(keys: Res<ButtonInput<KeyCode>>, mut panels: Query<&mut Visibility, With<InspectorPanel>>) {
+    if !keys.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    for mut visibility in panels.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+fn request_residual(
+    panels: Query<&Visibility, With<InspectorPanel>>,
+    mut request: ResMut<PressureResidualRequest>,
+) {
+    let visible = panels.iter().any(|visibility| *visibility != Visibility::Hidden);
+    if request.0 != visible {
+        request.0 = visible;
+    }
+}
+
+fn update_residual_label(
+    residual: Res<PressureResidual>,
+    mut labels: Query<&mut Text, With<ResidualLabel>>,
+) {
+    if !residual.is_changed() {
+        return;
+    }
+    for mut text in labels.iter_mut() {
+        text.sections[0].value = format!(
+            "pressure residual = {:.6} after {}",
+            residual.residual, residual.iterations
+        );
+    }
+}
+
+// 文件里没写的字段取默认值；解析失败时保留当前配置，改好后再次保存即可
+fn reload_fluid_config(
+    time: Res<Time>,
+    mut file: ResMut<FluidConfigFile>,
+    mut fluid_config: ResMut<FluidConfig>,
+) {
+    if !file.poll.tick(time.delta()).just_finished() {
+        return;
+    }
+    let modified = modified_time(&file.path);
+    if modified.is_none() || modified == file.modified {
+        return;
+    }
+    file.modified = modified;
+    match FluidConfig::load(&file.path) {
+        Ok(config) => {
+            *fluid_config = config;
+            info!("reloaded {}", file.path.display());
+        }
+        Err(e) => error!("failed to reload {}: {}", file.path.display(), e),
+    }
+}
+
+fn save_fluid_config(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut file: ResMut<FluidConfigFile>,
+    fluid_config: Res<FluidConfig>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keys.just_pressed(KeyCode::KeyS) {
+        return;
+    }
+    match fluid_config.save(&file.path) {
+        Ok(()) => {
+            // 自己写的文件不必重新加载
+            file.modified = modified_time(&file.path);
+            info!("fluid config saved to {}", file.path.display());
+        }
+        Err(e) => error!("failed to save {}: {}", file.path.display(), e),
+    }
+}
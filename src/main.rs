@@ -1,3 +1,7 @@
+// encase 0.7 的 ShaderType 派生给每个字段生成一个不会被调用的 check 函数，新版 rustc 把它们报成死代码，只能在模块以上关掉；
+// universe 的接口（历史、温度、活跃块等）有一部分只在测试和回放里用到
+#![allow(dead_code)]
+
 mod advection;
 mod curl;
 mod divergence;
@@ -7,17 +11,24 @@ mod vorticity;
 mod velocity_out;
 mod fluid;
 mod display;
-mod compute_shader_game_of_life;
 mod universe;
 mod fluidsimulation;
 mod display2;
 mod clear;
+mod headless;
+mod fluid_solver;
+mod readback;
+mod resize;
+mod painting;
+mod sim_command;
+mod multigrid;
+mod boundary;
+mod fluid_config;
+mod inspector;
+mod capture;
+mod recorder;
 
 use std::collections::VecDeque;
-use std::mem::swap;
-use std::process::id;
-use std::time::{Duration, Instant};
-use crossbeam_channel::{bounded, Receiver};
 use bevy::{
     prelude::*,
     render::{
@@ -26,42 +37,112 @@ use bevy::{
     },
     sprite::{Material2d, Material2dPlugin},
 };
-use bevy::pbr::MaterialPipeline;
-use bevy::render::camera::RenderTarget;
-use bevy::render::mesh::{Indices, MeshVertexBufferLayout};
+use bevy::render::mesh::Indices;
 use bevy::render::render_asset::RenderAssetUsages;
-use bevy::render::render_resource::{CommandEncoderDescriptor, CompareFunction, ComputePassDescriptor, DepthStencilState, Extent3d, ImageDataLayout, PipelineCache, RenderPipelineDescriptor, SpecializedMeshPipelineError, TextureDimension, TextureFormat, TextureUsages};
-use bevy::render::renderer::{RenderDevice, RenderQueue};
-use bevy::render::{Render, RenderPlugin};
+use bevy::render::render_resource::{ShaderDefVal, Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy::render::renderer::{RenderAdapter, RenderDevice};
+use bevy::render::RenderPlugin;
 use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
-use bevy::render::settings::{Backends, WgpuSettings};
+use bevy::render::settings::{Backends, WgpuFeatures, WgpuSettings};
 use bevy::render::texture::TextureFormatPixelInfo;
-use bevy::sprite::{Material2dKey, MaterialMesh2dBundle};
+use bevy::sprite::MaterialMesh2dBundle;
 
-use bevy::utils::petgraph::visit::NodeRef;
-use bevy::window::PrimaryWindow;
-use rand::{thread_rng, Rng};
-use rand::seq::SliceRandom;
-use crate::advection::{ AdvectionPipeline, AdvectionPlugin, DensityAdvectionImage, VelocityAdvectionImage};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::SplitMix64;
+use serde::Deserialize;
+use crate::advection::{ DensityAdvectionImage, VelocityAdvectionImage};
 use crate::clear::ClearImage;
-use crate::compute_shader_game_of_life::{GameOfLifeComputePlugin, GameOfLifeImage};
-use crate::curl::{CurlBindGroup, CurlImage, CurlPipeline, CurlPlugin};
+use crate::capture::CaptureOptions;
+use crate::curl::CurlImage;
 use crate::display::DisplayTarget;
 // use crate::display1::DisplayPlugin;
 
-use crate::divergence::{DivergenceImage, DivergencePlugin};
+use crate::divergence::DivergenceImage;
+use crate::fluid_solver::FluidSolver;
 use crate::fluidsimulation::FluidSimulationPlugin;
-use crate::gradient_subtract::{GradientSubtractBindGroup, GradientSubtractImage, GradientSubtractPipeline, GradientSubtractPlugin};
-use crate::pressure::{PressureBindGroup, PressureImage, PressurePipeline, PressurePlugin};
-use crate::universe::{CellGrid, Species};
-use crate::velocity_out::{VelocityOutBindGroup, VelocityOutImage, VelocityOutPipeline, VelocityOutPlugin};
-use crate::vorticity::{VorticityBindGroup, VorticityImage, VorticityPipeline, VorticityPlugin};
-
-pub const WIDTH: u32 = 600;
-pub const HEIGHT: u32 = 600;
-pub const SIZE: (u32, u32) = (WIDTH, HEIGHT);
+use crate::inspector::InspectorPlugin;
+use crate::painting::PaintingPlugin;
+use crate::recorder::RecorderPlugin;
+use crate::sim_command::SimCommandPlugin;
+use crate::gradient_subtract::GradientSubtractImage;
+use crate::pressure::PressureImage;
+use crate::universe::{CellGrid, Species, SpeciesRegistry, MAX_SPECIES};
+use crate::velocity_out::VelocityOutImage;
+use crate::vorticity::VorticityImage;
+
+pub const DEFAULT_WIDTH: u32 = 600;
+pub const DEFAULT_HEIGHT: u32 = 600;
 pub const WORKGROUP_SIZE: u32 = 8;
-///平流(Advection)	初始速度场	更新速度场
+
+// 模拟网格的尺寸，运行时由命令行 --size WxH（或 --width/--height）决定
+// CellGrid、FluidTextures 的分配以及所有计算着色器的调度都使用它
+#[derive(Resource, ExtractResource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimulationSize {
+    pub width: u32,
+    pub height: u32,
+}
+impl Default for SimulationSize {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+        }
+    }
+}
+impl SimulationSize {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width: width.max(1),
+            height: height.max(1),
+        }
+    }
+
+    pub fn from_args(args: &[String]) -> Self {
+        let mut size = SimulationSize::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--size" => {
+                    if let Some((w, h)) = iter.next().and_then(|v| v.split_once('x')) {
+                        if let (Ok(w), Ok(h)) = (w.parse(), h.parse()) {
+                            size = SimulationSize::new(w, h);
+                        }
+                    }
+                }
+                "--width" => {
+                    if let Some(w) = iter.next().and_then(|v| v.parse().ok()) {
+                        size = SimulationSize::new(w, size.height);
+                    }
+                }
+                "--height" => {
+                    if let Some(h) = iter.next().and_then(|v| v.parse().ok()) {
+                        size = SimulationSize::new(size.width, h);
+                    }
+                }
+                _ => {}
+            }
+        }
+        size
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+
+    pub fn texel_size(&self) -> [f32; 2] {
+        [1.0 / self.width as f32, 1.0 / self.height as f32]
+    }
+
+    pub fn extent(&self) -> Extent3d {
+        Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 }
+    }
+
+    // 不是8的倍数时向上取整，着色器内部会跳过越界的线程
+    pub fn workgroups(&self) -> (u32, u32) {
+        (self.width.div_ceil(WORKGROUP_SIZE), self.height.div_ceil(WORKGROUP_SIZE))
+    }
+}
+// 平流(Advection)	初始速度场	更新速度场
 // 涡度计算(Curl)	平流后的速度场	计算流体旋转
 // 散度计算(Divergence)	速度场	计算不可压缩性
 // 压力求解(Pressure)	散度场	校正速度场
@@ -78,80 +159,259 @@ pub struct FluidTextures {
     burns: Handle<Image>,
     cells: Handle<Image>,
     velocity_out: Handle<Image>,
-    output: Handle<Image>,
 }
 impl FluidTextures {
-    fn log(&self, images: &Assets<Image>,) {
-        if let Some(image) = images.get(&self.velocity.0) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("velocity 0 texture is all zero: {}", is_all_zero);
-        }
-        if let Some(image) = images.get(&self.velocity.1) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("velocity 1 texture is all zero: {}", is_all_zero);
-        }
-        if let Some(image) = images.get(&self.density.0) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("density 0 texture is all zero: {}", is_all_zero);
-        }
-        if let Some(image) = images.get(&self.density.1) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("density 1 texture is all zero: {}", is_all_zero);
-        }
-        if let Some(image) = images.get(&self.pressure.0) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("pressure 0 texture is all zero: {}", is_all_zero);
-        }
-        if let Some(image) = images.get(&self.pressure.1) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("pressure 1 texture is all zero: {}", is_all_zero);
+    // 按模拟尺寸分配所有纹理
+    // 速度、压力、旋度、散度是有符号的场，按 FluidFormats 分配；
+    // density 以及与 CellGrid 交换字节的 burns、cells、velocity_out 保持 Rgba8Unorm（编码见 assets/wind.wgsl）
+    pub(crate) fn allocate(images: &mut Assets<Image>, size: SimulationSize, formats: FluidFormats) -> Self {
+        let bytes = TextureFormat::Rgba8Unorm;
+        FluidTextures {
+            velocity: (
+                create_texture(images, size, formats.velocity),
+                create_storage_texture(images, size, formats.velocity),
+            ),
+            density: (create_texture(images, size, bytes), create_storage_texture(images, size, bytes)),
+            // 压力两个缓冲区都会被计算着色器写入
+            pressure: (
+                create_storage_texture(images, size, formats.scalar),
+                create_storage_texture(images, size, formats.scalar),
+            ),
+            curl: create_storage_texture(images, size, formats.scalar),
+            divergence: create_storage_texture(images, size, formats.scalar),
+            burns: create_texture(images, size, bytes),
+            cells: create_storage_texture(images, size, bytes),
+            velocity_out: create_storage_texture(images, size, bytes),
         }
+    }
+}
+fn create_texture(images: &mut Assets<Image>, size: SimulationSize, format: TextureFormat) -> Handle<Image> {
+    let pixel_count = size.pixel_count();
+    // 全零的字节在各种浮点格式里也是 0.0
+    let data_size = pixel_count * format.pixel_size();
+    let initial_data = vec![0u8; data_size];
+    let mut image = Image::new(
+        size.extent(),
+        TextureDimension::D2,
+        initial_data,
+        format,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::nearest();
+    image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+        | TextureUsages::COPY_DST;
+    images.add(image)
+}
 
-        if let Some(image) = images.get(&self.curl) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("curl  texture is all zero: {}", is_all_zero);
-        }
-        if let Some(image) = images.get(&self.divergence) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("divergence   texture is all zero: {}", is_all_zero);
-        }
-        if let Some(image) = images.get(&self.burns) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("burns texture is all zero: {}", is_all_zero);
-        }
-        if let Some(image) = images.get(&self.cells) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("cells texture is all zero: {}", is_all_zero);
+// 创建存储纹理的函数
+fn create_storage_texture(images: &mut Assets<Image>, size: SimulationSize, format: TextureFormat) -> Handle<Image> {
+    let pixel_count = size.pixel_count();
+    let data_size = pixel_count * format.pixel_size();
+    let initial_data = vec![0u8; data_size];
+    let mut image = Image::new(
+        size.extent(),
+        TextureDimension::D2,
+        initial_data,
+        format,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::nearest();
+    image.texture_descriptor.usage = TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING
+        | TextureUsages::COPY_DST | TextureUsages::COPY_SRC;
+    images.add(image)
+}
+// 有符号流体场的纹理格式，启动时确定，窗口缩放重新分配纹理时沿用
+// 默认速度用 Rg16Float，压力、旋度、散度用 R32Float；
+// 这些场既要被计算着色器写入又要线性采样，Rg16Float、R16Float 的存储写入和 R32Float、Rg32Float 的线性过滤依赖适配器，
+// 只有选中这类格式时才保留 TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES；适配器不支持时退回 Rgba8Unorm 并给出警告，见 with_fallback
+// 计算着色器的输出声明按 FluidFormats::output_def 选择，见各个 *.wgsl 开头的 #ifdef OUTPUT_*
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct FluidFormats {
+    pub velocity: TextureFormat,
+    pub scalar: TextureFormat,
+}
+
+impl Default for FluidFormats {
+    fn default() -> Self {
+        Self {
+            velocity: TextureFormat::Rg16Float,
+            scalar: TextureFormat::R32Float,
         }
-        if let Some(image) = images.get(&self.velocity_out) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("velocity_out texture is all zero: {}", is_all_zero);
+    }
+}
+
+impl FluidFormats {
+    // --velocity-format rg16float|rg32float，--scalar-format r32float|r16float
+    pub fn from_args(args: &[String]) -> Self {
+        let mut formats = FluidFormats::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--velocity-format" => match iter.next().map(String::as_str) {
+                    Some("rg16float") => formats.velocity = TextureFormat::Rg16Float,
+                    Some("rg32float") => formats.velocity = TextureFormat::Rg32Float,
+                    other => eprintln!("unknown velocity format {:?}, using {:?}", other, formats.velocity),
+                },
+                "--scalar-format" => match iter.next().map(String::as_str) {
+                    Some("r32float") => formats.scalar = TextureFormat::R32Float,
+                    Some("r16float") => formats.scalar = TextureFormat::R16Float,
+                    other => eprintln!("unknown scalar format {:?}, using {:?}", other, formats.scalar),
+                },
+                _ => {}
+            }
         }
-        if let Some(image) = images.get(&self.output) {
-            let is_all_zero = image.data.iter().all(|&b| b == 0);
-            info!("output texture is all zero: {}", is_all_zero);
+        formats
+    }
+
+    // adapter 为 None 或设备没打开适配器特性时，只看规范保证的格式能力
+    // bevy 没有重新导出 TextureFormatFeatureFlags，按名字查 FILTERABLE
+    fn usable(format: TextureFormat, adapter: Option<&RenderAdapter>, device_features: WgpuFeatures) -> bool {
+        let features = match adapter {
+            Some(adapter) if device_features.contains(WgpuFeatures::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) => {
+                adapter.get_texture_format_features(format)
+            }
+            _ => format.guaranteed_format_features(device_features),
+        };
+        features.allowed_usages.contains(TextureUsages::STORAGE_BINDING)
+            && features.flags.iter_names().any(|(name, _)| name == "FILTERABLE")
+    }
+
+    // 不打开适配器特性时，是否有格式无法存储写入或线性过滤
+    pub fn needs_adapter_features(&self) -> bool {
+        [self.velocity, self.scalar]
+            .iter()
+            .any(|&format| !Self::usable(format, None, WgpuFeatures::empty()))
+    }
+
+    // 按实际创建的设备检查两种格式，不可用的换回原来的 Rgba8Unorm（着色器里的 #else 分支）
+    pub fn with_fallback(self, adapter: &RenderAdapter, device: &RenderDevice) -> Self {
+        let check = |format: TextureFormat, name: &str| {
+            if Self::usable(format, Some(adapter), device.features()) {
+                format
+            } else {
+                warn!("{} format {:?} cannot be used as a filterable storage texture on this adapter, falling back to Rgba8Unorm", name, format);
+                TextureFormat::Rgba8Unorm
+            }
+        };
+        FluidFormats {
+            velocity: check(self.velocity, "velocity"),
+            scalar: check(self.scalar, "scalar"),
         }
+    }
 
+    // 写入 format 的存储纹理时传给着色器的 shader def，例如 OUTPUT_RG16FLOAT
+    pub fn output_def(format: TextureFormat) -> ShaderDefVal {
+        format!("OUTPUT_{:?}", format).to_uppercase().into()
     }
 }
-// 流体配置参数
-#[derive(Resource,ExtractResource,Clone)]
+
+// 流体配置参数，配置文件按字段名反序列化（见 fluid_config.rs），没写的字段取 Default，也就是 tuned 的值
+#[derive(Resource,ExtractResource,Clone,Debug,Deserialize)]
+#[serde(default, deny_unknown_fields)]
 struct FluidConfig {
     velocity_dissipation: f32,
     density_dissipation: f32,
     curl_strength: f32,
     pressure_dissipation: f32,
     pressure_iterations: u32,
+    // 压力求解方式，每帧读取，运行中可以切换
+    pressure_solver: PressureSolver,
+    // 多重网格模式每帧的 V 循环次数
+    multigrid_cycles: u32,
+    // 网格四周和固体物质的边界方式
+    edge_boundary: EdgeBoundary,
+    solid_boundary: SolidBoundary,
+}
+
+// Jacobi 做 pressure_iterations 次迭代；Multigrid 在金字塔上做 multigrid_cycles 次 V 循环，
+// 大网格上收敛快得多（见 multigrid.rs，CPU 对照见 FluidSolver::solve_pressure_multigrid）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PressureSolver {
+    #[default]
+    Jacobi,
+    Multigrid,
+}
+
+impl PressureSolver {
+    // --pressure-solver jacobi|multigrid
+    pub fn from_args(args: &[String]) -> Self {
+        let mut solver = PressureSolver::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--pressure-solver" {
+                let name = iter.next();
+                match name.and_then(|name| PressureSolver::parse(name)) {
+                    Some(parsed) => solver = parsed,
+                    None => eprintln!("unknown pressure solver {:?}, using {:?}", name, solver),
+                }
+            }
+        }
+        solver
+    }
+
+    // --pressure-solver 的取值，与配置文件里的名字相同（见 fluid_config.rs）
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "jacobi" => Some(PressureSolver::Jacobi),
+            "multigrid" => Some(PressureSolver::Multigrid),
+            _ => None,
+        }
+    }
+}
+
+// 网格四周的边界方式，divergence、pressure、gradient_subtract 和多重网格的处理一致（见 assets/boundary.wgsl）
+//   Closed：封闭的墙，法向速度为 0，压力取边上的值
+//   Open：流出边界，速度取边上的值，压力为 0，流体可以流出
+//   Periodic：从对边绕回
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum EdgeBoundary {
+    #[default]
+    Closed = 0,
+    Open = 1,
+    Periodic = 2,
+}
+
+// 固体物质（SpeciesDef::solid）表面的边界方式，两种都不能穿过
+//   NoSlip：贴着固体的速度为 0
+//   FreeSlip：只去掉垂直于表面的分量，流体可以沿表面滑动
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum SolidBoundary {
+    #[default]
+    NoSlip = 0,
+    FreeSlip = 1,
 }
+
 impl Default for FluidConfig {
     fn default() -> Self {
-        println!("FluidConfig_default");
-        Self{
-            velocity_dissipation: 0.0,
-            density_dissipation:  0.0,
-            curl_strength:  0.0,
-            pressure_dissipation:  0.0,
-            pressure_iterations:  0,
+        FluidConfig::tuned()
+    }
+}
+impl FluidConfig {
+    // 调好的参数，窗口模式和无窗口模式共用
+    fn tuned() -> Self {
+        Self {
+            velocity_dissipation: 0.99,
+            density_dissipation: 0.99,
+            curl_strength: 3.0,
+            pressure_dissipation: 0.99,
+            pressure_iterations: 20,
+            pressure_solver: PressureSolver::Jacobi,
+            multigrid_cycles: 2,
+            edge_boundary: EdgeBoundary::Closed,
+            solid_boundary: SolidBoundary::NoSlip,
+        }
+    }
+
+    // 每帧压力求解的工作量：Jacobi 的迭代次数或多重网格的 V 循环次数
+    pub(crate) fn pressure_steps(&self) -> u32 {
+        match self.pressure_solver {
+            PressureSolver::Jacobi => self.pressure_iterations,
+            PressureSolver::Multigrid => self.multigrid_cycles,
         }
     }
 }
@@ -180,7 +440,8 @@ struct CellMaterial {
     #[sampler(2)]  // 第三个绑定位置
     // #[storage_texture(1, image_format = Rgba8Unorm, access = ReadWrite)]
     data_tex: Handle<Image>,
-
+    #[uniform(3)]
+    palette: SpeciesPalette,
 }
 
 #[derive(ShaderType, Clone, Debug)]
@@ -191,11 +452,21 @@ struct ShaderParams {
     is_snapshot: u32,
 }
 
-#[derive(Component)]
-struct Rotating {
-    speed: f32,
+// 注册表生成的调色板，每种物质占四个 vec4（色相、饱和度、亮度的系数，alpha 和着色方式），按 id 排列
+#[derive(ShaderType, Clone, Debug)]
+struct SpeciesPalette {
+    entries: [Vec4; MAX_SPECIES * 4],
 }
 
+impl SpeciesPalette {
+    fn from_registry(registry: &SpeciesRegistry) -> Self {
+        SpeciesPalette {
+            entries: registry.palette().map(Vec4::from_array),
+        }
+    }
+}
+
+
 
 impl Material2d for CellMaterial {
 
@@ -212,11 +483,15 @@ impl Material2d for CellMaterial {
 
 // 主应用
 
+
+// 持有 grid.wgsl、wind.wgsl 的句柄，着色器库在程序运行期间保持加载
 #[derive(Resource)]
-struct Falg(usize);
+struct ShaderLibrary(Vec<Handle<Shader>>);
 
-#[derive(Resource,Deref)]
-struct SeedPositionReceiver(Receiver<SeedPosition>);
+// 初始场景里逐步投放的沙子和种子，按投放时的 tick 计数排好序
+// 读档时场景已经播种过了，用空的 SeedFeeder
+#[derive(Resource, Default)]
+struct SeedFeeder(VecDeque<(u64, SeedPosition)>);
 
 
 #[derive(Debug, Clone, Copy)]
@@ -231,70 +506,70 @@ struct SeedPosition {
 struct CellCanvas;
 
 
-fn rotate_system(
-    time: Res<Time>,
-    mut query: Query<(&mut Transform, &Rotating)>,
-) {
-    for (mut transform, rotating) in query.iter_mut() {
-        let angle = (time.elapsed_seconds() * rotating.speed).sin() * 30.0; // -30度到+30度之间摆动
-        transform.rotation = Quat::from_rotation_z(angle.to_radians());
-    }
-}
 
 
 
 // 更新纹理数据
-fn update_texture_data(
-    // cell_grid: Res<CellGrid>,
-    mut images: ResMut<Assets<Image>>,
+fn update_texture_data(mut cell_grid: ResMut<CellGrid>) {
+    // 纹理不在这里写：tick 留下的脏区域由 update_burns_and_cells_textures 上传
+    cell_grid.tick();
+}
+// 注册表变化（注册了新物质或改了颜色）时重新生成材质的调色板
+fn update_species_palette(
+    cell_grid: Res<CellGrid>,
     mut materials: ResMut<Assets<CellMaterial>>,
     material_query: Query<&Handle<CellMaterial>>,
-    mut cell_grid: ResMut<CellGrid>,
-
+    mut revision: Local<Option<u32>>,
 ) {
-    cell_grid.tick();
-    // 在update_texture_data中添加对齐检查
+    let current = cell_grid.registry().revision();
+    if *revision == Some(current) {
+        return;
+    }
+    *revision = Some(current);
+    let palette = SpeciesPalette::from_registry(cell_grid.registry());
     for material_handle in material_query.iter() {
         if let Some(material) = materials.get_mut(material_handle) {
-            if let Some(image) = images.get_mut(&material.data_tex) {
-    //             let format = image.texture_descriptor.format;
-    //             let bytes_per_row = image.width() * format.pixel_size() as u32;
-                let pixels = image.data.as_mut_slice();
-                for (i, cell) in cell_grid.cells.iter().enumerate() {
-                    let idx = i * 4;
-                    //             //
-                    if cell.species==Species::Seed {
-                        let (x,y)=cell_grid.get_x_y(i as i32);
-
-                    }
-                    pixels[idx] = cell.species as u8;
-                }
-            }
+            material.palette = palette.clone();
         }
     }
 }
+
+// 上一次绘制时光标所在的网格 uv，按住鼠标拖动时用来补齐两帧之间的笔画
 #[derive(Resource, Default)]
 struct LastMousePos(Option<Vec2>);
 
 // fn handle_input(
 
 // 移除原有的render_cells系统，修改handle_input和update_simulation保持不变...
-fn swap_cells(grid: &mut CellGrid, x1: usize, y1: usize, x2: usize, y2: usize) {
-    let idx1 = y1  * grid.width as usize + x1;
-    let idx2 = y2  * grid.width as usize+ x2;
-    grid.cells.swap(idx1 , idx2);
-}
 // 修改粒
 
 
 
 fn main() {
-    App::new()
+    let args: Vec<String> = std::env::args().collect();
+    let size = SimulationSize::from_args(&args);
+    if args.iter().any(|arg| arg == "--headless") {
+        headless::run(size, headless::HeadlessOptions::from_args(&args));
+        return;
+    }
+    // 流体参数从配置文件读取，运行中修改文件或在面板上拖动滑块都会立即生效（见 inspector.rs）
+    let config_path = fluid_config::config_path(&args).unwrap_or_else(|| fluid_config::DEFAULT_CONFIG_PATH.to_string());
+    let formats = FluidFormats::from_args(&args);
+    let mut app = App::new();
+    // 用CPU求解器代替GPU读回来驱动 winds
+    if args.iter().any(|arg| arg == "--cpu-fluid") {
+        app.insert_resource(FluidSolver::new(size.width as usize, size.height as usize));
+    }
+    app
         .add_plugins((
             DefaultPlugins
                 .set(RenderPlugin {
                     render_creation: WgpuSettings {
                         backends: Some(Backends::VULKAN),
+                        // 默认的 Functionality 优先级会打开适配器支持的全部特性，写进 features 反而会让不支持的适配器建不出设备；
+                        // 所以格式用不到时关掉 TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES，用得到时由 FluidFormats::with_fallback 检查
+                        disabled_features: (!formats.needs_adapter_features())
+                            .then_some(WgpuFeatures::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES),
                         ..default()
                     }
                         .into(),
@@ -302,7 +577,7 @@ fn main() {
                 })
                 .set(WindowPlugin {
                     primary_window: Some(Window {
-                        resolution: (WIDTH as f32, HEIGHT as f32).into(),
+                        resolution: (size.width as f32, size.height as f32).into(),
                         title: "Cell Simulation".to_string(),
                         ..default()
                     }),
@@ -312,25 +587,34 @@ fn main() {
 
             ExtractResourcePlugin::<FluidConfig>::default(),
             ExtractResourcePlugin::<FluidTextures>::default(),
+            ExtractResourcePlugin::<SimulationSize>::default(),
 
         ))
-        .insert_resource(CellGrid::new(WIDTH as i32, HEIGHT as i32))
+        .insert_resource(size)
+        .insert_resource(formats)
+        .insert_resource(CellGrid::new(size.width as i32, size.height as i32))
         .init_resource::<LastMousePos>()
         .init_resource::<FluidTextures>()
-        .init_resource::<FluidConfig>()
-        // .add_plugins( GameOfLifeComputePlugin)
+        .insert_resource(fluid_config::startup_config(Some(&config_path), &args))
         .add_plugins( FluidSimulationPlugin)
+        .add_plugins((
+            SimCommandPlugin,
+            PaintingPlugin,
+            InspectorPlugin { config_path },
+            RecorderPlugin { capture: CaptureOptions::from_args(&args) },
+        ))
 
         .add_systems(Startup, setup)
-        .insert_resource(Falg(0))
         // .add_systems(Render,update_texture_data)
         .add_systems(Update, (
             // handle_input,
             // update_simulation,
             // .after(handle_input),
             // debug_cameras,
-            apply_seed_positions,
+            resize::resize_simulation,
+            apply_seed_positions.after(resize::resize_simulation),
             update_texture_data.after(apply_seed_positions),
+            update_species_palette,
             // update_image.after(update_texture_data),
             // update_simulation,
             // rotate_system,
@@ -343,94 +627,81 @@ fn main() {
         .run();
 }
 
+// tick 计数到了就投放，与帧率、线程调度无关
 fn apply_seed_positions(
-    mut receiver: Res<SeedPositionReceiver>,
+    mut feeder: ResMut<SeedFeeder>,
     mut cell_grid: ResMut<CellGrid>,
 ) {
-    for position in receiver.try_iter() {
+    let ticks = cell_grid.ticks();
+    while feeder.0.front().is_some_and(|(tick, _)| *tick <= ticks) {
+        let (_, position) = feeder.0.pop_front().unwrap();
         cell_grid.paint(position.x, position.y, position.size as i32, position.s);
     }
-    // 处理所有可用的位置
-    // while let Ok(position) = receiver.0.try_recv() {
-    //     println!("rrrrrrrrrrr");
-    //     cell_grid.paint(position.x, position.y, position.size as i32, position.s);
-    // }
-    // cell_grid.winds()
 }
 
-pub fn  setup(
+const SAND_FEED_SEED: u64 = 0x5a4d_0001;
+const SEED_FEED_SEED: u64 = 0x5a4d_0002;
+// 两次投放之间隔几个 tick
+const SAND_FEED_EVERY: u64 = 3;
+const SEED_FEED_EVERY: u64 = 4;
+
+// 初始场景：预先绘制的几块物质，以及逐步投放的沙子和种子
+// 窗口模式和无窗口模式共用；随机数用固定种子，投放的位置、大小和 tick 每次都相同
+pub(crate) fn seed_scene(cell_grid: &mut CellGrid) -> SeedFeeder {
+    let width = cell_grid.width();
+    let height = cell_grid.height();
+    cell_grid.paint(width / 2, 50, 60, Species::Water);
+    cell_grid.paint(width * 3 / 4, 50, 60, Species::Fire);
+    cell_grid.paint(width - 50, height - 50, 60, Species::Lava);
+    cell_grid.paint(width - 50, height / 2, 60, Species::Dust);
+    let start = cell_grid.ticks();
+    let mut pending = Vec::new();
+
+    let mut rng = SplitMix64::seed_from_u64(SAND_FEED_SEED);
+    for (i, x) in (5..width - 5).step_by(10).enumerate() {
+        let y = height - 40 + (5.0 * (x as f64 / 20.0).sin()).floor() as i32;
+        let size = rng.gen_range(10.0..16.0);
+        pending.push((start + i as u64 * SAND_FEED_EVERY, SeedPosition { x, y, size, s: Species::Sand }));
+    }
+
+    let mut rng = SplitMix64::seed_from_u64(SEED_FEED_SEED);
+    let mut x: i32 = 40;  // 起始位置
+    let mut tick = start;
+    while x <= width - 40 {
+        // 计算波动效果的位置
+        let y = (height as f64 / 2.0 + 20.0 * (x as f64 / 20.0).sin()).floor() as i32;
+        pending.push((tick, SeedPosition { x, y, size: 6.0, s: Species::Seed }));
+        tick += SEED_FEED_EVERY;
+        // 生成随机步长 (50-60)
+        let step = 50.0 + rng.gen::<f64>() * 10.0;
+        x += step as i32;
+    }
+    // 同一个 tick 的沙子排在种子前面
+    pending.sort_by_key(|(tick, _)| *tick);
+    SeedFeeder(pending.into())
+}
+
+fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<CellMaterial>>,
     mut images: ResMut<Assets<Image>>,
     // primary_window: Query<PrimaryWindow>,
     mut fluid_textures: ResMut<FluidTextures>,
-    mut fluid_config: ResMut<FluidConfig>,
     mut cell_grid: ResMut<CellGrid>,
+    size: Res<SimulationSize>,
+    fluid_formats: Res<FluidFormats>,
+    asset_server: Res<AssetServer>,
 )
 {
-    let (tx, rx) =  bounded::<SeedPosition>(5);
-    // // 获取异步计算线程池
-    // let task_pool = AsyncComputeTaskPool::get();
-    let tx2=tx.clone();
-    cell_grid.paint(300, 50, 60, Species::Water);
-    cell_grid.paint(450, 50, 60, Species::Fire);
-    cell_grid.paint(550, 550, 60, Species::Lava);
-    cell_grid.paint(550, 300, 60, Species::Dust);
-    // 提交异步任务 - 只计算位置，不修改资源
-    // let task =  task_pool.spawn(async move {
-    std::thread::spawn(move || {
-        // 提交异步任务
-        let width = WIDTH as i32;
-        let height = HEIGHT as i32;
-        let mut rng = rand::thread_rng();
-        for x in (5..width - 5).step_by(10) {
-            let y = height - 40 + (5.0 * (x as f64 / 20.0).sin()).floor() as i32;
-            let size = rng.gen_range(10.0..16.0);
-            // cell_grid.paint(x, y, size as i32, Species::Sand);
-            let start_time = Instant::now();
-            let duration = Duration::from_secs_f32(rng.gen_range(0.0..0.1));
-            while start_time.elapsed() < duration {
-                // Spinning for 'duration', simulating doing hard work!
-            }
-            if tx.send(SeedPosition { x, y, size, s: Species::Sand }).is_err() {
-                break; // 如果接收端关闭则退出
-            }
-            // println!("sand:{:?},{:?}",x,y);
-        }
-    });
-   std::thread::spawn(move || {
-       let width = WIDTH as i32;
-       let height = HEIGHT as i32;
-       let mut rng = rand::thread_rng();
-        let mut x: i32 = 40;  // 起始位置
-
-        while x <= width - 40 {
-            // 计算波动效果的位置
-            let y = (height as f64 / 2.0 + 20.0 * (x as f64 / 20.0).sin()).floor() as i32;
-            // println!("seed:{:?},{:?}",x,y);
-            // 绘制种子
-            // cell_grid.paint(
-            //     x,
-            //     y,
-            //     6,
-            //     Species::Seed
-            // );
-            let start_time = Instant::now();
-            let duration = Duration::from_secs_f32(rng.gen_range(0.0..0.1));
-            while start_time.elapsed() < duration {
-                // Spinning for 'duration', simulating doing hard work!
-            }
-            if tx2.send(SeedPosition { x, y, size:6.0,s:Species::Seed}).is_err() {
-                break; // 如果接收端关闭则退出
-            }
-            // 生成随机步长 (50-60)
-            let step = 50.0 + rng.gen::<f64>() * 10.0;
-            x += step as i32;
-        }
-    });
-    // *seed_position_receiver = SeedPositionReceiver(Arc::new(Mutex::new(Some(rx))));
-    commands.insert_resource(SeedPositionReceiver(rx));
+    let size = *size;
+    // sand.wgsl 通过 #import sand::grid 使用的坐标函数、流体着色器通过 #import sand::wind 和 sand::boundary 使用的编解码与边界处理，加载后才能解析导入
+    commands.insert_resource(ShaderLibrary(vec![
+        asset_server.load("grid.wgsl"),
+        asset_server.load("wind.wgsl"),
+        asset_server.load("boundary.wgsl"),
+    ]));
+    commands.insert_resource(seed_scene(&mut cell_grid));
     // 创建全屏四边形
     let mut mesh = Mesh::new(bevy::render::render_resource::PrimitiveTopology::TriangleList,
                              RenderAssetUsages::all()
@@ -458,59 +729,7 @@ pub fn  setup(
     );
     mesh.insert_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
     let quad = meshes.add(mesh);
-    fn create_texture(images: &mut Assets<Image>) -> Handle<Image> {
-        let pixel_count = (WIDTH * HEIGHT) as usize;
-        // 计算数据大小（每个像素 4 字节）
-        let data_size = pixel_count * 4;
-        let initial_data = vec![0u8; data_size];
-        let mut image = Image::new(
-            Extent3d { width: WIDTH, height: HEIGHT, depth_or_array_layers: 1 },
-            TextureDimension::D2,
-            initial_data,
-            TextureFormat::Rgba8Unorm,
-            RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
-        );
-        image.sampler = ImageSampler::nearest();
-        image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
-            | TextureUsages::COPY_DST;
-        images.add(image)
-    }
 
-    // 创建存储纹理的函数
-    fn create_storage_texture(images: &mut Assets<Image>) -> Handle<Image> {
-        let pixel_count = (WIDTH * HEIGHT) as usize;
-        // 计算数据大小（每个像素 4 字节）
-        let data_size = pixel_count * 4;
-        let initial_data = vec![0u8; data_size];
-        let mut image = Image::new(
-            Extent3d { width: WIDTH, height: HEIGHT, depth_or_array_layers: 1 },
-            TextureDimension::D2,
-            initial_data,
-            TextureFormat::Rgba8Unorm,
-            RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
-        );
-        image.sampler = ImageSampler::nearest();
-        image.texture_descriptor.usage = TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING
-            | TextureUsages::COPY_DST | TextureUsages::COPY_SRC;
-        images.add(image)
-    }
-    // 初始化流体配置
-    fluid_config.velocity_dissipation = 0.99;
-    fluid_config.density_dissipation = 0.99;
-    fluid_config.curl_strength = 3.0;
-    fluid_config.pressure_dissipation = 0.99;
-    fluid_config.pressure_iterations = 20;
-
-    let (mut x,mut y) =(create_texture(&mut images),create_texture(&mut images));
-    {
-        println!("x_y_{:?},{:?}", (&x).id(),(&y).id());
-    }
-    {
-        swap(&mut x,&mut y);
-    }
-    {
-        println!("x_y_{:?},{:?}", x.id(), y.id());
-    }
     // 初始化所有纹理
     // fluid_textures.velocity = (create_texture(), create_texture());
     // fluid_textures.density = (create_texture(), create_texture());
@@ -520,16 +739,8 @@ pub fn  setup(
     // fluid_textures.burns = create_texture();
     // fluid_textures.cells = create_texture();
     // fluid_textures.velocity_out = create_texture();
-    fluid_textures.velocity = (create_texture(&mut images),create_storage_texture(&mut images) );
-    fluid_textures.density = (create_texture(&mut images), create_storage_texture(&mut images));
-    fluid_textures.pressure = (create_texture(&mut images), create_storage_texture(&mut images));
-    fluid_textures.curl = create_storage_texture(&mut images);
-    fluid_textures.divergence =  create_storage_texture(&mut images);
-    fluid_textures.burns = create_texture(&mut images);
-    fluid_textures.cells = create_storage_texture(&mut images);
-    fluid_textures.velocity_out = create_storage_texture(&mut images);
+    *fluid_textures = FluidTextures::allocate(&mut images, size, *fluid_formats);
     // let data_tex_handle = images.add(image); // 强引用在此处创建
-    let cc=create_texture(&mut images);
     // 创建材质
     let material = materials.add(CellMaterial {
         data_tex: fluid_textures.cells.clone(),
@@ -538,9 +749,10 @@ pub fn  setup(
         params: ShaderParams {
             time: 0.0,
             dpi: 1.0,
-            resolution: Vec2::new(WIDTH as f32, WIDTH as f32),
+            resolution: Vec2::new(size.width as f32, size.height as f32),
             is_snapshot: 0,
         },
+        palette: SpeciesPalette::from_registry(cell_grid.registry()),
     });
 
     commands.spawn((
@@ -549,14 +761,13 @@ pub fn  setup(
             material: material.clone(),
             transform: Transform {
                 translation: Vec3::new(0.0, 0.0, 0.0),
-                scale: Vec3::new(WIDTH as f32, HEIGHT as f32 ,1.0),
+                scale: Vec3::new(size.width as f32, size.height as f32 ,1.0),
                 ..default()
             },
 
             ..default()
         },
         CellCanvas,
-        Rotating { speed: 1.0 },
     ));
 
     commands.spawn(Camera2dBundle {
@@ -571,20 +782,29 @@ pub fn  setup(
 
 
 
-    commands.insert_resource(GameOfLifeImage { texture:  fluid_textures.cells.clone() });
+    insert_pass_images(&mut commands, &fluid_textures);
+}
+
+// 根据 FluidTextures 插入各个计算通道的 *Image 资源，启动和窗口缩放时共用
+pub(crate) fn insert_pass_images(commands: &mut Commands, fluid_textures: &FluidTextures) {
+    // 速度在一帧内的流向与 FluidSolver::step 相同，每一步读上一步的结果：
+    // 平流 velocity.0 -> velocity.1，旋度读 velocity.1，涡度约束 velocity.1 -> velocity.0，
+    // 散度读 velocity.0，梯度减法 velocity.0 -> velocity.1，velocity_out 编码 velocity.1，
+    // 最后 VelocityOutComputeNode 把 velocity.1、density.1 复制回 .0 给下一帧
     // 初始化AdvectionImage资源
     commands.insert_resource(VelocityAdvectionImage {
         velocity_tex: fluid_textures.velocity.0.clone(),
         source_tex: fluid_textures.velocity.0.clone(),
-        // 注意：这里使用cells作为风场
-        wind_tex: create_texture(&mut images),//fluid_textures.cells.clone(),
+        // 使用burns作为风场
+        wind_tex: fluid_textures.burns.clone(),
         // 输出到速度的写纹理
         output_tex: fluid_textures.velocity.1.clone(),
     });
     commands.insert_resource(DensityAdvectionImage {
         // 使用burns作为风场
         burns_tex: fluid_textures.burns.clone(),
-        velocity_tex: fluid_textures.velocity.0.clone(),
+        // 沿平流之后的速度回溯
+        velocity_tex: fluid_textures.velocity.1.clone(),
         // 使用密度作为源
         density_tex: fluid_textures.density.0.clone(),
         // 输出到密度的写纹理
@@ -593,33 +813,39 @@ pub fn  setup(
 
     // 初始化CurlImage资源
     commands.insert_resource(CurlImage {
-        velocity_tex: fluid_textures.velocity.0.clone(),
+        velocity_tex: fluid_textures.velocity.1.clone(),
         output_tex: fluid_textures.curl.clone(),
     });
     // 初始化VorticityImage资源
     commands.insert_resource(VorticityImage {
-        velocity_tex: fluid_textures.velocity.0.clone(),
+        velocity_tex: fluid_textures.velocity.1.clone(),
         curl_tex: fluid_textures.curl.clone(),
-        output_tex: fluid_textures.velocity.1.clone(),
+        output_tex: fluid_textures.velocity.0.clone(),
     });
     commands.insert_resource(DivergenceImage {
         velocity_tex: fluid_textures.velocity.0.clone(),
         output_tex: fluid_textures.divergence.clone(),
+        cells_tex: fluid_textures.cells.clone(),
     });
+    // 压力在一帧内的流向：clear 衰减上一帧的 pressure.0 并注入 burns 的压力 -> pressure.1，
+    // 压力求解在 pressure.1 和 pressure.0 之间做 pressure_iterations 次 Jacobi 迭代，结果总是落在 pressure.0，
+    // 后续的梯度减法和 velocity_out 读 pressure.0
     commands.insert_resource(ClearImage {
-        u_texture_tex: fluid_textures.burns.clone(),
-        u_wind_tex: fluid_textures.pressure.0.clone(),
+        u_texture_tex: fluid_textures.pressure.0.clone(),
+        u_wind_tex: fluid_textures.burns.clone(),
         output_tex: fluid_textures.pressure.1.clone(),
     });
     // 初始化PressureImage资源
     commands.insert_resource(PressureImage {
-        pressure_tex: fluid_textures.pressure.0.clone(),
+        pressure_tex: fluid_textures.pressure.1.clone(),
         divergence_tex: fluid_textures.divergence.clone(),
-        output_tex: fluid_textures.pressure.1.clone(),
+        output_tex: fluid_textures.pressure.0.clone(),
+        cells_tex: fluid_textures.cells.clone(),
     });
     // 初始化VelocityOutImage资源
+    // 在梯度减法之后执行，编码最终的速度和压力，供读回到 CellGrid::winds
     commands.insert_resource(VelocityOutImage {
-        velocity_tex: fluid_textures.velocity.0.clone(),
+        velocity_tex: fluid_textures.velocity.1.clone(),
         pressure_tex: fluid_textures.pressure.0.clone(),
         output_tex: fluid_textures.velocity_out.clone(),
     });
@@ -628,20 +854,21 @@ pub fn  setup(
     commands.insert_resource(GradientSubtractImage {
         wind_tex: fluid_textures.burns.clone(),          // 风场
         pressure_tex: fluid_textures.pressure.0.clone(), // 压力
-        velocity_tex: fluid_textures.velocity_out.clone(), // 使用上步输出!
+        velocity_tex: fluid_textures.velocity.0.clone(), // 涡度约束之后的速度
         cells_tex: fluid_textures.cells.clone(),
-        output_tex: fluid_textures.velocity.1.clone(),   // 写回速度
+        output_tex: fluid_textures.velocity.1.clone(),   // 最终的速度
     });
     // commands.insert_resource(DisplayImage {
     //     density_tex: fluid_textures.density.0.clone(),
-    //     output_tex: create_storage_texture(&mut images),
+    //     output_tex: create_storage_texture(&mut images, size),
     // });
+    // 调试显示读取的纹理，模式见 display.rs
     commands.insert_resource(DisplayTarget {
-            image: fluid_textures.density.0.clone()
-        });
+        density: fluid_textures.density.1.clone(),
+        velocity: fluid_textures.velocity.1.clone(),
+        pressure: fluid_textures.pressure.0.clone(),
+        curl: fluid_textures.curl.clone(),
+        divergence: fluid_textures.divergence.clone(),
+        burns: fluid_textures.burns.clone(),
+    });
 }
-
-
-
-
-
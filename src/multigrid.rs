@@ -0,0 +1,400 @@
+use std::borrow::Cow;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::*;
+use bevy::render::render_resource::binding_types::{texture_2d, texture_storage_2d, uniform_buffer};
+use bevy::render::renderer::RenderDevice;
+use bevy::render::{Render, RenderApp, RenderSet};
+use crate::boundary::{boundary_buffer, BoundaryUniforms, FluidSpecies};
+use crate::fluid_solver::{
+    multigrid_levels, MULTIGRID_COARSE_SWEEPS, MULTIGRID_POST_SWEEPS, MULTIGRID_PRE_SWEEPS,
+};
+use crate::pressure::PressureImage;
+use crate::{FluidConfig, FluidFormats, SimulationSize, WORKGROUP_SIZE};
+
+// 多重网格压力求解（FluidConfig::pressure_solver = Multigrid 时由 PressureComputeNode 调用）
+// 金字塔只存在于渲染世界：每层两张压力纹理 p[0]、p[1] 来回读写，一张右端项 rhs，都是 r32float
+// load 把 pressure.1 和 divergence 搬进第 0 层，V 循环结束后 store 把第 0 层写回 pressure.0，
+// 之后的残差统计和 gradient_subtract 与 Jacobi 模式完全相同
+// smooth 和 restrict 按 FluidConfig 的边界方式取邻居（见 boundary.wgsl），固体只在第 0 层处理
+// 各层尺寸和平滑次数与 CPU 的 FluidSolver::solve_pressure_multigrid 一致
+pub struct MultigridPlugin;
+
+impl Plugin for MultigridPlugin {
+    fn build(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app.add_systems(
+            Render,
+            (
+                prepare_multigrid_textures.in_set(RenderSet::PrepareResources),
+                prepare_bind_groups.in_set(RenderSet::PrepareBindGroups),
+            ),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app.init_resource::<MultigridPipeline>();
+    }
+}
+
+struct MultigridLevel {
+    width: u32,
+    height: u32,
+    p: [TextureView; 2],
+    rhs: TextureView,
+}
+
+impl MultigridLevel {
+    fn workgroups(&self) -> (u32, u32) {
+        (self.width.div_ceil(WORKGROUP_SIZE), self.height.div_ceil(WORKGROUP_SIZE))
+    }
+}
+
+// 按 SimulationSize 创建，尺寸变化时重新分配
+#[derive(Resource)]
+struct MultigridTextures {
+    size: SimulationSize,
+    levels: Vec<MultigridLevel>,
+}
+
+impl MultigridTextures {
+    fn new(render_device: &RenderDevice, size: SimulationSize) -> Self {
+        let create_view = |label: &'static str, width: usize, height: usize| {
+            render_device
+                .create_texture(&TextureDescriptor {
+                    label: Some(label),
+                    size: Extent3d {
+                        width: width as u32,
+                        height: height as u32,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::R32Float,
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor::default())
+        };
+        let levels = multigrid_levels(size.width as usize, size.height as usize)
+            .into_iter()
+            .map(|(width, height)| MultigridLevel {
+                width: width as u32,
+                height: height as u32,
+                p: [
+                    create_view("multigrid_pressure_0", width, height),
+                    create_view("multigrid_pressure_1", width, height),
+                ],
+                rhs: create_view("multigrid_rhs", width, height),
+            })
+            .collect();
+        MultigridTextures { size, levels }
+    }
+}
+
+fn prepare_multigrid_textures(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    size: Res<SimulationSize>,
+    textures: Option<Res<MultigridTextures>>,
+) {
+    if textures.is_some_and(|textures| textures.size == *size) {
+        return;
+    }
+    commands.insert_resource(MultigridTextures::new(&render_device, *size));
+}
+
+// 每个 kernel 一个布局，避免同一张纹理在一个绑定组里既读又写
+#[derive(Resource)]
+struct MultigridPipeline {
+    load_layout: BindGroupLayout,
+    smooth_layout: BindGroupLayout,
+    restrict_layout: BindGroupLayout,
+    prolong_layout: BindGroupLayout,
+    store_layout: BindGroupLayout,
+    load: CachedComputePipelineId,
+    smooth: CachedComputePipelineId,
+    restrict: CachedComputePipelineId,
+    prolong: CachedComputePipelineId,
+    store: CachedComputePipelineId,
+}
+
+impl FromWorld for MultigridPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let formats = *world.resource::<FluidFormats>();
+        let read = || texture_2d(TextureSampleType::Float { filterable: false });
+        let write = || texture_storage_2d(TextureFormat::R32Float, StorageTextureAccess::WriteOnly);
+        let load_layout = render_device.create_bind_group_layout(
+            "multigrid_load_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(ShaderStages::COMPUTE, (read(), read(), write(), write())),
+        );
+        // 最后两项是细胞纹理和边界
+        let boundary = || uniform_buffer::<BoundaryUniforms>(false);
+        let smooth_layout = render_device.create_bind_group_layout(
+            "multigrid_smooth_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(ShaderStages::COMPUTE, (read(), read(), write(), read(), boundary())),
+        );
+        let restrict_layout = render_device.create_bind_group_layout(
+            "multigrid_restrict_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (read(), read(), write(), write(), read(), boundary()),
+            ),
+        );
+        let prolong_layout = render_device.create_bind_group_layout(
+            "multigrid_prolong_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(ShaderStages::COMPUTE, (read(), read(), write())),
+        );
+        let store_layout = render_device.create_bind_group_layout(
+            "multigrid_store_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    read(),
+                    texture_storage_2d(formats.scalar, StorageTextureAccess::WriteOnly),
+                ),
+            ),
+        );
+        let shader = world.resource::<AssetServer>().load("multigrid.wgsl");
+        let pipeline_cache = world.resource::<PipelineCache>();
+        // 只有 store 写入 FluidTextures 的纹理，其余 kernel 都在 r32float 的金字塔内部
+        let store_defs = vec![FluidFormats::output_def(formats.scalar)];
+        let queue = |entry_point: &'static str, layout: &BindGroupLayout, shader_defs: Vec<ShaderDefVal>| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(Cow::from(entry_point)),
+                layout: vec![layout.clone()],
+                push_constant_ranges: Vec::new(),
+                shader: shader.clone(),
+                shader_defs,
+                entry_point: Cow::from(entry_point),
+            })
+        };
+        MultigridPipeline {
+            load: queue("load_main", &load_layout, vec![]),
+            smooth: queue("smooth_main", &smooth_layout, vec![]),
+            restrict: queue("restrict_main", &restrict_layout, vec![]),
+            prolong: queue("prolong_main", &prolong_layout, vec![]),
+            store: queue("store_main", &store_layout, store_defs),
+            load_layout,
+            smooth_layout,
+            restrict_layout,
+            prolong_layout,
+            store_layout,
+        }
+    }
+}
+
+// 下标是读取的压力纹理：smooth[l][i] 读 p[i] 写 p[1 - i]，
+// restrict[l][i] 读第 l 层的 p[i]、写第 l + 1 层的 rhs 和 p[0]，
+// prolong[l][i][j] 读第 l 层的 p[i] 和第 l + 1 层的 p[j]、写第 l 层的 p[1 - i]，
+// store[i] 读第 0 层的 p[i]
+#[derive(Resource)]
+struct MultigridBindGroups {
+    load: BindGroup,
+    smooth: Vec<[BindGroup; 2]>,
+    restrict: Vec<[BindGroup; 2]>,
+    prolong: Vec<[[BindGroup; 2]; 2]>,
+    store: [BindGroup; 2],
+}
+
+fn prepare_bind_groups(
+    mut commands: Commands,
+    gpu_images: Res<RenderAssets<Image>>,
+    pressure_image: Res<PressureImage>,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<MultigridPipeline>,
+    textures: Option<Res<MultigridTextures>>,
+    fluid_config: Res<FluidConfig>,
+    fluid_species: Res<FluidSpecies>,
+) {
+    let Some(textures) = textures else {
+        return;
+    };
+    let (Some(pressure), Some(divergence), Some(output), Some(cells)) = (
+        gpu_images.get(&pressure_image.pressure_tex),
+        gpu_images.get(&pressure_image.divergence_tex),
+        gpu_images.get(&pressure_image.output_tex),
+        gpu_images.get(&pressure_image.cells_tex),
+    ) else {
+        return;
+    };
+    let levels = &textures.levels;
+    let level_0 = &levels[0];
+    let boundary = boundary_buffer(&render_device, &fluid_config, *fluid_species);
+
+    let load = render_device.create_bind_group(
+        "multigrid_load_bind_group",
+        &pipeline.load_layout,
+        &BindGroupEntries::sequential((
+            &pressure.texture_view,
+            &divergence.texture_view,
+            &level_0.p[0],
+            &level_0.rhs,
+        )),
+    );
+    let smooth = levels
+        .iter()
+        .map(|level| {
+            [0, 1].map(|i| {
+                render_device.create_bind_group(
+                    "multigrid_smooth_bind_group",
+                    &pipeline.smooth_layout,
+                    &BindGroupEntries::sequential((
+                        &level.p[i],
+                        &level.rhs,
+                        &level.p[1 - i],
+                        &cells.texture_view,
+                        boundary.as_entire_binding(),
+                    )),
+                )
+            })
+        })
+        .collect();
+    let restrict = levels
+        .windows(2)
+        .map(|pair| {
+            let (fine, coarse) = (&pair[0], &pair[1]);
+            [0, 1].map(|i| {
+                render_device.create_bind_group(
+                    "multigrid_restrict_bind_group",
+                    &pipeline.restrict_layout,
+                    &BindGroupEntries::sequential((
+                        &fine.p[i],
+                        &fine.rhs,
+                        &coarse.rhs,
+                        &coarse.p[0],
+                        &cells.texture_view,
+                        boundary.as_entire_binding(),
+                    )),
+                )
+            })
+        })
+        .collect();
+    let prolong = levels
+        .windows(2)
+        .map(|pair| {
+            let (fine, coarse) = (&pair[0], &pair[1]);
+            [0, 1].map(|i| {
+                [0, 1].map(|j| {
+                    render_device.create_bind_group(
+                        "multigrid_prolong_bind_group",
+                        &pipeline.prolong_layout,
+                        &BindGroupEntries::sequential((&fine.p[i], &coarse.p[j], &fine.p[1 - i])),
+                    )
+                })
+            })
+        })
+        .collect();
+    let store = [0, 1].map(|i| {
+        render_device.create_bind_group(
+            "multigrid_store_bind_group",
+            &pipeline.store_layout,
+            &BindGroupEntries::sequential((&level_0.p[i], &output.texture_view)),
+        )
+    });
+    commands.insert_resource(MultigridBindGroups {
+        load,
+        smooth,
+        restrict,
+        prolong,
+        store,
+    });
+}
+
+// 录制 V 循环时记录每层当前的压力在 p[0] 还是 p[1]
+struct VCycleEncoder<'a> {
+    pass: ComputePass<'a>,
+    pipelines: [&'a ComputePipeline; 3],
+    bind_groups: &'a MultigridBindGroups,
+    levels: &'a [MultigridLevel],
+    current: Vec<usize>,
+}
+
+impl<'a> VCycleEncoder<'a> {
+    fn dispatch(&mut self, pipeline: usize, bind_group: &'a BindGroup, (x, y): (u32, u32)) {
+        self.pass.set_pipeline(self.pipelines[pipeline]);
+        self.pass.set_bind_group(0, bind_group, &[]);
+        self.pass.dispatch_workgroups(x, y, 1);
+    }
+
+    fn smooth(&mut self, level: usize, sweeps: u32) {
+        let bind_groups = self.bind_groups;
+        for _ in 0..sweeps {
+            let bind_group = &bind_groups.smooth[level][self.current[level]];
+            self.dispatch(0, bind_group, self.levels[level].workgroups());
+            self.current[level] ^= 1;
+        }
+    }
+
+    fn v_cycle(&mut self, level: usize) {
+        if level + 1 == self.levels.len() {
+            self.smooth(level, MULTIGRID_COARSE_SWEEPS);
+            return;
+        }
+        let bind_groups = self.bind_groups;
+        self.smooth(level, MULTIGRID_PRE_SWEEPS);
+        let bind_group = &bind_groups.restrict[level][self.current[level]];
+        self.dispatch(1, bind_group, self.levels[level + 1].workgroups());
+        self.current[level + 1] = 0;
+        self.v_cycle(level + 1);
+        let bind_group = &bind_groups.prolong[level][self.current[level]][self.current[level + 1]];
+        self.dispatch(2, bind_group, self.levels[level].workgroups());
+        self.current[level] ^= 1;
+        self.smooth(level, MULTIGRID_POST_SWEEPS);
+    }
+}
+
+// 在 encoder 里录制 cycles 次 V 循环，结果写入 pressure.0
+// 管线还没编译好或者纹理还是旧尺寸时返回 false，本帧跳过压力求解
+pub(crate) fn encode_multigrid<'a>(world: &'a World, encoder: &'a mut CommandEncoder, cycles: u32) -> bool {
+    let pipeline_cache = world.resource::<PipelineCache>();
+    let pipeline = world.resource::<MultigridPipeline>();
+    let size = *world.resource::<SimulationSize>();
+    let (Some(textures), Some(bind_groups)) = (
+        world.get_resource::<MultigridTextures>(),
+        world.get_resource::<MultigridBindGroups>(),
+    ) else {
+        return false;
+    };
+    if textures.size != size {
+        return false;
+    }
+    let (Some(load), Some(smooth), Some(restrict), Some(prolong), Some(store)) = (
+        pipeline_cache.get_compute_pipeline(pipeline.load),
+        pipeline_cache.get_compute_pipeline(pipeline.smooth),
+        pipeline_cache.get_compute_pipeline(pipeline.restrict),
+        pipeline_cache.get_compute_pipeline(pipeline.prolong),
+        pipeline_cache.get_compute_pipeline(pipeline.store),
+    ) else {
+        return false;
+    };
+
+    let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+        label: Some("Multigrid Pressure Pass"),
+        ..default()
+    });
+    let (workgroups_x, workgroups_y) = size.workgroups();
+    pass.set_pipeline(load);
+    pass.set_bind_group(0, &bind_groups.load, &[]);
+    pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+
+    let mut cycle = VCycleEncoder {
+        pass,
+        pipelines: [smooth, restrict, prolong],
+        bind_groups,
+        levels: &textures.levels,
+        current: vec![0; textures.levels.len()],
+    };
+    for _ in 0..cycles {
+        cycle.v_cycle(0);
+    }
+    let current = cycle.current[0];
+    let mut pass = cycle.pass;
+    pass.set_pipeline(store);
+    pass.set_bind_group(0, &bind_groups.store[current], &[]);
+    pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+    true
+}
@@ -0,0 +1,191 @@
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use crate::inspector::BlocksPainting;
+use crate::sim_command::{apply_sim_commands, SimCommand};
+use crate::universe::{coords, CellGrid, Species};
+use crate::LastMousePos;
+
+// 鼠标绘制：左键用当前物质画，右键擦除（Species::Empty），滚轮调整笔刷大小
+// 数字键 1-9、0 选择常用物质，[ 和 ] 在全部物质之间切换，当前物质和笔刷大小显示在窗口标题上
+// Ctrl+Z 撤销一笔，Ctrl+Shift+Z 或 Ctrl+Y 重做
+// 绘制和撤销都以 SimCommand 发出，按下到松开是一笔
+pub struct PaintingPlugin;
+
+impl Plugin for PaintingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Brush>()
+            .add_systems(Update, (
+                select_species,
+                brush_size,
+                update_window_title,
+                undo_shortcuts.before(apply_sim_commands),
+                paint_with_mouse.before(apply_sim_commands),
+            ));
+    }
+}
+
+// 调色板：注册表里除 Empty 以外的物质，按注册顺序排列，数字键对应前十个
+// 第三方注册的物质排在内置物质后面，用 [ 和 ] 切换到
+fn palette(cell_grid: &CellGrid) -> Vec<Species> {
+    cell_grid
+        .registry()
+        .iter()
+        .map(|def| def.species)
+        .filter(|species| *species != Species::Empty)
+        .collect()
+}
+
+const DIGIT_KEYS: [KeyCode; 10] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::Digit0,
+];
+
+const MIN_BRUSH: i32 = 1;
+const MAX_BRUSH: i32 = 100;
+
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct Brush {
+    pub(crate) species: Species,
+    pub(crate) size: i32,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            species: Species::Sand,
+            size: 10,
+        }
+    }
+}
+
+fn select_species(keys: Res<ButtonInput<KeyCode>>, cell_grid: Res<CellGrid>, mut brush: ResMut<Brush>) {
+    let palette = palette(&cell_grid);
+    if palette.is_empty() {
+        return;
+    }
+    for (key, species) in DIGIT_KEYS.iter().zip(palette.iter()) {
+        if keys.just_pressed(*key) {
+            brush.species = *species;
+        }
+    }
+    let current = palette.iter().position(|s| *s == brush.species).unwrap_or(0);
+    if keys.just_pressed(KeyCode::BracketRight) {
+        brush.species = palette[(current + 1) % palette.len()];
+    }
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        brush.species = palette[(current + palette.len() - 1) % palette.len()];
+    }
+}
+
+fn brush_size(mut wheel: EventReader<MouseWheel>, mut brush: ResMut<Brush>) {
+    for event in wheel.read() {
+        // 触控板按像素滚动，换算成大致的行数
+        let lines = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 20.0,
+        };
+        let step = (lines.abs().ceil() as i32).max(1) * lines.signum() as i32;
+        brush.size = (brush.size + step).clamp(MIN_BRUSH, MAX_BRUSH);
+    }
+}
+
+fn update_window_title(
+    brush: Res<Brush>,
+    cell_grid: Res<CellGrid>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !brush.is_changed() {
+        return;
+    }
+    let name = cell_grid.registry().name(brush.species);
+    for mut window in windows.iter_mut() {
+        window.title = format!("Cell Simulation - {} (brush {})", name, brush.size);
+    }
+}
+
+fn undo_shortcuts(keys: Res<ButtonInput<KeyCode>>, mut commands: EventWriter<SimCommand>) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !ctrl {
+        return;
+    }
+    if keys.just_pressed(KeyCode::KeyZ) {
+        commands.send(if shift { SimCommand::Redo } else { SimCommand::Undo });
+    }
+    if keys.just_pressed(KeyCode::KeyY) {
+        commands.send(SimCommand::Redo);
+    }
+}
+
+// 拖动时在上一帧和这一帧的光标位置之间插值，快速移动也能画出连续的线
+fn paint_with_mouse(
+    buttons: Res<ButtonInput<MouseButton>>,
+    brush: Res<Brush>,
+    mut last_mouse_pos: ResMut<LastMousePos>,
+    mut commands: EventWriter<SimCommand>,
+    cell_grid: Res<CellGrid>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<&Camera>,
+    panel: Query<&Interaction, With<BlocksPainting>>,
+) {
+    if buttons.just_released(MouseButton::Left) || buttons.just_released(MouseButton::Right) {
+        commands.send(SimCommand::EndStroke);
+    }
+    let species = if buttons.pressed(MouseButton::Left) {
+        brush.species
+    } else if buttons.pressed(MouseButton::Right) {
+        Species::Empty
+    } else {
+        last_mouse_pos.0 = None;
+        return;
+    };
+    // 光标在流体参数面板上，或者正在拖动面板上的滑块
+    if panel.iter().any(|interaction| *interaction != Interaction::None) {
+        last_mouse_pos.0 = None;
+        return;
+    }
+    let (Ok(window), Some(camera)) = (windows.get_single(), cameras.iter().find(|c| c.is_active)) else {
+        return;
+    };
+    let Some(uv) = window
+        .cursor_position()
+        .and_then(|cursor| cursor_to_uv(camera, cursor))
+    else {
+        last_mouse_pos.0 = None;
+        return;
+    };
+
+    let (width, height) = (cell_grid.width(), cell_grid.height());
+    let from = last_mouse_pos.0.unwrap_or(uv);
+    // 按笔刷半径的一半取样，圆与圆之间不会留缝
+    let spacing = (brush.size as f32 / 2.0).max(1.0);
+    let cells = (uv - from) * Vec2::new(width as f32, height as f32);
+    let steps = (cells.length() / spacing).ceil().max(1.0) as i32;
+    for i in 1..=steps {
+        let p = from.lerp(uv, i as f32 / steps as f32);
+        let (x, y) = coords::uv_to_cell(width, height, p.x, p.y);
+        commands.send(SimCommand::Paint { x, y, size: brush.size, species });
+    }
+    last_mouse_pos.0 = Some(uv);
+}
+
+// sand.wgsl 的顶点着色器直接把网格顶点当作裁剪空间坐标输出，四边形总是铺满相机视口，
+// 它的 Transform 和相机的投影不影响位置，所以光标只需换算到视口内的相对位置
+// 窗口坐标和网格一样 y 向下，视口内的相对位置就是网格 uv（见 universe::coords）
+fn cursor_to_uv(camera: &Camera, cursor: Vec2) -> Option<Vec2> {
+    let viewport = camera.logical_viewport_rect()?;
+    let local = (cursor - viewport.min) / viewport.size();
+    if local.x < 0.0 || local.x >= 1.0 || local.y < 0.0 || local.y >= 1.0 {
+        return None;
+    }
+    Some(local)
+}
@@ -1,26 +1,54 @@
 use std::borrow::Cow;
-use bevy::core_pipeline::core_2d::graph::{Core2d, Node2d};
+use std::sync::atomic::{AtomicU32, Ordering};
 use bevy::prelude::*;
 use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
 use bevy::render::render_asset::RenderAssets;
 use bevy::render::{render_graph, Render, RenderApp, RenderSet};
-use bevy::render::render_graph::{NodeRunError, RenderGraph, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNodeRunner};
+use bevy::render::render_graph::{NodeRunError, RenderGraphContext, RenderLabel};
 use bevy::render::render_resource::*;
-use bevy::render::render_resource::binding_types::{sampler, texture_2d, texture_storage_2d, uniform_buffer};
+use bevy::render::render_resource::binding_types::{sampler, storage_buffer_sized, texture_2d, texture_storage_2d, uniform_buffer};
 use bevy::render::renderer::{RenderContext, RenderDevice};
-use crate::{FluidConfig, HEIGHT, WIDTH, WORKGROUP_SIZE};
-// ... 原有代码 ...
+use bevy::render::texture::GpuImage;
+use crossbeam_channel::{bounded, Receiver, Sender};
+use crate::boundary::{boundary_buffer, BoundaryUniforms, FluidSpecies};
+use crate::fluid_solver::FluidSolver;
+use crate::multigrid::encode_multigrid;
+use crate::readback::StagingRing;
+use crate::{FluidConfig, FluidFormats, PressureSolver, SimulationSize};
+
+// 压力求解：每帧按 FluidConfig::pressure_iterations 做若干次 Jacobi 迭代
+// clear 把本帧的初值写进 pressure.1，迭代在 pressure.1 和 pressure.0 之间来回读写，
+// 结果最后停在 pressure.1 时（迭代次数为偶数，包括 0）再拷贝到 pressure.0，后续的 pass 总是读 pressure.0
+// pressure_solver 为 Multigrid 时改由 multigrid.rs 做 V 循环，结果同样写入 pressure.0
+// 主世界的 PressureResidualRequest 为 true 时（流体参数面板打开着），迭代结束后 residual_main 统计最终压力场的残差，
+// 经 StagingRing 异步读回主世界的 PressureResidual，用来观察收敛情况；没人看的时候既不统计也不读回
+// CPU 路径见 FluidSolver::solve_pressure_iterations
 pub struct PressurePlugin;
 
 impl Plugin for PressurePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(ExtractResourcePlugin::<PressureImage>::default());
+        let (sender, receiver) = bounded::<PressureResidual>(1);
+        app.add_plugins((
+            ExtractResourcePlugin::<PressureImage>::default(),
+            ExtractResourcePlugin::<PressureResidualRequest>::default(),
+        ))
+            .init_resource::<PressureResidual>()
+            .init_resource::<PressureResidualRequest>()
+            .insert_resource(PressureResidualReceiver(receiver))
+            .add_systems(Update, apply_pressure_residual);
 
         let render_app = app.sub_app_mut(RenderApp);
         render_app
+            .insert_resource(PressureResidualSender(sender))
             .add_systems(
                 Render,
-                prepare_bind_group.in_set(RenderSet::PrepareBindGroups),
+                (
+                    prepare_residual_buffers.in_set(RenderSet::PrepareResources),
+                    prepare_bind_group.in_set(RenderSet::PrepareBindGroups),
+                    map_pressure_residual
+                        .after(RenderSet::Render)
+                        .before(RenderSet::Cleanup),
+                ),
             );
 
 
@@ -32,6 +60,25 @@ impl Plugin for PressurePlugin {
     }
 }
 
+// 最近一帧压力求解的收敛情况：残差是每个像素 |L + R + T + B - 4C - div| 的平均值
+// GPU 路径由 residual_main 读回，CPU 路径（--cpu-fluid、无窗口模式）由 FluidSolver 写入
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq)]
+pub struct PressureResidual {
+    pub residual: f32,
+    // Jacobi 的迭代次数，多重网格时是 V 循环次数，见 FluidConfig::pressure_steps
+    pub iterations: u32,
+}
+
+// 是否需要 GPU 统计并读回残差，由流体参数面板在显示时打开
+#[derive(Resource, Clone, Copy, Default, ExtractResource)]
+pub struct PressureResidualRequest(pub bool);
+
+#[derive(Resource)]
+struct PressureResidualReceiver(Receiver<PressureResidual>);
+
+#[derive(Resource)]
+struct PressureResidualSender(Sender<PressureResidual>);
+
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone,RenderLabel)]
 pub(crate) struct PressureComputeLabel;
@@ -48,23 +95,70 @@ struct PressureUniforms {
 #[derive(Resource)]
 pub struct PressurePipeline {
     pub(crate) pipeline: CachedComputePipelineId,
+    residual_pipeline: CachedComputePipelineId,
     bind_group_layout: BindGroupLayout,
 }
 
-#[derive(Resource, Clone, ExtractResource, AsBindGroup)]
+#[derive(Resource, Clone, ExtractResource)]
 pub struct PressureImage {
-    #[texture(0, visibility(compute))]
-    #[sampler(3)]
     pub(crate) pressure_tex: Handle<Image>,
-    #[texture(1, visibility(compute))]
-    #[sampler(4)]
     pub(crate) divergence_tex: Handle<Image>,
-    #[storage_texture(2, image_format = Rgba8Unorm, access = WriteOnly)]
     pub(crate) output_tex: Handle<Image>,
+    // 判断固体用的细胞纹理
+    pub(crate) cells_tex: Handle<Image>,
+}
+
+// forward 读 pressure.1 写 pressure.0，backward 反过来
+#[derive(Resource)]
+pub struct PressureBindGroup {
+    forward: BindGroup,
+    backward: BindGroup,
 }
 
+// 同时在读回的帧数
+const RESIDUAL_READBACK_SLOTS: usize = 3;
+
+// residual_main 写入的每个工作组的部分和，以及读回它们的暂存缓冲区
+// 按 SimulationSize 创建，尺寸变化时重新分配
 #[derive(Resource)]
-pub struct PressureBindGroup(pub(crate) BindGroup);
+struct PressureResidualBuffers {
+    partials: Buffer,
+    ring: StagingRing,
+    size: SimulationSize,
+    // 每个槽写入时用了多少次迭代（或 V 循环）
+    iterations: Vec<AtomicU32>,
+}
+
+impl PressureResidualBuffers {
+    fn new(render_device: &RenderDevice, size: SimulationSize) -> Self {
+        let (workgroups_x, workgroups_y) = size.workgroups();
+        let bytes = (workgroups_x * workgroups_y * 4) as u64;
+        let partials = render_device.create_buffer(&BufferDescriptor {
+            label: Some("pressure_residual_buffer"),
+            size: bytes,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        PressureResidualBuffers {
+            partials,
+            ring: StagingRing::new(render_device, "pressure_residual_readback_buffer", bytes, RESIDUAL_READBACK_SLOTS),
+            size,
+            iterations: (0..RESIDUAL_READBACK_SLOTS).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+}
+
+fn prepare_residual_buffers(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    size: Res<SimulationSize>,
+    buffers: Option<Res<PressureResidualBuffers>>,
+) {
+    if buffers.is_some_and(|buffers| buffers.size == *size) {
+        return;
+    }
+    commands.insert_resource(PressureResidualBuffers::new(&render_device, *size));
+}
 
 fn prepare_bind_group(
     mut commands: Commands,
@@ -72,11 +166,15 @@ fn prepare_bind_group(
     pressure_image: Res<PressureImage>,
     render_device: Res<RenderDevice>,
     pressure_pipeline: Res<PressurePipeline>,
+    residual_buffers: Res<PressureResidualBuffers>,
+    size: Res<SimulationSize>,
     fluid_config: Res<FluidConfig>,
+    fluid_species: Res<FluidSpecies>,
 ) {
     let pressure_tex_view = gpu_images.get(&pressure_image.pressure_tex).unwrap();
     let divergence_tex_view = gpu_images.get(&pressure_image.divergence_tex).unwrap();
     let output_tex_view = gpu_images.get(&pressure_image.output_tex).unwrap();
+    let cells_tex_view = gpu_images.get(&pressure_image.cells_tex).unwrap();
 
     let pressure_sampler = render_device.create_sampler(&SamplerDescriptor {
         address_mode_u: AddressMode::ClampToEdge,
@@ -93,7 +191,7 @@ fn prepare_bind_group(
         ..Default::default()
     });
     let uniforms = PressureUniforms {
-        texel_size: [1.0 / WIDTH as f32, 1.0 / HEIGHT as f32],
+        texel_size: size.texel_size(),
         alpha: 1.0,  // 标准Gauss-Seidel迭代
         reciprocal_beta: 0.25,  // 对应2D网格的系数
     };
@@ -103,27 +201,36 @@ fn prepare_bind_group(
         contents: bytemuck::cast_slice(&[uniforms]),
         usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
     });
+    let boundary = boundary_buffer(&render_device, &fluid_config, *fluid_species);
 
-    let bind_group = render_device.create_bind_group(
-        "pressure_bind_group",
-        &pressure_pipeline.bind_group_layout,
-        &BindGroupEntries::sequential
-            (
+    let create_bind_group = |label: &str, source: &GpuImage, target: &GpuImage| {
+        render_device.create_bind_group(
+            label,
+            &pressure_pipeline.bind_group_layout,
+            &BindGroupEntries::sequential
                 (
-                    &pressure_tex_view.texture_view,
-                    &divergence_tex_view.texture_view,
-                    &output_tex_view.texture_view,
-                    &pressure_sampler,
-                    &divergence_sampler,
-                    BindingResource::Buffer(BufferBinding {
-                        buffer: &uniform_buffer,
-                        offset: 0,
-                        size: None,
-                    })
+                    (
+                        &source.texture_view,
+                        &divergence_tex_view.texture_view,
+                        &target.texture_view,
+                        &pressure_sampler,
+                        &divergence_sampler,
+                        BindingResource::Buffer(BufferBinding {
+                            buffer: &uniform_buffer,
+                            offset: 0,
+                            size: None,
+                        }),
+                        residual_buffers.partials.as_entire_binding(),
+                        &cells_tex_view.texture_view,
+                        boundary.as_entire_binding(),
+                    )
                 )
-            )
-    );
-    commands.insert_resource(PressureBindGroup(bind_group));
+        )
+    };
+    commands.insert_resource(PressureBindGroup {
+        forward: create_bind_group("pressure_forward_bind_group", pressure_tex_view, output_tex_view),
+        backward: create_bind_group("pressure_backward_bind_group", output_tex_view, pressure_tex_view),
+    });
 }
 
 
@@ -131,6 +238,7 @@ fn prepare_bind_group(
 impl FromWorld for PressurePipeline {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
+        let formats = *world.resource::<FluidFormats>();
         let bind_group_layout = render_device.create_bind_group_layout(
             "curl_bind_group_layout",
             &BindGroupLayoutEntries::sequential(
@@ -138,27 +246,35 @@ impl FromWorld for PressurePipeline {
                 (
                     texture_2d(TextureSampleType::Float { filterable: true }),
                     texture_2d(TextureSampleType::Float { filterable: true }),
-                    texture_storage_2d(TextureFormat::Rgba8Unorm, StorageTextureAccess::WriteOnly),
+                    texture_storage_2d(formats.scalar, StorageTextureAccess::WriteOnly),
                     sampler(SamplerBindingType::Filtering),
                     sampler(SamplerBindingType::Filtering),
-                    uniform_buffer::<PressureUniforms>(false)
+                    uniform_buffer::<PressureUniforms>(false),
+                    storage_buffer_sized(false, None),
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    uniform_buffer::<BoundaryUniforms>(false),
                 )
             ));
         let shader = world
             .resource::<AssetServer>()
             .load("pressure.wgsl");
         let pipeline_cache = world.resource::<PipelineCache>();
-        let pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-            label: None,
-            layout: vec![bind_group_layout.clone()],
-            push_constant_ranges: Vec::new(),
-            shader: shader.clone(),
-            shader_defs: vec![],
-            entry_point: Cow::from("pressure_main"),
-        });
+        let queue = |entry_point: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(Cow::from(entry_point)),
+                layout: vec![bind_group_layout.clone()],
+                push_constant_ranges: Vec::new(),
+                shader: shader.clone(),
+                shader_defs: vec![FluidFormats::output_def(formats.scalar)],
+                entry_point: Cow::from(entry_point),
+            })
+        };
+        let pipeline = queue("pressure_main");
+        let residual_pipeline = queue("residual_main");
 
         PressurePipeline {
             pipeline,
+            residual_pipeline,
             bind_group_layout,
         }
     }
@@ -170,27 +286,131 @@ pub(crate) struct PressureComputeNode;
 impl render_graph::Node for PressureComputeNode {
     fn run(
         &self,
-        graph: &mut RenderGraphContext,
+        _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
         let pipeline_cache = world.resource::<PipelineCache>();
         let pressure_pipeline = world.resource::<PressurePipeline>();
         let pressure_bind_group = world.resource::<PressureBindGroup>();
-        // println!("Pressure  Compute Pass");
-        let mut pass = render_context
-            .command_encoder()
-            .begin_compute_pass(&ComputePassDescriptor {
-                label: Some("Pressure Compute Pass"),
+        let pressure_image = world.resource::<PressureImage>();
+        let residual_buffers = world.resource::<PressureResidualBuffers>();
+        let gpu_images = world.resource::<RenderAssets<Image>>();
+        let size = *world.resource::<SimulationSize>();
+        let fluid_config = world.resource::<FluidConfig>();
+        let iterations = fluid_config.pressure_iterations;
+        let (Some(pipeline), Some(residual_pipeline)) = (
+            pipeline_cache.get_compute_pipeline(pressure_pipeline.pipeline),
+            pipeline_cache.get_compute_pipeline(pressure_pipeline.residual_pipeline),
+        ) else {
+            return Ok(());
+        };
+        let (Some(pressure_1), Some(pressure_0)) = (
+            gpu_images.get(&pressure_image.pressure_tex),
+            gpu_images.get(&pressure_image.output_tex),
+        ) else {
+            return Ok(());
+        };
+        let (workgroups_x, workgroups_y) = size.workgroups();
+        let encoder = render_context.command_encoder();
+
+        if fluid_config.pressure_solver == PressureSolver::Multigrid {
+            if !encode_multigrid(world, encoder, fluid_config.multigrid_cycles) {
+                return Ok(());
+            }
+        } else {
+            encode_jacobi(encoder, pipeline, pressure_bind_group, iterations, (workgroups_x, workgroups_y));
+            if iterations % 2 == 0 {
+                encoder.copy_texture_to_texture(
+                    pressure_1.texture.as_image_copy(),
+                    pressure_0.texture.as_image_copy(),
+                    size.extent(),
+                );
+            }
+        }
+
+        if !world.get_resource::<PressureResidualRequest>().is_some_and(|request| request.0) {
+            return Ok(());
+        }
+        // 所有槽都还在等待映射时这一帧不统计
+        let Some((slot, readback)) = residual_buffers.ring.acquire() else {
+            return Ok(());
+        };
+        // backward 读的是 pressure.0，也就是最终结果
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Pressure Residual Pass"),
                 ..default()
             });
-
-        if let Some(pipeline) = pipeline_cache.get_compute_pipeline(pressure_pipeline.pipeline) {
-            pass.set_pipeline(pipeline);
-            pass.set_bind_group(0, &pressure_bind_group.0, &[]);
-            pass.dispatch_workgroups(WIDTH / WORKGROUP_SIZE, HEIGHT / WORKGROUP_SIZE, 1);
+            pass.set_pipeline(residual_pipeline);
+            pass.set_bind_group(0, &pressure_bind_group.backward, &[]);
+            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
         }
+        encoder.copy_buffer_to_buffer(&residual_buffers.partials, 0, readback, 0, residual_buffers.partials.size());
+        residual_buffers.iterations[slot].store(fluid_config.pressure_steps(), Ordering::Relaxed);
 
         Ok(())
     }
 }
+
+// 每次 dispatch 一次迭代，forward、backward 交替
+fn encode_jacobi(
+    encoder: &mut CommandEncoder,
+    pipeline: &ComputePipeline,
+    pressure_bind_group: &PressureBindGroup,
+    iterations: u32,
+    (workgroups_x, workgroups_y): (u32, u32),
+) {
+    // 同一个 compute pass 里每次 dispatch 是独立的使用范围，来回读写同一对纹理是允许的
+    let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+        label: Some("Pressure Compute Pass"),
+        ..default()
+    });
+    pass.set_pipeline(pipeline);
+    for i in 0..iterations {
+        let bind_group = if i % 2 == 0 {
+            &pressure_bind_group.forward
+        } else {
+            &pressure_bind_group.backward
+        };
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+    }
+}
+
+// 渲染完成后推进映射，不等待GPU；映射完成的槽把各工作组的部分和加起来，换算成平均值发回主世界
+fn map_pressure_residual(
+    buffers: Option<Res<PressureResidualBuffers>>,
+    render_device: Res<RenderDevice>,
+    sender: Res<PressureResidualSender>,
+) {
+    let Some(buffers) = buffers else {
+        return;
+    };
+    buffers.ring.poll(&render_device, |slot, data| {
+        let sum: f64 = data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .sum();
+        let residual = PressureResidual {
+            residual: (sum / buffers.size.pixel_count() as f64) as f32,
+            iterations: buffers.iterations[slot].load(Ordering::Relaxed),
+        };
+        // 主世界还没取走上一帧的数据时直接丢弃
+        let _ = sender.0.try_send(residual);
+    });
+}
+
+// 使用CPU求解器时残差由 step_cpu_fluid 写入，GPU读回的数据被丢弃
+fn apply_pressure_residual(
+    receiver: Res<PressureResidualReceiver>,
+    mut residual: ResMut<PressureResidual>,
+    cpu_solver: Option<Res<FluidSolver>>,
+) {
+    let Some(latest) = receiver.0.try_iter().last() else {
+        return;
+    };
+    if cpu_solver.is_none() {
+        *residual = latest;
+    }
+}
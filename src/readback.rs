@@ -0,0 +1,296 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_graph::{self, NodeRunError, RenderGraphContext, RenderLabel};
+use bevy::render::render_resource::*;
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::{Render, RenderApp, RenderSet};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use crate::advection::DensityAdvectionImage;
+use crate::fluid_solver::FluidSolver;
+use crate::pressure::PressureResidual;
+use crate::universe::{CellGrid, Wind};
+use crate::velocity_out::VelocityOutImage;
+use crate::{update_texture_data, FluidConfig, SimulationSize};
+
+// 把流体结果读回CPU，写入 CellGrid::winds，让 blow_wind、update_dust、update_stone 能感受到真实的风
+// velocity_out 纹理已经按 Wind 的字节布局编码了 (vx, vy, pressure)，density 取密度平流的输出
+// 如果存在 FluidSolver 资源（--cpu-fluid），则改用CPU求解器，GPU读回的数据被丢弃
+// 读回不等待GPU：拷贝进 StagingRing 的两个槽轮流使用，映射完成后才交给主世界，CPU 用的是上一帧（或更早）的风场
+pub struct WindReadbackPlugin;
+
+impl Plugin for WindReadbackPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = bounded::<Vec<Wind>>(1);
+        app.insert_resource(WindReadbackReceiver(receiver))
+            .add_systems(Update, (
+                apply_wind_readback.before(update_texture_data),
+                step_cpu_fluid.after(update_texture_data),
+            ));
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .insert_resource(WindReadbackSender(sender))
+            .add_systems(
+                Render,
+                (
+                    prepare_wind_readback_buffers.in_set(RenderSet::PrepareResources),
+                    map_wind_readback
+                        .after(RenderSet::Render)
+                        .before(RenderSet::Cleanup),
+                ),
+            );
+    }
+}
+
+#[derive(Resource)]
+struct WindReadbackReceiver(Receiver<Vec<Wind>>);
+
+#[derive(Resource)]
+struct WindReadbackSender(Sender<Vec<Wind>>);
+
+// 槽的状态：空闲 -> 节点写入了拷贝命令 -> 已发起映射 -> 映射完成，读出后回到空闲
+const SLOT_FREE: u8 = 0;
+const SLOT_COPIED: u8 = 1;
+const SLOT_MAPPING: u8 = 2;
+const SLOT_MAPPED: u8 = 3;
+
+// 一圈可映射的暂存缓冲区，用来异步读回GPU数据
+// 节点用 acquire 占一个空闲的槽并往里拷贝；渲染之后 poll 给新拷贝的槽发起映射，
+// 只用 Maintain::Poll 推进设备，不等待GPU，映射完成的槽在这一帧或之后的某一帧读出并释放
+// 所有槽都还没读出时 acquire 返回 None，这一帧不读回
+pub(crate) struct StagingRing {
+    buffers: Vec<Buffer>,
+    states: Vec<Arc<AtomicU8>>,
+    // 每个槽被占用时的序号，同一帧映射完成的几个槽按它从旧到新读出
+    order: Vec<AtomicU64>,
+    next: AtomicU64,
+}
+
+impl StagingRing {
+    pub(crate) fn new(render_device: &RenderDevice, label: &'static str, size: u64, slots: usize) -> Self {
+        let buffers = (0..slots)
+            .map(|_| {
+                render_device.create_buffer(&BufferDescriptor {
+                    label: Some(label),
+                    size,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+        StagingRing {
+            buffers,
+            states: (0..slots).map(|_| Arc::new(AtomicU8::new(SLOT_FREE))).collect(),
+            order: (0..slots).map(|_| AtomicU64::new(0)).collect(),
+            next: AtomicU64::new(0),
+        }
+    }
+
+    // 占用一个空闲的槽，返回槽号和它的缓冲区，调用方必须在本帧的命令里写满这个缓冲区
+    pub(crate) fn acquire(&self) -> Option<(usize, &Buffer)> {
+        let slot = self.states.iter().position(|state| {
+            state
+                .compare_exchange(SLOT_FREE, SLOT_COPIED, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        })?;
+        self.order[slot].store(self.next.fetch_add(1, Ordering::Relaxed), Ordering::Relaxed);
+        Some((slot, &self.buffers[slot]))
+    }
+
+    // 在 RenderSet::Render 提交命令之后调用，read 收到槽号和缓冲区的内容
+    pub(crate) fn poll(&self, render_device: &RenderDevice, mut read: impl FnMut(usize, &[u8])) {
+        for (buffer, state) in self.buffers.iter().zip(&self.states) {
+            if state
+                .compare_exchange(SLOT_COPIED, SLOT_MAPPING, Ordering::AcqRel, Ordering::Relaxed)
+                .is_err()
+            {
+                continue;
+            }
+            let state = state.clone();
+            buffer.slice(..).map_async(MapMode::Read, move |result| {
+                // 映射失败（比如缓冲区已随尺寸变化被丢弃）时直接放回
+                let next = if result.is_ok() { SLOT_MAPPED } else { SLOT_FREE };
+                state.store(next, Ordering::Release);
+            });
+        }
+        render_device.poll(Maintain::Poll);
+
+        let mut ready: Vec<usize> = (0..self.buffers.len())
+            .filter(|&slot| self.states[slot].load(Ordering::Acquire) == SLOT_MAPPED)
+            .collect();
+        ready.sort_by_key(|&slot| self.order[slot].load(Ordering::Relaxed));
+        for slot in ready {
+            let buffer = &self.buffers[slot];
+            read(slot, &buffer.slice(..).get_mapped_range()[..]);
+            buffer.unmap();
+            self.states[slot].store(SLOT_FREE, Ordering::Release);
+        }
+    }
+}
+
+// 同时在读回的帧数，两个槽轮流使用
+const WIND_READBACK_SLOTS: usize = 2;
+
+// 纹理拷贝的目标缓冲区，每个槽前半是 velocity_out，后半（从 plane_bytes 开始）是密度
+// 每行按 COPY_BYTES_PER_ROW_ALIGNMENT 对齐，所以 plane_bytes 也满足拷贝偏移的对齐
+// 按 SimulationSize 创建，尺寸变化时重新分配，旧尺寸还没读出的帧随之丢弃
+#[derive(Resource)]
+struct WindReadbackBuffers {
+    ring: StagingRing,
+    size: SimulationSize,
+    padded_bytes_per_row: usize,
+    plane_bytes: usize,
+}
+
+impl WindReadbackBuffers {
+    fn new(render_device: &RenderDevice, size: SimulationSize) -> Self {
+        let padded_bytes_per_row = RenderDevice::align_copy_bytes_per_row(size.width as usize * 4);
+        let plane_bytes = padded_bytes_per_row * size.height as usize;
+        WindReadbackBuffers {
+            ring: StagingRing::new(
+                render_device,
+                "wind_readback_buffer",
+                (plane_bytes * 2) as u64,
+                WIND_READBACK_SLOTS,
+            ),
+            size,
+            padded_bytes_per_row,
+            plane_bytes,
+        }
+    }
+}
+
+fn prepare_wind_readback_buffers(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    size: Res<SimulationSize>,
+    buffers: Option<Res<WindReadbackBuffers>>,
+) {
+    if buffers.is_some_and(|buffers| buffers.size == *size) {
+        return;
+    }
+    commands.insert_resource(WindReadbackBuffers::new(&render_device, *size));
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub(crate) struct WindReadbackLabel;
+
+#[derive(Default)]
+pub(crate) struct WindReadbackNode;
+
+impl render_graph::Node for WindReadbackNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let gpu_images = world.resource::<RenderAssets<Image>>();
+        let (Some(buffers), Some(velocity_out_image), Some(density_image)) = (
+            world.get_resource::<WindReadbackBuffers>(),
+            world.get_resource::<VelocityOutImage>(),
+            world.get_resource::<DensityAdvectionImage>(),
+        ) else {
+            return Ok(());
+        };
+        let (Some(velocity_out), Some(density)) = (
+            gpu_images.get(&velocity_out_image.output_tex),
+            gpu_images.get(&density_image.output_tex),
+        ) else {
+            return Ok(());
+        };
+        // 纹理还是旧尺寸时跳过，等待重新分配
+        if velocity_out.size.x as u32 != buffers.size.width || velocity_out.size.y as u32 != buffers.size.height {
+            return Ok(());
+        }
+
+        // 两个槽都还在等待映射时跳过这一帧
+        let Some((_, buffer)) = buffers.ring.acquire() else {
+            return Ok(());
+        };
+
+        let encoder = render_context.command_encoder();
+        for (gpu_image, offset) in [(velocity_out, 0), (density, buffers.plane_bytes)] {
+            encoder.copy_texture_to_buffer(
+                gpu_image.texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer,
+                    layout: ImageDataLayout {
+                        offset: offset as u64,
+                        bytes_per_row: Some(buffers.padded_bytes_per_row as u32),
+                        rows_per_image: None,
+                    },
+                },
+                buffers.size.extent(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+// 渲染完成后推进映射，不等待GPU；映射完成的槽去掉行对齐的填充，组装成 Wind 发回主世界
+fn map_wind_readback(
+    buffers: Option<Res<WindReadbackBuffers>>,
+    render_device: Res<RenderDevice>,
+    sender: Res<WindReadbackSender>,
+) {
+    let Some(buffers) = buffers else {
+        return;
+    };
+    buffers.ring.poll(&render_device, |_, data| {
+        let (velocity_out, density) = data.split_at(buffers.plane_bytes);
+        let mut winds = Vec::with_capacity(buffers.size.pixel_count());
+        for y in 0..buffers.size.height as usize {
+            for x in 0..buffers.size.width as usize {
+                let offset = y * buffers.padded_bytes_per_row + x * 4;
+                winds.push(Wind {
+                    dx: velocity_out[offset],
+                    dy: velocity_out[offset + 1],
+                    pressure: velocity_out[offset + 2],
+                    density: density[offset],
+                });
+            }
+        }
+        // 主世界还没取走上一帧的数据时直接丢弃
+        let _ = sender.0.try_send(winds);
+    });
+}
+
+// 取最新一帧的读回结果，写入 winds
+fn apply_wind_readback(
+    receiver: Res<WindReadbackReceiver>,
+    mut cell_grid: ResMut<CellGrid>,
+    cpu_solver: Option<Res<FluidSolver>>,
+) {
+    let Some(winds) = receiver.0.try_iter().last() else {
+        return;
+    };
+    if cpu_solver.is_some() {
+        return;
+    }
+    if winds.len() == cell_grid.winds.len() {
+        cell_grid.set_winds(&winds);
+    }
+}
+
+// CPU求解器的回退路径：在 tick 产生新的 burns 之后推进一步并写回 winds
+fn step_cpu_fluid(
+    cpu_solver: Option<ResMut<FluidSolver>>,
+    fluid_config: Res<FluidConfig>,
+    time: Res<Time>,
+    mut cell_grid: ResMut<CellGrid>,
+    mut residual: ResMut<PressureResidual>,
+) {
+    let Some(mut cpu_solver) = cpu_solver else {
+        return;
+    };
+    let dt = time.delta_seconds().min(0.016);
+    cpu_solver.step_grid(&fluid_config, dt, &mut cell_grid);
+    *residual = PressureResidual {
+        residual: cpu_solver.residual,
+        iterations: fluid_config.pressure_steps(),
+    };
+}
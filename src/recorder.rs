@@ -0,0 +1,53 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use crate::capture::{CaptureOptions, Recorder};
+use crate::universe::CellGrid;
+use crate::update_texture_data;
+
+// 窗口模式的录制（格式和参数见 capture.rs）：R 键开始或停止，每次 tick 之后取帧，退出时写完 GIF
+// 录的是 CellGrid 的细胞层，不是屏幕上显示的画面；这里只出图，编码在后台线程
+pub struct RecorderPlugin {
+    pub(crate) capture: CaptureOptions,
+}
+
+impl Plugin for RecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Recorder::new(self.capture.clone()))
+            .add_systems(Update, (
+                toggle_recording,
+                capture_frame.after(update_texture_data).after(toggle_recording),
+            ))
+            // 关闭窗口时 AppExit 在 Update 之后才发出
+            .add_systems(Last, finish_on_exit);
+    }
+}
+
+fn toggle_recording(keys: Res<ButtonInput<KeyCode>>, mut recorder: ResMut<Recorder>) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl || !keys.just_pressed(KeyCode::KeyR) {
+        return;
+    }
+    if recorder.is_active() {
+        if let Err(e) = recorder.stop() {
+            eprintln!("failed to finish capture: {}", e);
+        }
+    } else {
+        recorder.start();
+    }
+}
+
+// 写失败时停止录制，避免每帧重复报错
+fn capture_frame(cell_grid: Res<CellGrid>, mut recorder: ResMut<Recorder>) {
+    if let Err(e) = recorder.capture(&cell_grid) {
+        eprintln!("capture failed: {}", e);
+        let _ = recorder.stop();
+    }
+}
+
+fn finish_on_exit(mut exit: EventReader<AppExit>, mut recorder: ResMut<Recorder>) {
+    if exit.read().last().is_some() {
+        if let Err(e) = recorder.stop() {
+            eprintln!("failed to finish capture: {}", e);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use bevy::window::WindowResized;
+use crate::fluid_solver::FluidSolver;
+use crate::universe::CellGrid;
+use crate::{insert_pass_images, CellCanvas, CellMaterial, FluidFormats, FluidTextures, SimulationSize};
+
+// 窗口缩放时让模拟跟随窗口尺寸：
+// CellGrid 保留已有内容（裁剪或补空），FluidTextures 和所有 *Image 资源按新尺寸重新分配，
+// 渲染世界里的绑定组每帧都会根据 *Image 重新创建，读回缓冲区按 SimulationSize 自动重建
+pub(crate) fn resize_simulation(
+    mut commands: Commands,
+    mut resized: EventReader<WindowResized>,
+    mut size: ResMut<SimulationSize>,
+    mut cell_grid: ResMut<CellGrid>,
+    mut images: ResMut<Assets<Image>>,
+    mut fluid_textures: ResMut<FluidTextures>,
+    fluid_formats: Res<FluidFormats>,
+    mut materials: ResMut<Assets<CellMaterial>>,
+    mut canvas_query: Query<(&Handle<CellMaterial>, &mut Transform), With<CellCanvas>>,
+    fluid_solver: Option<ResMut<FluidSolver>>,
+) {
+    // 拖动窗口边缘时一帧可能收到多个事件，只处理最后一个
+    let Some(event) = resized.read().last() else {
+        return;
+    };
+    // 最小化时窗口尺寸为0，保持原来的网格
+    if event.width < 1.0 || event.height < 1.0 {
+        return;
+    }
+    let new_size = SimulationSize::new(event.width as u32, event.height as u32);
+    if new_size == *size {
+        return;
+    }
+    info!("resize simulation {}x{} -> {}x{}", size.width, size.height, new_size.width, new_size.height);
+    *size = new_size;
+
+    cell_grid.resize(new_size.width as i32, new_size.height as i32);
+    if let Some(mut fluid_solver) = fluid_solver {
+        *fluid_solver = FluidSolver::new(new_size.width as usize, new_size.height as usize);
+    }
+
+    // 旧纹理在所有句柄释放后由资源系统回收
+    *fluid_textures = FluidTextures::allocate(&mut images, new_size, *fluid_formats);
+    insert_pass_images(&mut commands, &fluid_textures);
+
+    for (material_handle, mut transform) in canvas_query.iter_mut() {
+        if let Some(material) = materials.get_mut(material_handle) {
+            material.data_tex = fluid_textures.cells.clone();
+            material.params.resolution = Vec2::new(new_size.width as f32, new_size.height as f32);
+        }
+        transform.scale = Vec3::new(new_size.width as f32, new_size.height as f32, 1.0);
+    }
+}
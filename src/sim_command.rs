@@ -0,0 +1,50 @@
+use bevy::prelude::*;
+use crate::universe::{CellGrid, Species};
+use crate::update_texture_data;
+
+// 修改网格的命令，鼠标绘制、快捷键和脚本都通过它操作 CellGrid
+// 连续的 Paint 属于同一笔，直到 EndStroke；撤销和重做以笔画为单位
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub enum SimCommand {
+    Paint {
+        x: i32,
+        y: i32,
+        size: i32,
+        species: Species,
+    },
+    EndStroke,
+    Undo,
+    Redo,
+}
+
+pub struct SimCommandPlugin;
+
+impl Plugin for SimCommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SimCommand>()
+            .add_systems(Update, apply_sim_commands.before(update_texture_data));
+    }
+}
+
+pub(crate) fn apply_sim_commands(
+    mut commands: EventReader<SimCommand>,
+    mut cell_grid: ResMut<CellGrid>,
+) {
+    for command in commands.read() {
+        match *command {
+            SimCommand::Paint { x, y, size, species } => {
+                if !cell_grid.in_stroke() {
+                    cell_grid.begin_stroke();
+                }
+                cell_grid.paint(x, y, size, species);
+            }
+            SimCommand::EndStroke => cell_grid.end_stroke(),
+            SimCommand::Undo => {
+                cell_grid.undo();
+            }
+            SimCommand::Redo => {
+                cell_grid.redo();
+            }
+        }
+    }
+}
@@ -1,9 +1,38 @@
-use std::collections::VecDeque;
-use std::mem;
+use std::collections::HashMap;
+use std::fmt;
 use bevy::prelude::Resource;
 use rand_xoshiro::SplitMix64;
 use rand::{Rng, SeedableRng};
 
+mod activity;
+pub mod coords;
+mod heat;
+mod history;
+mod png;
+mod registry;
+mod render;
+mod replay;
+#[cfg(test)]
+mod scenario;
+mod shard;
+mod snapshot;
+#[cfg(test)]
+mod tests;
+
+// 注册第三方物质、读写回放用到的类型，程序本身不一定都用得到
+#[allow(unused_imports)]
+pub use activity::{TexelRect, SLEEP_AFTER};
+pub use heat::AMBIENT_TEMPERATURE;
+pub use png::UnknownSpecies;
+#[allow(unused_imports)]
+pub use registry::{ColourStyle, HeatSource, PhaseChange, SpeciesColour, SpeciesDef, SpeciesRegistry, MAX_SPECIES};
+pub use render::{render_cells_rgba, RenderParams, RgbaImage};
+#[allow(unused_imports)]
+pub use replay::{Divergence, Edit, EditEvent, ReplayLog};
+#[allow(unused_imports)]
+pub use shard::CHUNK_SIZE;
+use shard::Shard;
+
 static EMPTY_CELL: Cell = Cell {
     species: Species::Empty,
     ra: 0,
@@ -16,11 +45,25 @@ pub struct CellGrid {
     pub(crate) width: i32,
     pub(crate) height: i32,
     pub(crate) cells: Vec<Cell>,
-    undo_stack: VecDeque<Vec<Cell>>,
+    // 以笔画为单位的撤销/重做历史
+    history: history::History,
     pub(crate) winds: Vec<Wind>,
     pub(crate) burns: Vec<Wind>,
     generation: u8,
     rng: SplitMix64,
+    // 每种物质通过 set_fluid 向流体注入时的强度
+    emission: [Emission; MAX_SPECIES],
+    // 物质的行为和参数，tick 通过它分发更新
+    registry: SpeciesRegistry,
+    // 单调递增的 tick 计数（generation 会回绕），回放用它定位 paint
+    ticks: u64,
+    recording: Option<ReplayLog>,
+    // 并行 tick 的线程数，见 shard.rs
+    tick_threads: usize,
+    // 每个块的休眠状态和脏标记，见 activity.rs
+    activity: activity::Activity,
+    // 温度场，见 heat.rs
+    heat: heat::Heat,
 }
 
 
@@ -32,63 +75,9 @@ impl CellGrid {
                 self.cells[idx] = EMPTY_CELL;
             }
         }
+        self.reset_temperature();
+        self.wake_all();
     }
-    pub fn tick(&mut self) {
-        // let mut next = self.cells.clone();
-        // let dx = self.winds[(self.width * self.height / 2) as usize].dx;
-        // let js: JsValue = (dx).into();
-        // console::log_2(&"dx: ".into(), &js);
-
-        for x in 0..self.width {
-            for y in 0..self.height {
-                let cell = self.get_cell(x, y);
-                let wind = self.get_wind(x, y);
-                CellGrid::blow_wind(
-                    cell,
-                    wind,
-                    SandApi {
-                        universe: self,
-                        x,
-                        y,
-                    },
-                )
-            }
-        }
-        self.generation = self.generation.wrapping_add(1);
-        if self.generation  == 255 {
-            self.generation /=2;
-        }
-        for x in 0..self.width {
-            let scanx = if self.generation % 2 == 0 {
-                self.width - (1 + x)
-            } else {
-                x
-            };
-
-            for y in 0..self.height {
-                let idx = self.get_index(scanx, y);
-                let cell = self.get_cell(scanx, y);
-
-                self.burns[idx] = Wind {
-                    dx: 0,
-                    dy: 0,
-                    pressure: 0,
-                    density: 0,
-                };
-                CellGrid::update_cell(
-                    cell,
-                    SandApi {
-                        universe: self,
-                        x: scanx,
-                        y,
-                    },
-                );
-            }
-        }
-
-        self.generation = self.generation.wrapping_add(1);
-    }
-
     pub fn width(&self) -> i32 {
         self.width
     }
@@ -108,8 +97,35 @@ impl CellGrid {
     pub fn burns(&self) -> *const Wind {
         self.burns.as_ptr()
     }
+
+    pub fn generation(&self) -> u8 {
+        self.generation
+    }
+
+    // 按细胞纹理的布局（species, ra, rb, clock）导出所有细胞，顺序与纹理的像素一致
+    pub fn cells_rgba(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.cells.len() * 4);
+        for cell in self.cells.iter() {
+            data.push(cell.species.id());
+            data.push(cell.ra);
+            data.push(cell.rb);
+            data.push(cell.clock);
+        }
+        data
+    }
+
+    // 统计每种物质的细胞数量，按数量从多到少排列
+    pub fn species_counts(&self) -> Vec<(Species, usize)> {
+        let mut counts: HashMap<Species, usize> = HashMap::new();
+        for cell in self.cells.iter() {
+            *counts.entry(cell.species).or_insert(0) += 1;
+        }
+        let mut counts: Vec<(Species, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.id().cmp(&b.0.id())));
+        counts
+    }
     pub fn paint(&mut self, x: i32, y: i32, size: i32, species: Species) {
-        let size = size;
+        self.record_edit(Edit::Paint { x, y, size, species });
         let radius: f64 = (size as f64) / 2.0;
 
         let floor = (radius + 1.0) as i32;
@@ -128,47 +144,28 @@ impl CellGrid {
                     continue;
                 }
                 if self.get_cell(px, py).species == Species::Empty || species == Species::Empty {
-                    self.cells[i] = Cell {
-                        species: species,
+                    let cell = Cell {
+                        species,
                         ra: 60
                             + (size as u8)
                             + (self.rng.gen::<f32>() * 30.) as u8
-                            + ((self.generation % 127) as i8 - 60).abs() as u8,
+                            + ((self.generation % 127) as i8 - 60).unsigned_abs(),
                         rb: 0,
                         clock: self.generation,
-                    }
+                    };
+                    self.note_edit(i, cell);
+                    self.cells[i] = cell;
+                    self.heat.temperature[i] = self.registry.initial_temperature(species);
+                    self.heat.latent[i] = 0.0;
+                    self.activity.wake_cell(px, py);
                 }
             }
         }
     }
 
-    pub fn push_undo(&mut self) {
-        self.undo_stack.push_front(self.cells.clone());
-        self.undo_stack.truncate(50);
-    }
-
-    pub fn pop_undo(&mut self) {
-        let old_state = self.undo_stack.pop_front();
-        match old_state {
-            Some(state) => self.cells = state,
-            None => (),
-        };
-    }
-
-    pub fn flush_undos(&mut self) {
-        self.undo_stack.clear();
-    }
-
     pub fn new(width: i32, height: i32) -> CellGrid {
         let cells = (0..width * height).map(|_i| EMPTY_CELL).collect();
-        let winds: Vec<Wind> = (0..width * height)
-            .map(|_i| Wind {
-                dx: 0,
-                dy: 0,
-                pressure: 0,
-                density: 0,
-            })
-            .collect();
+        let winds: Vec<Wind> = (0..width * height).map(|_i| CALM_WIND).collect();
 
         let burns: Vec<Wind> = (0..width * height)
             .map(|_i| Wind {
@@ -183,30 +180,78 @@ impl CellGrid {
             width,
             height,
             cells,
-            undo_stack: VecDeque::with_capacity(50),
+            history: history::History::default(),
             burns,
             winds,
             generation: 0,
             rng,
+            emission: Emission::defaults(),
+            registry: SpeciesRegistry::builtin(),
+            ticks: 0,
+            recording: None,
+            tick_threads: CellGrid::default_tick_threads(),
+            activity: activity::Activity::new(width, height),
+            heat: heat::Heat::new(width, height),
         }
     }
+
+    // 改变网格尺寸，保留从 (0, 0) 开始的重叠区域，多出的部分裁掉，新增的部分为空
+    // 风场和温度按同样的方式保留，burns 每帧都会重新生成所以直接清空
+    pub fn resize(&mut self, width: i32, height: i32) {
+        if width == self.width && height == self.height {
+            return;
+        }
+        let mut resized = CellGrid::new(width, height);
+        for x in 0..width.min(self.width) {
+            for y in 0..height.min(self.height) {
+                let old_idx = self.get_index(x, y);
+                let new_idx = resized.get_index(x, y);
+                resized.cells[new_idx] = self.cells[old_idx];
+                resized.winds[new_idx] = self.winds[old_idx];
+                resized.heat.temperature[new_idx] = self.heat.temperature[old_idx];
+                resized.heat.latent[new_idx] = self.heat.latent[old_idx];
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.cells = resized.cells;
+        self.winds = resized.winds;
+        self.burns = resized.burns;
+        self.activity = resized.activity;
+        self.heat = resized.heat;
+        self.refresh_windy_chunks();
+        // 撤销历史里的下标已经不对了
+        self.history.clear();
+    }
+
+    pub fn emission(&self, species: Species) -> Emission {
+        self.emission[species.index()]
+    }
+
+    pub fn set_emission(&mut self, species: Species, emission: Emission) {
+        self.emission[species.index()] = emission;
+    }
+
+    pub fn registry(&self) -> &SpeciesRegistry {
+        &self.registry
+    }
+
+    // 注册新物质或替换内置物质，改动会在下一次 tick 生效
+    pub fn registry_mut(&mut self) -> &mut SpeciesRegistry {
+        &mut self.registry
+    }
 }
 impl CellGrid {
 fn get_index(&self, x: i32, y: i32) -> usize {
-    (x * self.height + y) as usize
+    coords::cell_index(self.width, x, y)
 }
 
 fn get_cell(&self, x: i32, y: i32) -> Cell {
     let i = self.get_index(x, y);
-    return self.cells[i];
+    self.cells[i]
 }
 pub fn get_x_y(&self,i:i32)->(i32,i32){
-    (i / self.height,i % self.height)
-}
-
-fn get_wind(&self, x: i32, y: i32) -> Wind {
-    let i = self.get_index(x, y);
-    return self.winds[i];
+    coords::cell_coords(self.width, i as usize)
 }
 
 fn blow_wind(cell: Cell, wind: Wind, mut api: SandApi) {
@@ -219,45 +264,12 @@ fn blow_wind(cell: Cell, wind: Wind, mut api: SandApi) {
     let mut dx = 0;
     let mut dy = 0;
 
-    let threshold = match cell.species {
-        Species::Empty => 500,
-        Species::Wall => 500,
-        Species::Cloner => 500,
-
-        Species::Stone => 70,
-        Species::Wood => 70,
-
-        Species::Plant => 60,
-        Species::Lava => 60,
-        Species::Ice => 60,
-
-        Species::Fungus => 54,
-
-        Species::Oil => 50,
-
-        // Intentionally left out and covered by the default case
-        // Species::Water => 40,
-        // Species::Acid => 40,
-        Species::Seed => 35,
-
-        Species::Sand => 30,
-        Species::Mite => 30,
-        Species::Rocket => 30,
-
-        Species::Dust => 10,
-        Species::Fire => 5,
-        Species::Gas => 5,
-        /*
-         Some hacked species values exist outside of the enum values.
-         Making sure the default case is emitted allows "BELP" to have a defined wind threshold.
-         Originally, threshold was a hardcoded value, so this preserves that original glitch behavior.
-         See: https://sandspiel.club/#eMlYGC52XIto0NM1WjaJ
-        */
-        _ => 40,
-    };
+    // 未注册的 id 也有默认阈值 40，保留 Sandspiel 里 "BELP" 之类 hack 物质的行为
+    // See: https://sandspiel.club/#eMlYGC52XIto0NM1WjaJ
+    let threshold = api.universe.registry.wind_threshold(cell.species);
 
-    let wx = (wind.dy as i32) - 126;
-    let wy = (wind.dx as i32) - 126;
+    let wx = (wind.dx as i32) - WIND_ZERO as i32;
+    let wy = (wind.dy as i32) - WIND_ZERO as i32;
 
     if wx > threshold {
         dx = 1;
@@ -275,19 +287,11 @@ fn blow_wind(cell: Cell, wind: Wind, mut api: SandApi) {
         api.set(0, 0, EMPTY_CELL);
         if dy == -1
             && api.get(dx, -2).species == Species::Empty
-            && (cell.species == Species::Sand
-            || cell.species == Species::Water
-            || cell.species == Species::Lava
-            || cell.species == Species::Acid
-            || cell.species == Species::Mite
-            || cell.species == Species::Dust
-            || cell.species == Species::Oil
-            || cell.species == Species::Rocket)
+            && api.universe.registry.wind_lift(cell.species)
         {
             dy = -2;
         }
         api.set(dx, dy, cell);
-        return;
     }
 }
 fn update_cell(cell: Cell, api: SandApi) {
@@ -307,19 +311,12 @@ pub struct Cell {
     pub(crate) clock: u8,
 }
 // Cell 的方法：
-// new：这是一个构造函数，创建一个新的 Cell 实例。它会基于物种（species）和随机生成的数值来初始化 ra 和 rb 属性。
-// update：调用细胞的 species 更新方法来改变细胞的状态。这个方法通过 SandApi（API 代理）来执行物种的更新逻辑。
+// update：从注册表里取出细胞物质的更新函数来改变细胞的状态，未注册的物质什么也不做。这个方法通过 SandApi（API 代理）来执行物种的更新逻辑。
+// 新细胞由 SandApi::new_cell 创建，随机数取自网格的 rng，保证回放可复现
 impl Cell {
-    pub fn new(species: Species) -> Cell {
-        Cell {
-            species: species,
-            ra: 100 + (rand::random::<f32>() * 50.) as u8,
-            rb: 0,
-            clock: 0,
-        }
-    }
     pub fn update(&self, api: SandApi) {
-        self.species.update(*self, api);
+        let update = api.universe.registry.update_fn(self.species);
+        update(*self, api);
     }
 }
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -330,72 +327,152 @@ pub struct Wind {
     pub(crate) density: u8,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,Default)]
-#[repr(u8)]
-pub enum Species {
-    #[default]
-    Empty = 0,
-    Wall = 1,
-    Sand = 2,
-    Water = 3,
-    // X = 21,
-    Stone = 13,
-    Ice = 9,
-    Gas = 4,
-    Cloner = 5,
+// winds 的 dx、dy 字节里表示静止的值，沿用 Sandspiel 的 126；
+// blow_wind、热平流、wake_windy_chunks 和流体的编解码（encode_wind、assets/wind.wgsl 的 WIND_ZERO）都以它为零
+pub const WIND_ZERO: u8 = 126;
+
+// 静止的风，新网格的风场从这里开始
+pub const CALM_WIND: Wind = Wind {
+    dx: WIND_ZERO,
+    dy: WIND_ZERO,
+    pressure: 0,
+    density: 0,
+};
+
+// 发射源强度：物质调用 set_fluid 写入 burns 时，各通道按这里的倍数缩放
+// burns 每帧上传到GPU，在密度平流（density）、clear（pressure）和梯度减法（dx, dy）中注入流体
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Emission {
+    pub(crate) velocity: f32,
+    pub(crate) pressure: f32,
+    pub(crate) density: f32,
+}
+
+impl Default for Emission {
+    fn default() -> Self {
+        Emission {
+            velocity: 1.0,
+            pressure: 1.0,
+            density: 1.0,
+        }
+    }
+}
+
+impl Emission {
+    pub const NONE: Emission = Emission {
+        velocity: 0.0,
+        pressure: 0.0,
+        density: 0.0,
+    };
+
+    pub fn new(velocity: f32, pressure: f32, density: f32) -> Emission {
+        Emission {
+            velocity,
+            pressure,
+            density,
+        }
+    }
+
+    // 各物质的默认强度，未列出的保持原样
+    fn defaults() -> [Emission; MAX_SPECIES] {
+        let mut table = [Emission::default(); MAX_SPECIES];
+        table[Species::Fire.index()] = Emission::new(1.0, 1.0, 1.2);
+        table[Species::Lava.index()] = Emission::new(1.0, 1.0, 1.5);
+        table[Species::Rocket.index()] = Emission::new(1.0, 1.5, 1.0);
+        // 粉尘爆炸主要是压力
+        table[Species::Dust.index()] = Emission::new(1.0, 1.5, 1.0);
+        table
+    }
+
+    fn scale(v: u8, k: f32) -> u8 {
+        (v as f32 * k).round().clamp(0.0, 255.0) as u8
+    }
+
+    pub fn apply(&self, wind: Wind) -> Wind {
+        Wind {
+            dx: Emission::scale(wind.dx, self.velocity),
+            dy: Emission::scale(wind.dy, self.velocity),
+            pressure: Emission::scale(wind.pressure, self.pressure),
+            density: Emission::scale(wind.density, self.density),
+        }
+    }
+}
+
+// 物质就是细胞纹理 r 通道里的一个字节，具体行为由 SpeciesRegistry 决定
+// 内置物质以关联常量的形式给出，第三方物质可以用任意未占用的 id（小于 MAX_SPECIES）
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Species(pub u8);
+
+#[allow(non_upper_case_globals)]
+impl Species {
+    pub const Empty: Species = Species(0);
+    pub const Wall: Species = Species(1);
+    pub const Sand: Species = Species(2);
+    pub const Water: Species = Species(3);
+    pub const Gas: Species = Species(4);
+    pub const Cloner: Species = Species(5);
+    pub const Fire: Species = Species(6);
+    pub const Wood: Species = Species(7);
+    pub const Lava: Species = Species(8);
+    pub const Ice: Species = Species(9);
     // Sink = 10,
-    Mite = 15,
-    Wood = 7,
-    Plant = 11,
-    Fungus = 18,
-    Seed = 19,
-    Fire = 6,
-    Lava = 8,
-    Acid = 12,
-    Dust = 14,
-    Oil = 16,
-    Rocket = 17,
+    pub const Plant: Species = Species(11);
+    pub const Acid: Species = Species(12);
+    pub const Stone: Species = Species(13);
+    pub const Dust: Species = Species(14);
+    pub const Mite: Species = Species(15);
+    pub const Oil: Species = Species(16);
+    pub const Rocket: Species = Species(17);
+    pub const Fungus: Species = Species(18);
+    pub const Seed: Species = Species(19);
+    pub const Glass: Species = Species(20);
+    // X = 21,
 }
+
 impl Species {
-    // Species::update 方法是一个分发器，根据不同的物种类型调用不同的更新函数。每个物种的行为是由其对应的 update_* 方法决定的。
-    pub fn update(&self, cell: Cell, api: SandApi) {
-        match self {
-            Species::Empty => {}
-            Species::Wall => {}
-            Species::Sand => update_sand(cell, api),
-            Species::Dust => update_dust(cell, api),
-            Species::Water => update_water(cell, api),
-            Species::Stone => update_stone(cell, api),
-            Species::Gas => update_gas(cell, api),
-            Species::Cloner => update_cloner(cell, api),
-            Species::Rocket => update_rocket(cell, api),
-            Species::Fire => update_fire(cell, api),
-            Species::Wood => update_wood(cell, api),
-            Species::Lava => update_lava(cell, api),
-            Species::Ice => update_ice(cell, api),
-            // Species::Snow => update_ice(cell, api),
-            //lightning
-            // Species::Sink => update_sink(cell, api),
-            Species::Plant => update_plant(cell, api),
-            Species::Acid => update_acid(cell, api),
-            Species::Mite => update_mite(cell, api),
-            Species::Oil => update_oil(cell, api),
-            Species::Fungus => update_fungus(cell, api),
-            Species::Seed => update_seed(cell, api),
-            // Species::X => update_x(cell, api),
+    // 从细胞纹理、存档里的字节还原物质，超出注册表范围的值返回 None
+    // 是否真的注册过要问 SpeciesRegistry::contains
+    pub fn from_u8(value: u8) -> Option<Species> {
+        if (value as usize) < MAX_SPECIES {
+            Some(Species(value))
+        } else {
+            None
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        self.0
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// 内置物质打印名字，和原来枚举的 Debug 输出一致
+impl fmt::Debug for Species {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match SpeciesRegistry::builtin_name(*self) {
+            Some(name) => f.write_str(name),
+            None => write!(f, "Species({})", self.0),
         }
     }
 }
 
-pub struct SandApi<'a> {
+// 物质规则操作网格的接口，读写范围限制在当前细胞周围 ±2 以内，并行 tick 依赖这一点
+pub struct SandApi<'a, 'g> {
     x: i32,
     y: i32,
-    universe: &'a mut CellGrid,
+    universe: &'a mut Shard<'g>,
+    // 正在更新的细胞的物质，用于查找发射强度
+    species: Species,
+    // 正在更新的细胞已经把温度带到了新位置
+    carried: bool,
 }
 
-impl<'a> SandApi<'a> {
+impl SandApi<'_, '_> {
     pub fn get(&mut self, dx: i32, dy: i32) -> Cell {
-        if dx > 2 || dx < -2 || dy > 2 || dy < -2 {
+        if !(-2..=2).contains(&dx) || !(-2..=2).contains(&dy) {
             panic!("oob set");
         }
         let nx = self.x + dx;
@@ -411,7 +488,7 @@ impl<'a> SandApi<'a> {
         self.universe.get_cell(nx, ny)
     }
     pub fn set(&mut self, dx: i32, dy: i32, v: Cell) {
-        if dx > 2 || dx < -2 || dy > 2 || dy < -2 {
+        if !(-2..=2).contains(&dx) || !(-2..=2).contains(&dy) {
             panic!("oob set");
         }
         let nx = self.x + dx;
@@ -421,9 +498,21 @@ impl<'a> SandApi<'a> {
             return;
         }
         let i = self.universe.get_index(nx, ny);
+        // 正在更新的细胞第一次被写到别处时（下落、流动、被风吹动），它的温度跟着过去：
+        // 与目标位置交换温度，被挤开的细胞或空气换到原来的位置
+        if !self.carried && (dx != 0 || dy != 0) && v.species == self.species {
+            let here = self.universe.get_index(self.x, self.y);
+            self.universe.swap_heat(here, i);
+            self.carried = true;
+        }
+        let old = self.universe.cells[i];
         // v.clock += 1;
         self.universe.cells[i] = v;
         self.universe.cells[i].clock = self.universe.generation.wrapping_add(1);
+        // 只刷新 clock 的写入（原地不动的细胞）不算变化，不会让块保持清醒
+        if old.species != v.species || old.ra != v.ra || old.rb != v.rb {
+            self.universe.touch(nx, ny);
+        }
     }
     pub fn get_fluid(&mut self) -> Wind {
         let idx = self.universe.get_index(self.x, self.y);
@@ -432,14 +521,39 @@ impl<'a> SandApi<'a> {
     }
     pub fn set_fluid(&mut self, v: Wind) {
         let idx = self.universe.get_index(self.x, self.y);
+        let emission = self.universe.emission(self.species);
+
+        self.universe.burns[idx] = emission.apply(v);
+    }
 
-        self.universe.burns[idx] = v;
+    // 当前细胞所在位置的温度（摄氏度），见 heat.rs
+    pub fn temperature(&self) -> f32 {
+        self.universe.temperature[self.universe.get_index(self.x, self.y)]
     }
 
     pub fn rand_int(&mut self, n: i32) -> i32 {
         self.universe.rng.gen_range(0..n)
     }
 
+    // tick 中的随机数都从当前块的 rng 里取，块的种子来自网格的 rng，这样读档后的演化可以逐位复现
+    pub fn rand_f32(&mut self) -> f32 {
+        self.universe.rng.gen::<f32>()
+    }
+
+    // 当前细胞能否沉入 nbr：nbr 是比它轻的流体
+    pub fn sinks_into(&self, nbr: Cell) -> bool {
+        self.universe.registry.sinks_into(self.species, nbr.species)
+    }
+
+    pub fn new_cell(&mut self, species: Species) -> Cell {
+        Cell {
+            species,
+            ra: 100 + (self.rand_f32() * 50.) as u8,
+            rb: 0,
+            clock: 0,
+        }
+    }
+
     pub fn once_in(&mut self, n: i32) -> bool {
         self.rand_int(n) == 0
     }
@@ -490,7 +604,7 @@ impl<'a> SandApi<'a> {
 //
 // 如果下方是空的，沙子会下落。
 // 如果旁边是空的，则沙子会向旁边移动。
-// 如果周围有水、气体、油或酸，沙子也会交换位置。
+// 如果下方是比沙子轻的流体（水、气体、油或酸），沙子也会交换位置。
 
 // 沙子的更新逻辑是根据其周围的细胞状态来决定的。
 pub fn update_sand(cell: Cell, mut api: SandApi) {
@@ -503,11 +617,7 @@ pub fn update_sand(cell: Cell, mut api: SandApi) {
     } else if api.get(dx, 1).species == Species::Empty {
         api.set(0, 0, EMPTY_CELL);
         api.set(dx, 1, cell);
-    } else if nbr.species == Species::Water
-        || nbr.species == Species::Gas
-        || nbr.species == Species::Oil
-        || nbr.species == Species::Acid
-    {
+    } else if api.sinks_into(nbr) {
         api.set(0, 0, nbr);
         api.set(0, 1, cell);
     } else {
@@ -529,7 +639,7 @@ pub fn update_dust(cell: Cell, mut api: SandApi) {
             0,
             Cell {
                 species: Species::Fire,
-                ra: (150 + (cell.ra / 10)) as u8,
+                ra: (150 + (cell.ra / 10)),
                 rb: 0,
                 clock: 0,
             },
@@ -588,11 +698,7 @@ pub fn update_stone(cell: Cell, mut api: SandApi) {
     if nbr_species == Species::Empty {
         api.set(0, 0, EMPTY_CELL);
         api.set(0, 1, cell);
-    } else if nbr_species == Species::Water
-        || nbr_species == Species::Gas
-        || nbr_species == Species::Oil
-        || nbr_species == Species::Acid
-    {
+    } else if api.sinks_into(nbr) {
         api.set(0, 0, nbr);
         api.set(0, 1, cell);
     } else {
@@ -666,8 +772,8 @@ pub fn update_water(cell: Cell, mut api: SandApi) {
         // 如果水附近有其他水细胞，函数会检查它们的 ra 值（可能是颜色或状态指示符）。
         // 如果它们不同，水会通过复制另一个水细胞的 ra 值来进行“扩散”。
 
-        if nbr.species == Species::Water {
-            if nbr.ra % 2 != cell.ra % 2 {
+        if nbr.species == Species::Water
+            && nbr.ra % 2 != cell.ra % 2 {
                 api.set(
                     dx,
                     dy,
@@ -677,7 +783,6 @@ pub fn update_water(cell: Cell, mut api: SandApi) {
                     },
                 )
             }
-        }
     } else if dx0.species == Species::Empty || dx0.species == Species::Oil {
         // 当前水流方向上的邻居是否为空（Species::Empty）或者含有油   如果是空的或者是油，水就可以流到该位置。
         // 模拟水流在碰到空细胞或油时的行为，并尝试使水与周围的水细胞发生交互，特别是在它们的 ra
@@ -967,7 +1072,7 @@ pub fn update_gas(cell: Cell, mut api: SandApi) {
 // 多样化的克隆条件：可以根据 generation 或 ra 值调整克隆体的克隆行为，使其更加有趣和复杂。
 // 克隆体之间的竞争或互动：可以加入克隆体之间的互动规则，比如克隆体相互之间的冲突或竞争。
 pub fn update_cloner(cell: Cell, mut api: SandApi) {
-    let mut clone_species = unsafe { mem::transmute(cell.rb as u8) };  // 将 `cell.rb` 转换为物种类型
+    let mut clone_species = Species(cell.rb);  // 将 `cell.rb` 转换为物种类型
     let g = api.universe.generation;  // 获取当前的宇宙代数
     // 这部分代码是用来遍历克隆体周围的 3x3 区域（包括当前位置）。
     // dx 和 dy 分别代表 x 和 y 方向上的偏移，范围从 -1 到 1。
@@ -994,7 +1099,7 @@ pub fn update_cloner(cell: Cell, mut api: SandApi) {
                         Cell {
                             species: cell.species,
                             ra: 200,
-                            rb: clone_species as u8,
+                            rb: clone_species.id(),
                             clock: 0,
                         },
                     );
@@ -1017,7 +1122,7 @@ pub fn update_cloner(cell: Cell, mut api: SandApi) {
                 // 如果选择的位置为空（Species::Empty），则克隆体会将一个新细胞放置在该位置，ra 会根据当前代数（g）和一个随机值来计算，以使得新克隆的细胞在某种程度上具备一定的随机性。
                 // 新创建的克隆体的 rb 值被设置为 0，表示它是一个新生的克隆体。
                 if api.rand_int(100) > 90 && api.get(dx, dy).species == Species::Empty {
-                    let ra = 80 + api.rand_int(30) as u8 + ((g % 127) as i8 - 60).abs() as u8;
+                    let ra = 80 + api.rand_int(30) as u8 + ((g % 127) as i8 - 60).unsigned_abs();
                     api.set(
                         dx,
                         dy,
@@ -1073,7 +1178,7 @@ pub fn update_rocket(cell: Cell, mut api: SandApi) {
     // 这里根据 cell.rb 的值来确定火箭的物种类型。如果 cell.rb 不为 100，则将 cell.rb 转换为一个物种（Species）。
     // 如果 cell.rb 为 100，则设置为沙子 (Species::Sand)。
     let clone_species = if cell.rb != 100 {
-        unsafe { mem::transmute(cell.rb as u8) }
+        Species(cell.rb)
     } else {
         Species::Sand
     };
@@ -1096,7 +1201,7 @@ pub fn update_rocket(cell: Cell, mut api: SandApi) {
             0,
             Cell {
                 ra: 1,
-                rb: sample.species as u8, //store the type
+                rb: sample.species.id(), //store the type
                 ..cell
             },
         );
@@ -1168,8 +1273,17 @@ pub fn update_rocket(cell: Cell, mut api: SandApi) {
             || nbr.species == Species::Fire
             || nbr.species == Species::Rocket
         {
-            api.set(0, 0, Cell::new(clone_species));
-            api.set(0, dy, Cell::new(clone_species));
+            // 飞行时喷出的尾气
+            api.set_fluid(Wind {
+                dx: 0,
+                dy: 0,
+                pressure: 30,
+                density: 60,
+            });
+            let trail = api.new_cell(clone_species);
+            api.set(0, 0, trail);
+            let trail = api.new_cell(clone_species);
+            api.set(0, dy, trail);
 
             let (ndx, ndy) = match api.rand_int(100) % 5 {
                 0 => adjacency_left((dx, dy)),
@@ -1208,7 +1322,7 @@ pub fn update_fire(cell: Cell, mut api: SandApi) {
     // 创建一个 degraded 变量，用来表示火焰的降解状态。降解是通过将当前的 ra 值减去一个随机值来实现的，
     // api.rand_dir() 返回一个随机的方向值（可能是 -1、0、1），因此这个变化是有随机性的。
     let ra = cell.ra;
-    let mut degraded = cell.clone();
+    let mut degraded = cell;
     degraded.ra = ra.saturating_sub((2 + api.rand_dir()) as u8);
 
     // 2. 随机选择一个方向进行扩散
@@ -1295,30 +1409,15 @@ pub fn update_lava(cell: Cell, mut api: SandApi) {
         );
     }
 
-    // 4. 与水的交互
-    //  如果随机选择的格子是水（Species::Water），则岩浆与水发生反应，岩浆变成石头（Species::Stone），并将石头放置在当前格子。
-    // 同时，水被移除，设置为 EMPTY_CELL。
-    //
-    let sample = api.get(dx, dy);
-    if sample.species == Species::Water {
-        api.set(
-            0,
-            0,
-            Cell {
-                species: Species::Stone,
-                ra: (150 + (dx + dy) * 10) as u8,
-                rb: 0,
-                clock: 0,
-            },
-        );
-        api.set(dx, dy, EMPTY_CELL);
+    // 4. 与水的交互由温度场决定（见 heat.rs）：岩浆是 1200 度的热源，
+    // 被水冷却到 800 度以下就凝成石头，水被烧到 100 度以上则变成气体
 
     //     5. 岩浆的移动
     // 接下来，岩浆尝试向周围的空白格子（Species::Empty）移动。如果周围的格子是空的，它会向该格子移动，否则保持当前位置。
     // 检查当前格子（0, 0）上下左右（0, 1、dx, 1、dx, 0）是否为空（Species::Empty）。
     // 如果某个方向的格子为空，则岩浆会向该方向移动。
     // 如果没有空格子可以移动，则岩浆保持在原位置。
-    } else if api.get(0, 1).species == Species::Empty {
+    if api.get(0, 1).species == Species::Empty {
         api.set(0, 0, EMPTY_CELL);
         api.set(0, 1, cell);
     } else if api.get(dx, 1).species == Species::Empty {
@@ -1332,6 +1431,9 @@ pub fn update_lava(cell: Cell, mut api: SandApi) {
     }
 }
 
+// 木头温度超过这个值开始燃烧
+const WOOD_IGNITION: f32 = 150.0;
+
 // 模拟木材在沙盒模拟环境中的行为。木材的行为包括与火、熔岩、水等物质的互动，以及根据条件改变状态（如变为火或变为空）。
 pub fn update_wood(cell: Cell, mut api: SandApi) {
     //
@@ -1341,10 +1443,11 @@ pub fn update_wood(cell: Cell, mut api: SandApi) {
 
     let nbr_species = api.get(dx, dy).species;
 
-    // 1. 初始化与火或熔岩交互
-    // 这段代码首先判断木材（Wood）是否处于初始状态（rb == 0）。如果是并且它周围的格子是火（Fire）或熔岩（Lava），则木材将变为状态 Wood，并且将其 rb 设置为 90。
+    // 1. 着火
+    // 未燃烧的木材（rb == 0）所在位置的温度超过 WOOD_IGNITION 时开始燃烧，rb 设置为 90。
+    // 热量来自附近的火（Fire）或熔岩（Lava），经温度场传过来（见 heat.rs）
     // ra 和 clock 由原始木材的属性继承
-    if rb == 0 && nbr_species == Species::Fire || nbr_species == Species::Lava {
+    if rb == 0 && api.temperature() > WOOD_IGNITION {
         api.set(
             0,
             0,
@@ -1422,16 +1525,14 @@ pub fn update_wood(cell: Cell, mut api: SandApi) {
         );
     }
 }
-// 你的代码用于模拟冰（Ice）的行为，主要涉及冰与火、岩浆、水等物质的互动。代码的逻辑比较清晰
+// 模拟冰（Ice）的行为：冰不会移动，压力足够大时会被压碎成水
+// 融化和结冰由温度场决定（见 heat.rs）：冰是 -30 度的冷源，靠近火焰、岩浆升到 0 度以上就化成水，
+// 相邻的水被它冷却到 0 度以下就结成冰
 pub fn update_ice(cell: Cell, mut api: SandApi) {
-    let (dx, dy) = api.rand_vec();
-
-    let i = api.rand_int(100);
-
     let fluid = api.get_fluid();
 
-    // // 如果流体压力大于120且有一定概率，冰会变成水
-    if fluid.pressure > 120 && api.rand_int(1) == 0 {
+    // 如果流体压力大于120，冰会变成水
+    if fluid.pressure > 120 {
         api.set(
             0,
             0,
@@ -1442,34 +1543,6 @@ pub fn update_ice(cell: Cell, mut api: SandApi) {
                 clock: 0,
             },
         );
-        return;
-    }
-
-    let nbr_species = api.get(dx, dy).species;
-    // // 如果邻居是火或岩浆，冰会变成水
-    if nbr_species == Species::Fire || nbr_species == Species::Lava {
-        api.set(
-            0,
-            0,
-            Cell {
-                species: Species::Water,
-                ra: cell.ra,
-                rb: cell.rb,
-                clock: 0,
-            },
-        );
-    } else if nbr_species == Species::Water && i < 7 {
-        // 如果邻居是水且随机条件成立，冰会变成冰块
-        api.set(
-            dx,
-            dy,
-            Cell {
-                species: Species::Ice,
-                ra: cell.ra,
-                rb: cell.rb,
-                clock: 0,
-            },
-        );
     }
 }
 
@@ -1596,7 +1669,7 @@ pub fn update_plant(cell: Cell, mut api: SandApi) {
         && api.get(-1, 1).species != Species::Plant
     {
         if api.get(0, 1).species == Species::Empty {
-            let i = (rand::random::<f32>() * rand::random::<f32>() * 100.) as i32;
+            let i = (api.rand_f32() * api.rand_f32() * 100.) as i32;
             let dec = api.rand_int(30) - 20;
             if (i + ra as i32) > 165 {
                 api.set(
@@ -1613,7 +1686,7 @@ pub fn update_plant(cell: Cell, mut api: SandApi) {
                 0,
                 0,
                 Cell {
-                    ra: (ra - 1) as u8,
+                    ra: (ra - 1),
                     ..cell
                 },
             );
@@ -1734,7 +1807,7 @@ pub fn update_seed(cell: Cell, mut api: SandApi) {
                     && (api.get(ldx, ldy).species == Species::Empty
                     || api.get(rdx, rdy).species == Species::Empty)
                 {
-                    let i = (rand::random::<f32>() * rand::random::<f32>() * 100.) as i32;
+                    let i = (api.rand_f32() * api.rand_f32() * 100.) as i32;
                     let dec = 9 - api.rand_int(3);
                     if (i + ra as i32) > 100 {
                         api.set(
@@ -1753,7 +1826,8 @@ pub fn update_seed(cell: Cell, mut api: SandApi) {
                 //
                 // 如果种子附近是水（Water），种子会转变为新的一颗种子（Species::Seed）。
                 if nbr_species == Species::Water {
-                    api.set(dx, dy, Cell::new(Species::Seed))
+                    let seed = api.new_cell(Species::Seed);
+                    api.set(dx, dy, seed)
                 }
             }
         }
@@ -1905,7 +1979,7 @@ pub fn update_fungus(cell: Cell, mut api: SandApi) {
             && api.get(ldx, ldy).species != Species::Fungus
             && api.get(rdx, rdy).species != Species::Fungus
         {
-            let i = (rand::random::<f32>() * rand::random::<f32>() * 100.) as i32;
+            let i = (api.rand_f32() * api.rand_f32() * 100.) as i32;
             let dec = 15 - api.rand_int(20);
             if (i + ra as i32) > 165 {
                 api.set(
@@ -1935,7 +2009,7 @@ pub fn update_acid(cell: Cell, mut api: SandApi) {
     // degraded.ra = ra - 60; 酸的腐蚀程度减少 60，表示酸的退化。
     // 如果酸的腐蚀程度小于 80（degraded.ra < 80），则酸会消失（设置为空单元格 EMPTY_CELL）。
     let ra = cell.ra;
-    let mut degraded = cell.clone();
+    let mut degraded = cell;
     degraded.ra = ra - 60;
     // i = api.rand_int(100);
     if degraded.ra < 80 {
@@ -2008,7 +2082,7 @@ pub fn update_mite(cell: Cell, mut api: SandApi) {
         dx = (cell.ra as i32) - 1;
     }
     let mut dy = 1;
-    let mut mite = cell.clone();
+    let mut mite = cell;
 
 
     if cell.rb > 10 {
@@ -2159,5 +2233,3 @@ pub fn split_dy_dx(s: u8) -> (i32, i32) {
 
     (dx, dy)
 }
-
-use js_sys;
@@ -0,0 +1,248 @@
+use super::*;
+use super::shard::CHUNK_SIZE;
+
+// 休眠块：以并行 tick 的块为单位记录活动
+// SandApi::set 真正改变了细胞（species、ra、rb 之一不同）、paint 或者足以吹动物质的风都会唤醒所在的块和它的 8 个邻居，
+// 风只在写入 winds 时（refresh_windy_chunks）按块检查一次，tick 只看块的标记，
+// 连续 SLEEP_AFTER 代没有被唤醒的块进入休眠，tick 时直接跳过
+// 含有 restless 物质（岩浆、植物这类静止时也会随机变化的）的块在更新时会保持清醒
+//
+// 同时记录脏块：自上次 take_dirty_rects 以来被更新或修改过的块，渲染端只上传这些区域
+pub const SLEEP_AFTER: u8 = 16;
+
+// update_dust、update_stone、update_ice 在压力超过这个值时发生变化
+const PRESSURE_WAKE: u8 = 120;
+
+// 纹理坐标里的矩形，纹素 (x, y) 就是细胞 (x, y)（见 coords.rs）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TexelRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone)]
+pub(super) struct Activity {
+    chunks_x: i32,
+    chunks_y: i32,
+    // 连续没有被唤醒的代数，达到 SLEEP_AFTER 即休眠
+    idle: Vec<u8>,
+    // 本次 tick 是否被唤醒过
+    touched: Vec<bool>,
+    dirty: Vec<bool>,
+    // 块里有足以吹动物质或引发压力反应的风，写入 winds 后由 refresh_windy_chunks 更新
+    windy: Vec<bool>,
+}
+
+impl Activity {
+    // 新网格所有块都是清醒且脏的
+    pub(super) fn new(width: i32, height: i32) -> Activity {
+        let chunks_x = (width + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let chunks_y = (height + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let count = (chunks_x * chunks_y) as usize;
+        Activity {
+            chunks_x,
+            chunks_y,
+            idle: vec![0; count],
+            touched: vec![false; count],
+            dirty: vec![true; count],
+            windy: vec![false; count],
+        }
+    }
+
+    // 每个块连续没有被唤醒的代数，存档用
+    pub(super) fn idle(&self) -> &[u8] {
+        &self.idle
+    }
+
+    // 读档时恢复休眠状态，长度必须与块数一致；所有块仍然标记为脏，渲染端整体上传一次
+    pub(super) fn set_idle(&mut self, idle: &[u8]) {
+        self.idle.copy_from_slice(idle);
+    }
+
+    pub(super) fn chunks_x(&self) -> i32 {
+        self.chunks_x
+    }
+
+    pub(super) fn chunk_index(&self, cx: i32, cy: i32) -> usize {
+        (cy * self.chunks_x + cx) as usize
+    }
+
+    pub(super) fn is_awake(&self, chunk: usize) -> bool {
+        self.idle[chunk] < SLEEP_AFTER
+    }
+
+    // 唤醒一个块和它的邻居，块本身标记为脏
+    pub(super) fn wake(&mut self, chunk: usize) {
+        let cx = chunk as i32 % self.chunks_x;
+        let cy = chunk as i32 / self.chunks_x;
+        for ny in (cy - 1).max(0)..(cy + 2).min(self.chunks_y) {
+            for nx in (cx - 1).max(0)..(cx + 2).min(self.chunks_x) {
+                let i = self.chunk_index(nx, ny);
+                self.idle[i] = 0;
+                self.touched[i] = true;
+            }
+        }
+        self.dirty[chunk] = true;
+    }
+
+    pub(super) fn wake_cell(&mut self, x: i32, y: i32) {
+        self.wake(self.chunk_index(x / CHUNK_SIZE, y / CHUNK_SIZE));
+    }
+
+    pub(super) fn wake_all(&mut self) {
+        self.idle.fill(0);
+        self.dirty.fill(true);
+    }
+
+    // 只保持自己清醒，不唤醒邻居
+    pub(super) fn keep_awake(&mut self, chunk: usize) {
+        self.idle[chunk] = 0;
+        self.touched[chunk] = true;
+    }
+
+    // 本代更新过的块要重新上传（burns 每次更新都会重写）
+    pub(super) fn mark_dirty(&mut self, chunk: usize) {
+        self.dirty[chunk] = true;
+    }
+
+    pub(super) fn begin_tick(&mut self) {
+        self.touched.fill(false);
+    }
+
+    // 本代没被唤醒的块离休眠更近一步
+    pub(super) fn end_tick(&mut self) {
+        for (idle, touched) in self.idle.iter_mut().zip(self.touched.iter()) {
+            if !touched {
+                *idle = idle.saturating_add(1);
+            }
+        }
+    }
+
+    // 清醒的块和它们的 8 个邻居，温度场只在这些块里推进（见 heat.rs）
+    pub(super) fn awake_with_halo(&self) -> Vec<bool> {
+        let mut active = vec![false; self.idle.len()];
+        for cy in 0..self.chunks_y {
+            for cx in 0..self.chunks_x {
+                if !self.is_awake(self.chunk_index(cx, cy)) {
+                    continue;
+                }
+                for ny in (cy - 1).max(0)..(cy + 2).min(self.chunks_y) {
+                    for nx in (cx - 1).max(0)..(cx + 2).min(self.chunks_x) {
+                        active[self.chunk_index(nx, ny)] = true;
+                    }
+                }
+            }
+        }
+        active
+    }
+
+    pub(super) fn awake_count(&self) -> usize {
+        (0..self.idle.len()).filter(|i| self.is_awake(*i)).count()
+    }
+}
+
+impl CellGrid {
+    // 整块替换风场（GPU 读回的结果等），长度必须与细胞数一致
+    pub fn set_winds(&mut self, winds: &[Wind]) {
+        self.winds.copy_from_slice(winds);
+        self.refresh_windy_chunks();
+    }
+
+    // 直接改写了 winds 之后调用，按块重新标记有风的块
+    pub fn refresh_windy_chunks(&mut self) {
+        let threshold = self.registry.min_wind_threshold();
+        let windy = |wind: &Wind| {
+            (wind.dx as i32 - WIND_ZERO as i32).abs() > threshold
+                || (wind.dy as i32 - WIND_ZERO as i32).abs() > threshold
+                || wind.pressure > PRESSURE_WAKE
+        };
+        for cy in 0..self.activity.chunks_y {
+            for cx in 0..self.activity.chunks_x {
+                let x0 = cx * CHUNK_SIZE;
+                let y0 = cy * CHUNK_SIZE;
+                let found = (y0..(y0 + CHUNK_SIZE).min(self.height)).any(|y| {
+                    let start = self.get_index(x0, y);
+                    let end = start + ((x0 + CHUNK_SIZE).min(self.width) - x0) as usize;
+                    self.winds[start..end].iter().any(windy)
+                });
+                let chunk = self.activity.chunk_index(cx, cy);
+                self.activity.windy[chunk] = found;
+            }
+        }
+    }
+
+    // 休眠的块里如果有足以吹动物质或引发压力反应的风，就唤醒它
+    pub(super) fn wake_windy_chunks(&mut self) {
+        for chunk in 0..self.activity.windy.len() {
+            if self.activity.windy[chunk] && !self.activity.is_awake(chunk) {
+                self.activity.wake(chunk);
+            }
+        }
+    }
+
+    // 直接改写了 cells（撤销、读档等）之后调用，所有块重新开始计时并整体上传
+    pub fn wake_all(&mut self) {
+        self.activity.wake_all();
+    }
+
+    // 当前清醒的块数，调试用
+    pub fn awake_chunks(&self) -> usize {
+        self.activity.awake_count()
+    }
+
+    // 取出自上次调用以来的脏区域，并清空脏标记
+    // 同一列里上下相接的脏块合并成一个矩形
+    pub fn take_dirty_rects(&mut self) -> Vec<TexelRect> {
+        let mut rects = Vec::new();
+        let (width, height) = (self.width, self.height);
+        for cx in 0..self.activity.chunks_x {
+            let mut cy = 0;
+            while cy < self.activity.chunks_y {
+                if !self.activity.dirty[self.activity.chunk_index(cx, cy)] {
+                    cy += 1;
+                    continue;
+                }
+                let start = cy;
+                while cy < self.activity.chunks_y && self.activity.dirty[self.activity.chunk_index(cx, cy)] {
+                    cy += 1;
+                }
+                let x0 = cx * CHUNK_SIZE;
+                let y0 = start * CHUNK_SIZE;
+                rects.push(TexelRect {
+                    x: x0 as u32,
+                    y: y0 as u32,
+                    width: ((x0 + CHUNK_SIZE).min(width) - x0) as u32,
+                    height: ((cy * CHUNK_SIZE).min(height) - y0) as u32,
+                });
+            }
+        }
+        self.activity.dirty.fill(false);
+        rects
+    }
+
+    // 矩形内的细胞，逐行排列，每个像素 (species, ra, rb, clock)
+    pub fn cells_rgba_rect(&self, rect: TexelRect) -> Vec<u8> {
+        let mut data = Vec::with_capacity((rect.width * rect.height * 4) as usize);
+        for row in rect.y..rect.y + rect.height {
+            let start = self.get_index(rect.x as i32, row as i32);
+            for cell in &self.cells[start..start + rect.width as usize] {
+                data.extend_from_slice(&[cell.species.id(), cell.ra, cell.rb, cell.clock]);
+            }
+        }
+        data
+    }
+
+    // 矩形内的 burns，布局同 cells_rgba_rect
+    pub fn burns_rgba_rect(&self, rect: TexelRect) -> Vec<u8> {
+        let mut data = Vec::with_capacity((rect.width * rect.height * 4) as usize);
+        for row in rect.y..rect.y + rect.height {
+            let start = self.get_index(rect.x as i32, row as i32);
+            for wind in &self.burns[start..start + rect.width as usize] {
+                data.extend_from_slice(&[wind.dx, wind.dy, wind.pressure, wind.density]);
+            }
+        }
+        data
+    }
+}
@@ -0,0 +1,26 @@
+// 网格、纹理和着色器共用的坐标约定
+//
+//   细胞 (x, y)：x 向右，y 向下，物质下落的方向就是 +y
+//   存储：行优先，cells/winds/burns[y * width + x]，同一行的细胞相邻，按行扫描对缓存友好
+//   纹理：与网格同尺寸，纹素 (x, y) 就是细胞 (x, y)，纹理数据的第 0 行是网格的顶部
+//   风场：Wind 的 dx/dy（速度纹理的 x/y 分量）分别对应 +x/+y 方向
+//   网格 uv：(x + 0.5) / width、(y + 0.5) / height，原点在左上角，y 向下
+//
+// 屏幕上 y 向上的坐标（裁剪空间、NDC）要先翻转成网格 uv，
+// 着色器里对应的函数在 assets/grid.wgsl，窗口坐标本来就是 y 向下，可以直接当作网格 uv
+
+pub fn cell_index(width: i32, x: i32, y: i32) -> usize {
+    (y * width + x) as usize
+}
+
+pub fn cell_coords(width: i32, i: usize) -> (i32, i32) {
+    let i = i as i32;
+    (i % width, i / width)
+}
+
+// 网格 uv 落在哪个细胞上，超出网格的夹到边上
+pub fn uv_to_cell(width: i32, height: i32, u: f32, v: f32) -> (i32, i32) {
+    let x = ((u * width as f32).floor() as i32).clamp(0, width - 1);
+    let y = ((v * height as f32).floor() as i32).clamp(0, height - 1);
+    (x, y)
+}
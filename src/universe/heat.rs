@@ -0,0 +1,314 @@
+use std::thread;
+use super::*;
+use super::shard::CHUNK_SIZE;
+
+// 温度场：每个细胞位置一个温度（摄氏度），和 winds 一样按位置存放；物质规则移动正在更新的细胞时把它的温度一起换过去（见 SandApi::set）
+// 每次 tick 在物质更新之后推进一步：
+//   1. 平流：按风速（blow_wind 用的字节编码，WIND_ZERO 为静止）做半拉格朗日回溯取样，固体细胞不参与
+//   2. 扩散：相邻两格之间的热流 = 两侧较小的导热系数 × 温差，温度变化 = 热流之和 / 热容，网格边缘绝热
+//   3. 热源把温度拉向自己的温度，所有细胞以 AMBIENT_LOSS 的比例向环境温度散热
+//   4. 相变：越过阈值的细胞温度停在阈值上，越过的度数计入潜热，攒够 latent_heat 就变成新物质，
+//      多出来的热量按热容折算成新物质的温度
+// 导热系数不超过 MAX_CONDUCTIVITY、热容不小于 MIN_HEAT_CAPACITY，四个邻居的热流加起来也不会让温度越过邻居
+// 每个细胞只读上一步的结果，按块行分给多个线程计算，结果与线程数无关
+//
+// 只有清醒的块和它们周围一圈的块参与（见 Activity::awake_with_halo），其余块的温度和休眠的细胞一样保持不变；
+// 扩散时这些块的细胞当作绝热的边，周围一圈的块升温或降温超过 HEAT_WAKE 时被唤醒，热量再继续往外传
+
+// 新网格、擦除后的空白和不是热源的物质都从这个温度开始
+pub const AMBIENT_TEMPERATURE: f32 = 20.0;
+
+// 每代向画面之外散失的比例，没有它的话封闭网格里的热源会让温度一直升高
+const AMBIENT_LOSS: f32 = 0.002;
+
+const MAX_CONDUCTIVITY: f32 = 0.25;
+const MIN_HEAT_CAPACITY: f32 = 1.0;
+
+// 风速字节偏离零点这么多相当于每代平流一格
+const WIND_PER_CELL: f32 = 64.0;
+
+// 一步之内温度变化超过这个值时所在的块保持清醒，让木头这类读温度的规则看到变化
+const HEAT_WAKE: f32 = 0.5;
+
+// 每个线程至少分到这么多行（一整行块），小网格不值得开线程
+const MIN_BAND_ROWS: usize = CHUNK_SIZE as usize;
+
+const NEIGHBOURS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+#[derive(Clone)]
+pub(super) struct Heat {
+    pub(super) temperature: Vec<f32>,
+    // 正在进行的相变已经越过阈值的度数，见 PhaseChange
+    pub(super) latent: Vec<f32>,
+    // 平流的结果，每步复用
+    advected: Vec<f32>,
+}
+
+impl Heat {
+    pub(super) fn new(width: i32, height: i32) -> Heat {
+        let count = (width * height) as usize;
+        Heat::from_parts(vec![AMBIENT_TEMPERATURE; count], vec![0.0; count])
+    }
+
+    pub(super) fn from_parts(temperature: Vec<f32>, latent: Vec<f32>) -> Heat {
+        let advected = vec![0.0; temperature.len()];
+        Heat {
+            temperature,
+            latent,
+            advected,
+        }
+    }
+}
+
+// 一步里每种物质用到的参数，注册表每次 register 时重新算好（见 SpeciesRegistry::thermal）
+#[derive(Clone, Copy, Default)]
+pub(super) struct Thermal {
+    capacity: f32,
+    conductivity: f32,
+    source: Option<HeatSource>,
+    heats_into: Option<PhaseChange>,
+    cools_into: Option<PhaseChange>,
+    solid: bool,
+}
+
+pub(super) fn thermal_table(registry: &SpeciesRegistry) -> [Thermal; MAX_SPECIES] {
+    std::array::from_fn(|i| {
+        let species = Species(i as u8);
+        Thermal {
+            capacity: registry.heat_capacity(species).max(MIN_HEAT_CAPACITY),
+            conductivity: registry.conductivity(species).clamp(0.0, MAX_CONDUCTIVITY),
+            source: registry.heat_source(species),
+            heats_into: registry.heats_into(species),
+            cools_into: registry.cools_into(species),
+            solid: registry.get(species).is_some_and(|def| def.solid),
+        }
+    })
+}
+
+// 只读的网格和参数，各个线程共用
+struct Field<'a> {
+    width: i32,
+    height: i32,
+    cells: &'a [Cell],
+    table: &'a [Thermal; MAX_SPECIES],
+    chunks_x: i32,
+    // 本步参与计算的块
+    active: &'a [bool],
+}
+
+impl Field<'_> {
+    fn thermal(&self, i: usize) -> &Thermal {
+        &self.table[self.cells[i].species.index()]
+    }
+
+    fn chunk(&self, x: i32, y: i32) -> usize {
+        ((y / CHUNK_SIZE) * self.chunks_x + x / CHUNK_SIZE) as usize
+    }
+
+    // 从 start 开始的 len 个细胞里属于参与计算的块的那些，逐行列出；start 和 len 都是整行
+    fn active_cells(&self, start: usize, len: usize) -> impl Iterator<Item = usize> + '_ {
+        let width = self.width as usize;
+        let chunk = CHUNK_SIZE as usize;
+        (start / width..(start + len) / width).flat_map(move |y| {
+            let row = y / chunk * self.chunks_x as usize;
+            (0..self.chunks_x as usize)
+                .filter(move |cx| self.active[row + cx])
+                .flat_map(move |cx| y * width + cx * chunk..y * width + ((cx + 1) * chunk).min(width))
+        })
+    }
+
+    // 从风吹来的位置双线性取样
+    fn advect(&self, temperature: &[f32], wind: Wind, i: usize) -> f32 {
+        let vx = (wind.dx as f32 - WIND_ZERO as f32) / WIND_PER_CELL;
+        let vy = (wind.dy as f32 - WIND_ZERO as f32) / WIND_PER_CELL;
+        if self.thermal(i).solid || (vx == 0.0 && vy == 0.0) {
+            return temperature[i];
+        }
+        let (x, y) = coords::cell_coords(self.width, i);
+        let sx = (x as f32 - vx).clamp(0.0, (self.width - 1) as f32);
+        let sy = (y as f32 - vy).clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (sx.floor() as i32, sy.floor() as i32);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+        let at = |x: i32, y: i32| temperature[coords::cell_index(self.width, x, y)];
+        let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    // 扩散、热源和向环境散热之后的温度，不参与计算的块和网格边缘一样绝热
+    fn conduct(&self, temperature: &[f32], i: usize) -> f32 {
+        let here = self.thermal(i);
+        let t = temperature[i];
+        let (x, y) = coords::cell_coords(self.width, i);
+        let mut flux = 0.0;
+        for (dx, dy) in NEIGHBOURS {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || nx >= self.width || ny < 0 || ny >= self.height || !self.active[self.chunk(nx, ny)] {
+                continue;
+            }
+            let j = coords::cell_index(self.width, nx, ny);
+            let k = here.conductivity.min(self.thermal(j).conductivity);
+            flux += k * (temperature[j] - t);
+        }
+        let mut next = t + flux / here.capacity;
+        if let Some(source) = here.source {
+            next += (source.temperature - next) * source.strength.clamp(0.0, 1.0);
+        }
+        next + (AMBIENT_TEMPERATURE - next) * AMBIENT_LOSS
+    }
+
+    // 相变：越过阈值时温度停在阈值上并累积潜热，攒够了返回新物质，温度换成多出来的热量折算的值
+    fn change_phase(&self, i: usize, temperature: &mut f32, latent: &mut f32) -> Option<Species> {
+        let thermal = self.thermal(i);
+        let t = *temperature;
+        let (change, over, heating) = match (thermal.heats_into, thermal.cools_into) {
+            (Some(change), _) if t > change.threshold => (change, t - change.threshold, true),
+            (_, Some(change)) if t < change.threshold => (change, change.threshold - t, false),
+            _ => {
+                *latent = 0.0;
+                return None;
+            }
+        };
+        let absorbed = *latent + over;
+        if absorbed < change.latent_heat {
+            *temperature = change.threshold;
+            *latent = absorbed;
+            return None;
+        }
+        let excess = (absorbed - change.latent_heat) * thermal.capacity / self.table[change.into.index()].capacity;
+        *temperature = if heating {
+            change.threshold + excess
+        } else {
+            change.threshold - excess
+        };
+        *latent = 0.0;
+        Some(change.into)
+    }
+}
+
+// 每段的细胞数：整数行块，段数不超过 threads，每段至少 MIN_BAND_ROWS 行
+fn band_len(width: usize, height: usize, threads: usize) -> usize {
+    let chunk_rows = height.div_ceil(CHUNK_SIZE as usize);
+    let bands = threads.min(height / MIN_BAND_ROWS).max(1);
+    chunk_rows.div_ceil(bands) * CHUNK_SIZE as usize * width
+}
+
+// 每段交给一个线程，run 收到段号和这一段在各个切片里的部分
+fn in_bands<B: Send, R: Send>(bands: Vec<B>, run: impl Fn(usize, B) -> R + Sync) -> Vec<R> {
+    if bands.len() == 1 {
+        return bands.into_iter().map(|band| run(0, band)).collect();
+    }
+    thread::scope(|scope| {
+        let run = &run;
+        let workers: Vec<_> = bands
+            .into_iter()
+            .enumerate()
+            .map(|(b, band)| scope.spawn(move || run(b, band)))
+            .collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+    })
+}
+
+impl CellGrid {
+    // (x, y) 处的温度，坐标在网格外时返回环境温度
+    pub fn temperature(&self, x: i32, y: i32) -> f32 {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return AMBIENT_TEMPERATURE;
+        }
+        self.heat.temperature[self.get_index(x, y)]
+    }
+
+    // 直接设定 (x, y) 处的温度，那里正在进行的相变重新开始
+    pub fn set_temperature(&mut self, x: i32, y: i32, temperature: f32) {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return;
+        }
+        let i = self.get_index(x, y);
+        self.heat.temperature[i] = temperature;
+        self.heat.latent[i] = 0.0;
+        self.activity.wake_cell(x, y);
+    }
+
+    // 每个细胞回到物质的初始温度（见 SpeciesRegistry::initial_temperature）
+    // 清空网格、读入不带温度的旧存档或 PNG 时调用
+    pub fn reset_temperature(&mut self) {
+        for (i, cell) in self.cells.iter().enumerate() {
+            self.heat.temperature[i] = self.registry.initial_temperature(cell.species);
+        }
+        self.heat.latent.fill(0.0);
+    }
+
+    pub(super) fn step_heat(&mut self) {
+        let active = self.activity.awake_with_halo();
+        if self.cells.is_empty() || !active.contains(&true) {
+            return;
+        }
+        let field = Field {
+            width: self.width,
+            height: self.height,
+            cells: &self.cells,
+            table: self.registry.thermal(),
+            chunks_x: self.activity.chunks_x(),
+            active: &active,
+        };
+        let band_len = band_len(self.width as usize, self.height as usize, self.tick_threads);
+        let winds = &self.winds;
+        let Heat {
+            temperature,
+            latent,
+            advected,
+        } = &mut self.heat;
+
+        // 只有参与计算的细胞写入 advected，扩散也只读这些细胞
+        let before: &[f32] = temperature;
+        in_bands(advected.chunks_mut(band_len).collect(), |b, band| {
+            let start = b * band_len;
+            for i in field.active_cells(start, band.len()) {
+                band[i - start] = field.advect(before, winds[i], i);
+            }
+        });
+
+        let advected: &[f32] = advected;
+        let bands = temperature.chunks_mut(band_len).zip(latent.chunks_mut(band_len)).collect();
+        let results = in_bands(bands, |b, (temperature, latent): (&mut [f32], &mut [f32])| {
+            let start = b * band_len;
+            let mut warmed = Vec::new();
+            let mut changes = Vec::new();
+            for i in field.active_cells(start, temperature.len()) {
+                let (t, l) = (&mut temperature[i - start], &mut latent[i - start]);
+                let next = field.conduct(advected, i);
+                if (next - *t).abs() > HEAT_WAKE {
+                    let (x, y) = coords::cell_coords(field.width, i);
+                    let chunk = field.chunk(x, y);
+                    if !warmed.contains(&chunk) {
+                        warmed.push(chunk);
+                    }
+                }
+                *t = next;
+                if let Some(species) = field.change_phase(i, t, l) {
+                    changes.push((i, species));
+                }
+            }
+            (warmed, changes)
+        });
+
+        // 相变保留 ra，和原来冰化成水时一样
+        for (warmed, changes) in results {
+            for chunk in warmed {
+                self.activity.keep_awake(chunk);
+            }
+            for (i, species) in changes {
+                let cell = self.cells[i];
+                self.cells[i] = Cell {
+                    species,
+                    ra: cell.ra,
+                    rb: 0,
+                    clock: 0,
+                };
+                let (x, y) = self.get_x_y(i as i32);
+                self.activity.wake_cell(x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, VecDeque};
+use super::snapshot::{decode_records, encode_records};
+use super::{Cell, CellGrid, Edit, Species};
+
+// 以笔画为单位的撤销/重做
+// 笔画进行中，paint 改写的每个细胞记下第一次改写前的值和最后一次改写后的值；
+// 笔画结束时按下标排序，连续的下标合并成区间，前后两组细胞分别做游程编码，
+// 只保存改动过的细胞，一笔 60 像素的圆大约几百字节，而不是整张网格的 1.4MB
+// 撤销把这些细胞写回笔画之前的值，重做再写回笔画之后的值，期间模拟造成的其它变化保持不动
+// 被写回的细胞的温度回到物质的初始温度，撤销一笔岩浆不会在原处留下热量
+const DEFAULT_BUDGET: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+struct StrokeDelta {
+    // (起始下标, 长度)
+    runs: Vec<(u32, u32)>,
+    count: usize,
+    before: Vec<u8>,
+    after: Vec<u8>,
+}
+
+impl StrokeDelta {
+    fn bytes(&self) -> usize {
+        self.runs.len() * 8 + self.before.len() + self.after.len()
+    }
+
+    fn apply(&self, cells: &mut [Cell], encoded: &[u8]) {
+        let Ok(records) = decode_records(encoded, self.count) else {
+            return;
+        };
+        let mut records = records.into_iter();
+        for &(start, len) in self.runs.iter() {
+            for i in start..start + len {
+                let Some(record) = records.next() else {
+                    return;
+                };
+                if let Some(cell) = cells.get_mut(i as usize) {
+                    *cell = record_cell(record);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct History {
+    undo: VecDeque<StrokeDelta>,
+    redo: Vec<StrokeDelta>,
+    // 正在进行的笔画：下标 -> (改写前, 改写后)
+    open: Option<BTreeMap<u32, (Cell, Cell)>>,
+    budget: usize,
+    used: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            open: None,
+            budget: DEFAULT_BUDGET,
+            used: 0,
+        }
+    }
+}
+
+impl History {
+    pub(crate) fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.open = None;
+        self.used = 0;
+    }
+
+    // 超出预算时从最老的笔画开始丢弃
+    fn trim(&mut self) {
+        while self.used > self.budget {
+            let dropped = match self.undo.pop_back() {
+                Some(delta) => delta,
+                None => match self.redo.first() {
+                    Some(_) => self.redo.remove(0),
+                    None => break,
+                },
+            };
+            self.used -= dropped.bytes();
+        }
+    }
+}
+
+impl CellGrid {
+    // 开始一笔，已经开始的笔画会先结束
+    pub fn begin_stroke(&mut self) {
+        self.end_stroke();
+        self.record_edit(Edit::BeginStroke);
+        self.history.open = Some(BTreeMap::new());
+    }
+
+    pub fn in_stroke(&self) -> bool {
+        self.history.open.is_some()
+    }
+
+    // 结束当前笔画并压入撤销栈，新的笔画会清空重做栈
+    pub fn end_stroke(&mut self) {
+        let Some(edits) = self.history.open.take() else {
+            return;
+        };
+        self.record_edit(Edit::EndStroke);
+        // 整笔都没有改动（比如在已有物质上画）不占撤销记录
+        let edits: Vec<(u32, (Cell, Cell))> = edits.into_iter().filter(|(_, (old, new))| old != new).collect();
+        if edits.is_empty() {
+            return;
+        }
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for (i, _) in edits.iter() {
+            match runs.last_mut() {
+                Some((start, len)) if *start + *len == *i => *len += 1,
+                _ => runs.push((*i, 1)),
+            }
+        }
+        let before: Vec<[u8; 4]> = edits.iter().map(|(_, (old, _))| cell_record(old)).collect();
+        let after: Vec<[u8; 4]> = edits.iter().map(|(_, (_, new))| cell_record(new)).collect();
+        let delta = StrokeDelta {
+            runs,
+            count: edits.len(),
+            before: encode_records(&before),
+            after: encode_records(&after),
+        };
+        for dropped in self.history.redo.drain(..) {
+            self.history.used -= dropped.bytes();
+        }
+        self.history.used += delta.bytes();
+        self.history.undo.push_front(delta);
+        self.history.trim();
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.end_stroke();
+        self.record_edit(Edit::Undo);
+        let Some(delta) = self.history.undo.pop_front() else {
+            return false;
+        };
+        delta.apply(&mut self.cells, &delta.before);
+        self.wake_delta(&delta);
+        self.history.redo.push(delta);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.end_stroke();
+        self.record_edit(Edit::Redo);
+        let Some(delta) = self.history.redo.pop() else {
+            return false;
+        };
+        delta.apply(&mut self.cells, &delta.after);
+        self.wake_delta(&delta);
+        self.history.undo.push_front(delta);
+        true
+    }
+
+    // 撤销、重做改写的细胞所在的块需要重新更新和上传
+    fn wake_delta(&mut self, delta: &StrokeDelta) {
+        for (start, len) in delta.runs.iter() {
+            for i in *start..*start + *len {
+                let (x, y) = self.get_x_y(i as i32);
+                self.activity.wake_cell(x, y);
+                let species = self.cells[i as usize].species;
+                self.heat.temperature[i as usize] = self.registry.initial_temperature(species);
+                self.heat.latent[i as usize] = 0.0;
+            }
+        }
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    // 撤销历史占用的内存上限（字节）
+    pub fn set_history_budget(&mut self, budget: usize) {
+        self.history.budget = budget;
+        self.history.trim();
+    }
+
+    pub fn history_bytes(&self) -> usize {
+        self.history.used
+    }
+
+    pub fn undo_depth(&self) -> usize {
+        self.history.undo.len()
+    }
+
+    pub fn redo_depth(&self) -> usize {
+        self.history.redo.len()
+    }
+
+    // paint 改写细胞前调用
+    pub(super) fn note_edit(&mut self, i: usize, new: Cell) {
+        let old = self.cells[i];
+        if let Some(open) = &mut self.history.open {
+            open.entry(i as u32)
+                .and_modify(|edit| edit.1 = new)
+                .or_insert((old, new));
+        }
+    }
+}
+
+fn cell_record(cell: &Cell) -> [u8; 4] {
+    [cell.species.id(), cell.ra, cell.rb, cell.clock]
+}
+
+fn record_cell([species, ra, rb, clock]: [u8; 4]) -> Cell {
+    Cell {
+        species: Species::from_u8(species).unwrap_or(Species::Empty),
+        ra,
+        rb,
+        clock,
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::{CompressedImageFormats, Image, ImageSampler, ImageType};
+use super::{Cell, CellGrid, Species};
+
+// 与 Sandspiel 分享世界用的 PNG：每个像素的 RGBA 依次是 species, ra, rb, clock，
+// 和 update_burns_and_cells_textures 写入细胞纹理的布局相同
+// 图片的第 y 行第 x 列对应网格的 (x, y)，y 向下增长，和物质下落的方向一致
+// 注意图片编辑器可能会丢掉 alpha 为0的像素的颜色，画关卡时 alpha 保持 255 即可
+
+// 读取 PNG 时遇到不认识的物质字节的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownSpecies {
+    // 返回错误
+    #[default]
+    Reject,
+    // 当作空白
+    Empty,
+}
+
+impl CellGrid {
+    pub fn to_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        // cells 和图片一样是行优先、y 向下的，可以直接当作像素数据
+        // try_into_dynamic 只认 sRGB 的 RGBA8，这里的 sRGB 只是标记，字节原样写出
+        let data = self.cells_rgba();
+        let image = Image::new(
+            Extent3d { width: self.width as u32, height: self.height as u32, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::MAIN_WORLD,
+        );
+        let dynamic = image
+            .try_into_dynamic()
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        dynamic
+            .save(path)
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    pub fn from_png<P: AsRef<Path>>(path: P, unknown: UnknownSpecies) -> io::Result<CellGrid> {
+        let bytes = fs::read(path)?;
+        let image = Image::from_buffer(
+            &bytes,
+            ImageType::Extension("png"),
+            CompressedImageFormats::NONE,
+            true,
+            ImageSampler::Default,
+            RenderAssetUsages::MAIN_WORLD,
+        )
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        // 灰度、RGB 等格式统一转成 RGBA8
+        let rgba = image
+            .try_into_dynamic()
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))?
+            .to_rgba8();
+        let (width, height) = rgba.dimensions();
+        let data = rgba.into_raw();
+
+        let mut cell_grid = CellGrid::new(width as i32, height as i32);
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let idx = cell_grid.get_index(x, y);
+                let offset = idx * 4;
+                let [species, ra, rb, clock] = [data[offset], data[offset + 1], data[offset + 2], data[offset + 3]];
+                // 只接受注册表里有的物质
+                let known = Species::from_u8(species).filter(|s| cell_grid.registry().contains(*s));
+                let species = match (known, unknown) {
+                    (Some(species), _) => species,
+                    (None, UnknownSpecies::Empty) => Species::Empty,
+                    (None, UnknownSpecies::Reject) => {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!("unknown species {} at ({}, {})", species, x, y),
+                        ));
+                    }
+                };
+                cell_grid.cells[idx] = Cell { species, ra, rb, clock };
+            }
+        }
+        // 图片里没有温度，热源（岩浆、冰等）按自己的温度开始
+        cell_grid.reset_temperature();
+        Ok(cell_grid)
+    }
+}
@@ -0,0 +1,602 @@
+use super::*;
+use super::heat::{thermal_table, Thermal};
+
+// 物质注册表：每种物质在这里一次性声明 id、名字、风阈值、密度、颜色参数和更新函数
+// tick 通过它分发更新，blow_wind 通过它取风阈值，下沉规则通过它比较密度，
+// sand.wgsl 通过它生成的调色板上色，第三方 crate 只需要 register 一个 SpeciesDef 就能加入新物质
+// id 必须小于 MAX_SPECIES（与发射强度表、调色板的大小一致）
+pub const MAX_SPECIES: usize = 32;
+
+// 未注册的 id 沿用 Sandspiel 原来的默认风阈值
+const DEFAULT_WIND_THRESHOLD: i32 = 40;
+
+// 热学参数的默认值，未注册的 id 也用它们，含义见 heat.rs
+const DEFAULT_HEAT_CAPACITY: f32 = 1.0;
+const DEFAULT_CONDUCTIVITY: f32 = 0.02;
+
+// 着色方式，sand.wgsl 里按它处理少数非线性的效果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ColourStyle {
+    Plain = 0,
+    // 背景：截图模式下变成白底不透明
+    Background = 1,
+    // ra 为偶数时稍微提亮，形成闪烁的水面
+    Shimmer = 2,
+    // 自发光：不透明，不叠加静态噪声，截图模式下变暗
+    Glow = 3,
+    // 色相取 常数项 + fract(fract(其余各项之和) * 0.5)，用于种子的渐变
+    Wrapped = 4,
+}
+
+// 颜色参数：每个分量是各项输入的线性组合，g = ra / 255，b = rb / 255
+//   hue        = hue[0] + hue[1] * g + hue[2] * b + hue[3] * time
+//   saturation = saturation[0] + saturation[1] * g + saturation[2] * b + saturation[3] * time
+//   lightness  = lightness[0] + lightness[1] * g + lightness[2] * b + lightness[3] * noise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeciesColour {
+    pub hue: [f32; 4],
+    pub saturation: [f32; 4],
+    pub lightness: [f32; 4],
+    pub alpha: f32,
+    pub style: ColourStyle,
+}
+
+impl SpeciesColour {
+    // 与 sand.wgsl 原来的默认值一致
+    pub const DEFAULT: SpeciesColour = SpeciesColour {
+        hue: [0.0, 0.0, 0.0, 0.0],
+        saturation: [0.6, 0.0, 0.0, 0.0],
+        lightness: [0.3, 0.5, 0.0, 0.0],
+        alpha: 0.6,
+        style: ColourStyle::Plain,
+    };
+
+    // 未注册的 id 显示为白色，便于发现
+    pub const UNKNOWN: SpeciesColour = SpeciesColour {
+        hue: [0.0, 0.0, 0.0, 0.0],
+        saturation: [1.0, 0.0, 0.0, 0.0],
+        lightness: [1.0, 0.0, 0.0, 0.0],
+        alpha: 1.0,
+        style: ColourStyle::Plain,
+    };
+
+    // 写进调色板的四个 vec4
+    pub fn packed(&self) -> [[f32; 4]; 4] {
+        [
+            self.hue,
+            self.saturation,
+            self.lightness,
+            [self.alpha, self.style as u8 as f32, 0.0, 0.0],
+        ]
+    }
+}
+
+// 热源：每代把所在位置的温度向 temperature 拉近 strength 的比例，1 表示恒温；温度低于环境的热源就是冷源
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeatSource {
+    pub temperature: f32,
+    pub strength: f32,
+}
+
+// 相变：温度越过 threshold 后停在阈值上，继续吸收（或放出）相当于 latent_heat 度的热量才变成 into
+// latent_heat 为 0 时越过阈值立刻转变
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhaseChange {
+    pub threshold: f32,
+    pub into: Species,
+    pub latent_heat: f32,
+}
+
+impl PhaseChange {
+    pub fn new(threshold: f32, into: Species, latent_heat: f32) -> PhaseChange {
+        PhaseChange {
+            threshold,
+            into,
+            latent_heat,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct SpeciesDef {
+    pub species: Species,
+    pub name: &'static str,
+    // 风速超过这个值才会把细胞吹动
+    pub wind_threshold: i32,
+    // 被向上吹时额外跳一格
+    pub wind_lift: bool,
+    // 更重的物质可以沉入 fluid 为 true 且密度更小的物质
+    pub density: u8,
+    pub fluid: bool,
+    // 静止时也会随机变化（岩浆随机取样邻居、植物生长等），所在的块不会休眠
+    pub restless: bool,
+    // 流体里的固体障碍：速度为 0，不能穿过，边界方式见 FluidConfig::solid_boundary
+    pub solid: bool,
+    // 风在其中保持速度（空气、气体、火），其余物质里梯度减法后的速度乘以阻尼系数
+    pub undamped: bool,
+    // 热容越大升温越慢；相邻两格之间按较小的导热系数传热
+    pub heat_capacity: f32,
+    pub conductivity: f32,
+    pub heat_source: Option<HeatSource>,
+    // 温度高于 heats_into 的阈值、低于 cools_into 的阈值时变成另一种物质
+    pub heats_into: Option<PhaseChange>,
+    pub cools_into: Option<PhaseChange>,
+    pub colour: SpeciesColour,
+    pub update: fn(Cell, SandApi),
+}
+
+impl SpeciesDef {
+    // 不会动、不会被吹动的物质，其余参数用默认值，再按需覆盖
+    pub fn new(species: Species, name: &'static str, update: fn(Cell, SandApi)) -> SpeciesDef {
+        SpeciesDef {
+            species,
+            name,
+            wind_threshold: DEFAULT_WIND_THRESHOLD,
+            wind_lift: false,
+            density: 0,
+            fluid: false,
+            restless: false,
+            solid: false,
+            undamped: false,
+            heat_capacity: DEFAULT_HEAT_CAPACITY,
+            conductivity: DEFAULT_CONDUCTIVITY,
+            heat_source: None,
+            heats_into: None,
+            cools_into: None,
+            colour: SpeciesColour::DEFAULT,
+            update,
+        }
+    }
+
+    fn builtin(species: Species, update: fn(Cell, SandApi)) -> SpeciesDef {
+        let name = SpeciesRegistry::builtin_name(species).expect("not a builtin species");
+        SpeciesDef::new(species, name, update)
+    }
+}
+
+#[derive(Clone)]
+pub struct SpeciesRegistry {
+    defs: [Option<SpeciesDef>; MAX_SPECIES],
+    // 注册顺序，调色板界面按这个顺序列出
+    order: Vec<Species>,
+    revision: u32,
+    // 按 id 排列的热学参数，每次注册后重新计算，step_heat 直接使用
+    thermal: [Thermal; MAX_SPECIES],
+}
+
+impl Default for SpeciesRegistry {
+    fn default() -> Self {
+        SpeciesRegistry::builtin()
+    }
+}
+
+impl SpeciesRegistry {
+    pub fn empty() -> Self {
+        let mut registry = SpeciesRegistry {
+            defs: [None; MAX_SPECIES],
+            order: Vec::new(),
+            revision: 0,
+            thermal: [Thermal::default(); MAX_SPECIES],
+        };
+        registry.thermal = thermal_table(&registry);
+        registry
+    }
+
+    // 注册或替换一种物质，返回之前的定义
+    pub fn register(&mut self, def: SpeciesDef) -> Option<SpeciesDef> {
+        let index = def.species.index();
+        assert!(index < MAX_SPECIES, "species id {} exceeds MAX_SPECIES", index);
+        let previous = self.defs[index].replace(def);
+        if previous.is_none() {
+            self.order.push(def.species);
+        }
+        self.revision = self.revision.wrapping_add(1);
+        self.thermal = thermal_table(self);
+        previous
+    }
+
+    pub fn get(&self, species: Species) -> Option<&SpeciesDef> {
+        self.defs.get(species.index()).and_then(|def| def.as_ref())
+    }
+
+    pub fn contains(&self, species: Species) -> bool {
+        self.get(species).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SpeciesDef> {
+        self.order.iter().filter_map(|species| self.get(*species))
+    }
+
+    pub fn name(&self, species: Species) -> &'static str {
+        self.get(species).map_or("Unknown", |def| def.name)
+    }
+
+    pub fn wind_threshold(&self, species: Species) -> i32 {
+        self.get(species).map_or(DEFAULT_WIND_THRESHOLD, |def| def.wind_threshold)
+    }
+
+    pub fn wind_lift(&self, species: Species) -> bool {
+        self.get(species).is_some_and(|def| def.wind_lift)
+    }
+
+    // 所有已注册物质里最小的风阈值，休眠块据此判断风能否吹动块里的物质
+    pub fn min_wind_threshold(&self) -> i32 {
+        self.iter().map(|def| def.wind_threshold).min().unwrap_or(DEFAULT_WIND_THRESHOLD)
+    }
+
+    pub fn is_restless(&self, species: Species) -> bool {
+        self.get(species).is_some_and(|def| def.restless)
+    }
+
+    // 按 id 排列的固体标记，第 i 位对应 id 为 i 的物质，流体的边界处理用它（MAX_SPECIES 正好是 32）
+    pub fn solid_mask(&self) -> u32 {
+        self.iter()
+            .filter(|def| def.solid)
+            .fold(0, |mask, def| mask | 1 << def.species.index())
+    }
+
+    // 与 solid_mask 相同的排列，标出 undamped 的物质
+    pub fn undamped_mask(&self) -> u32 {
+        self.iter()
+            .filter(|def| def.undamped)
+            .fold(0, |mask, def| mask | 1 << def.species.index())
+    }
+
+    pub fn heat_capacity(&self, species: Species) -> f32 {
+        self.get(species).map_or(DEFAULT_HEAT_CAPACITY, |def| def.heat_capacity)
+    }
+
+    pub fn conductivity(&self, species: Species) -> f32 {
+        self.get(species).map_or(DEFAULT_CONDUCTIVITY, |def| def.conductivity)
+    }
+
+    pub fn heat_source(&self, species: Species) -> Option<HeatSource> {
+        self.get(species).and_then(|def| def.heat_source)
+    }
+
+    pub fn heats_into(&self, species: Species) -> Option<PhaseChange> {
+        self.get(species).and_then(|def| def.heats_into)
+    }
+
+    pub fn cools_into(&self, species: Species) -> Option<PhaseChange> {
+        self.get(species).and_then(|def| def.cools_into)
+    }
+
+    pub(super) fn thermal(&self) -> &[Thermal; MAX_SPECIES] {
+        &self.thermal
+    }
+
+    // 画出来的细胞的温度：热源取自己的温度，其余为环境温度
+    pub fn initial_temperature(&self, species: Species) -> f32 {
+        self.heat_source(species).map_or(AMBIENT_TEMPERATURE, |source| source.temperature)
+    }
+
+    pub fn update_fn(&self, species: Species) -> fn(Cell, SandApi) {
+        self.get(species).map_or(update_none, |def| def.update)
+    }
+
+    // species 能否沉入 other：other 必须是流体且比 species 轻
+    pub fn sinks_into(&self, species: Species, other: Species) -> bool {
+        match (self.get(species), self.get(other)) {
+            (Some(heavy), Some(light)) => light.fluid && heavy.density > light.density,
+            _ => false,
+        }
+    }
+
+    // 每次注册都会递增，渲染端据此判断是否需要重建调色板
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    // 按 id 排列的调色板，每种物质占四个 vec4，未注册的 id 用 UNKNOWN
+    pub fn palette(&self) -> [[f32; 4]; MAX_SPECIES * 4] {
+        let mut palette = [[0.0; 4]; MAX_SPECIES * 4];
+        for (id, def) in self.defs.iter().enumerate() {
+            let colour = def.as_ref().map_or(SpeciesColour::UNKNOWN, |def| def.colour);
+            palette[id * 4..id * 4 + 4].copy_from_slice(&colour.packed());
+        }
+        palette
+    }
+
+    // 内置物质的名字，Species 的 Debug 输出也用它
+    pub(super) fn builtin_name(species: Species) -> Option<&'static str> {
+        let name = match species {
+            Species::Empty => "Empty",
+            Species::Wall => "Wall",
+            Species::Sand => "Sand",
+            Species::Water => "Water",
+            Species::Gas => "Gas",
+            Species::Cloner => "Cloner",
+            Species::Fire => "Fire",
+            Species::Wood => "Wood",
+            Species::Lava => "Lava",
+            Species::Ice => "Ice",
+            Species::Plant => "Plant",
+            Species::Acid => "Acid",
+            Species::Stone => "Stone",
+            Species::Dust => "Dust",
+            Species::Mite => "Mite",
+            Species::Oil => "Oil",
+            Species::Rocket => "Rocket",
+            Species::Fungus => "Fungus",
+            Species::Seed => "Seed",
+            Species::Glass => "Glass",
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    // 内置的物质，参数取自原来散落在 blow_wind、update_sand、update_stone 和 sand.wgsl 里的值
+    // 热学参数（摄氏度）：冰 0 度融化、水 100 度沸腾，岩浆低于 800 度凝成石头，沙子高于 600 度烧成玻璃
+    pub fn builtin() -> Self {
+        let mut registry = SpeciesRegistry::empty();
+        let plain = SpeciesColour::DEFAULT;
+
+        registry.register(SpeciesDef {
+            wind_threshold: 500,
+            // 空气
+            undamped: true,
+            conductivity: 0.005,
+            colour: SpeciesColour {
+                saturation: [0.1, 0.0, 0.0, 0.0],
+                lightness: [0.1, 0.0, 0.0, 0.0],
+                alpha: 0.1,
+                style: ColourStyle::Background,
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Empty, update_none)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 500,
+            solid: true,
+            heat_capacity: 2.0,
+            conductivity: 0.01,
+            colour: SpeciesColour {
+                hue: [0.1, 0.0, 0.0, 0.0],
+                saturation: [0.1, 0.0, 0.0, 0.0],
+                lightness: [0.4, 0.0, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Wall, update_none)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 30,
+            wind_lift: true,
+            density: 10,
+            heat_capacity: 2.0,
+            heats_into: Some(PhaseChange::new(600.0, Species::Glass, 50.0)),
+            colour: SpeciesColour {
+                hue: [0.1, 0.0, 0.0, 0.0],
+                saturation: [0.5, 0.0, 0.0, 0.0],
+                lightness: [0.6, 0.5, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Sand, update_sand)
+        });
+        registry.register(SpeciesDef {
+            wind_lift: true,
+            density: 3,
+            fluid: true,
+            heat_capacity: 4.0,
+            conductivity: 0.15,
+            heats_into: Some(PhaseChange::new(100.0, Species::Gas, 540.0)),
+            cools_into: Some(PhaseChange::new(0.0, Species::Ice, 80.0)),
+            colour: SpeciesColour {
+                hue: [0.6, 0.0, 0.0, 0.0],
+                lightness: [0.7, 0.25, 0.0, 0.1],
+                style: ColourStyle::Shimmer,
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Water, update_water)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 5,
+            density: 1,
+            fluid: true,
+            restless: true,
+            undamped: true,
+            colour: SpeciesColour {
+                saturation: [0.2, 0.0, 1.5, 0.0],
+                lightness: [0.7, 0.5, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Gas, update_gas)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 500,
+            restless: true,
+            solid: true,
+            colour: SpeciesColour {
+                hue: [0.9, 0.0, 0.0, 0.0],
+                saturation: [0.3, 0.0, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Cloner, update_cloner)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 5,
+            restless: true,
+            undamped: true,
+            conductivity: 0.05,
+            heat_source: Some(HeatSource { temperature: 800.0, strength: 1.0 }),
+            colour: SpeciesColour {
+                saturation: [0.9, 0.0, 0.0, 0.0],
+                lightness: [0.7, 0.3, 0.0, 0.0],
+                alpha: 1.0,
+                style: ColourStyle::Glow,
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Fire, update_fire)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 70,
+            heat_capacity: 2.0,
+            conductivity: 0.05,
+            colour: SpeciesColour {
+                hue: [0.0, 0.1, 0.0, 0.0],
+                saturation: [0.3, 0.0, 0.0, 0.0],
+                lightness: [0.3, 0.3, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Wood, update_wood)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 60,
+            wind_lift: true,
+            // 比沙子重，沙子不会沉进岩浆
+            density: 20,
+            fluid: true,
+            restless: true,
+            heat_capacity: 2.0,
+            conductivity: 0.25,
+            heat_source: Some(HeatSource { temperature: 1200.0, strength: 0.1 }),
+            cools_into: Some(PhaseChange::new(800.0, Species::Stone, 50.0)),
+            colour: SpeciesColour {
+                hue: [0.0, 0.1, 0.0, 0.0],
+                lightness: [0.7, 0.25, 0.0, 0.1],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Lava, update_lava)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 60,
+            heat_capacity: 2.0,
+            conductivity: 0.2,
+            heat_source: Some(HeatSource { temperature: -30.0, strength: 0.5 }),
+            heats_into: Some(PhaseChange::new(0.0, Species::Water, 80.0)),
+            colour: SpeciesColour {
+                hue: [0.6, 0.0, 0.0, 0.0],
+                saturation: [0.4, 0.0, 0.0, 0.0],
+                lightness: [0.7, 0.5, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Ice, update_ice)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 60,
+            restless: true,
+            colour: SpeciesColour {
+                hue: [0.4, 0.0, 0.0, 0.0],
+                saturation: [0.4, 0.0, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Plant, update_plant)
+        });
+        registry.register(SpeciesDef {
+            wind_lift: true,
+            density: 3,
+            fluid: true,
+            heat_capacity: 4.0,
+            conductivity: 0.15,
+            colour: SpeciesColour {
+                hue: [0.18, 0.0, 0.0, 0.0],
+                saturation: [0.9, 0.0, 0.0, 0.0],
+                lightness: [0.8, 0.2, 0.0, 0.05],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Acid, update_acid)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 70,
+            density: 10,
+            heat_capacity: 2.0,
+            colour: SpeciesColour {
+                hue: [-0.4, 0.5, 0.0, 0.0],
+                saturation: [0.1, 0.0, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Stone, update_stone)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 10,
+            wind_lift: true,
+            colour: SpeciesColour {
+                hue: [0.0, 2.0, 0.0, 0.0008],
+                saturation: [0.4, 0.0, 0.0, 0.0],
+                lightness: [0.8, 0.0, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Dust, update_dust)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 30,
+            wind_lift: true,
+            restless: true,
+            colour: SpeciesColour {
+                hue: [0.8, 0.0, 0.0, 0.0],
+                saturation: [0.9, 0.0, 0.0, 0.0],
+                lightness: [0.8, 0.0, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Mite, update_mite)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 50,
+            wind_lift: true,
+            density: 2,
+            fluid: true,
+            heat_capacity: 2.0,
+            conductivity: 0.05,
+            colour: SpeciesColour {
+                hue: [0.0, 5.0, 0.0, 0.008],
+                saturation: [0.2, 0.0, 0.0, 0.0],
+                lightness: [0.3, 0.0, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Oil, update_oil)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 30,
+            wind_lift: true,
+            restless: true,
+            colour: SpeciesColour {
+                saturation: [0.4, 0.0, 1.0, 0.0],
+                lightness: [0.9, 0.0, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Rocket, update_rocket)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 54,
+            restless: true,
+            colour: SpeciesColour {
+                hue: [-0.1, 0.15, 0.0, 0.0],
+                saturation: [-0.05, 0.8, 0.0, 0.0],
+                lightness: [1.5, -0.2, 0.0, 0.0],
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Fungus, update_fungus)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 35,
+            restless: true,
+            colour: SpeciesColour {
+                // fract(fract(2b) * 0.5) - 0.3
+                hue: [-0.3, 0.0, 2.0, 0.0],
+                saturation: [0.28, 0.7, 0.2, 0.0],
+                lightness: [0.81, 0.9, 0.0, 0.0],
+                style: ColourStyle::Wrapped,
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Seed, update_seed)
+        });
+        registry.register(SpeciesDef {
+            wind_threshold: 500,
+            solid: true,
+            heat_capacity: 2.0,
+            conductivity: 0.05,
+            colour: SpeciesColour {
+                hue: [0.5, 0.0, 0.0, 0.0],
+                saturation: [0.15, 0.0, 0.0, 0.0],
+                lightness: [0.8, 0.15, 0.0, 0.05],
+                alpha: 0.5,
+                ..plain
+            },
+            ..SpeciesDef::builtin(Species::Glass, update_none)
+        });
+        registry
+    }
+}
+
+fn update_none(_cell: Cell, _api: SandApi) {}
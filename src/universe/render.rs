@@ -0,0 +1,247 @@
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::Image;
+use super::registry::ColourStyle;
+use super::{CellGrid, MAX_SPECIES};
+
+// CPU 上的 sand.wgsl：按注册表的调色板给每个细胞着色，不需要 GPU，无窗口模式和测试用它出图
+// 每个细胞对应一个片段，窗口大小等于网格时与窗口里看到的一一对应
+// 噪声用的 sin 在 GPU 上精度不同，噪声项的结果可能差一两个字节，其余部分逐项与着色器相同
+// 窗口的渲染目标是 sRGB 格式，着色器输出的线性颜色写入时会编码成 sRGB，这里也一样
+
+// 与 sand.wgsl 的 ShaderParams 对应，resolution 取网格尺寸
+#[derive(Debug, Clone, Copy)]
+pub struct RenderParams {
+    pub time: f32,
+    pub dpi: f32,
+    // 截图模式：背景变成白底不透明，自发光的物质变暗
+    pub is_snapshot: bool,
+}
+
+impl Default for RenderParams {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            dpi: 1.0,
+            is_snapshot: false,
+        }
+    }
+}
+
+impl RenderParams {
+    pub fn snapshot() -> Self {
+        Self {
+            is_snapshot: true,
+            ..Self::default()
+        }
+    }
+}
+
+// 行优先、y 向下的 RGBA8 像素，颜色是 sRGB 编码，alpha 是着色器输出的不透明度，没有预乘
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * self.width + x) * 4) as usize;
+        [self.data[offset], self.data[offset + 1], self.data[offset + 2], self.data[offset + 3]]
+    }
+
+    // 像窗口那样在线性空间按 alpha 混合到纯色背景上（相机的清屏颜色是白色），结果不透明
+    pub fn flatten(&mut self, background: [u8; 3]) {
+        for pixel in self.data.chunks_exact_mut(4) {
+            let a = pixel[3] as f32 / 255.0;
+            for c in 0..3 {
+                let colour = srgb_to_linear(pixel[c] as f32 / 255.0);
+                let under = srgb_to_linear(background[c] as f32 / 255.0);
+                pixel[c] = to_byte(linear_to_srgb(colour * a + under * (1.0 - a)));
+            }
+            pixel[3] = 255;
+        }
+    }
+
+    // 字节本来就是 sRGB 编码；try_into_dynamic 只接受 Rgba8UnormSrgb，不接受 Rgba8Unorm
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let image = Image::new(
+            Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            self.data.clone(),
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::MAIN_WORLD,
+        );
+        let dynamic = image
+            .try_into_dynamic()
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        dynamic
+            .save(path)
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+}
+
+pub fn render_cells_rgba(cell_grid: &CellGrid, params: RenderParams) -> RgbaImage {
+    let (width, height) = (cell_grid.width, cell_grid.height);
+    let palette = cell_grid.registry().palette();
+    let mut data = Vec::with_capacity(cell_grid.cells.len() * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let cell = cell_grid.cells[cell_grid.get_index(x, y)];
+            // 片段中心的裁剪空间坐标，y 向上
+            let clip = [
+                (x as f32 + 0.5) / width as f32 * 2.0 - 1.0,
+                1.0 - (y as f32 + 0.5) / height as f32 * 2.0,
+            ];
+            let pixel_pos = [
+                (clip[0] * width as f32 / params.dpi).floor(),
+                (clip[1] * height as f32 / params.dpi).floor(),
+            ];
+            let rgba = shade(&palette, cell.species.id(), cell.ra, cell.rb, pixel_pos, params);
+            let [r, g, b, a] = rgba;
+            data.extend([r, g, b].map(linear_to_srgb).map(to_byte));
+            data.push(to_byte(a));
+        }
+    }
+    RgbaImage {
+        width: width as u32,
+        height: height as u32,
+        data,
+    }
+}
+
+// sand.wgsl 的 fragment
+fn shade(
+    palette: &[[f32; 4]; MAX_SPECIES * 4],
+    species: u8,
+    ra: u8,
+    rb: u8,
+    pixel_pos: [f32; 2],
+    params: RenderParams,
+) -> [f32; 4] {
+    let noise3 = snoise3([pixel_pos[0], pixel_pos[1], params.time * 0.05]);
+    let noise2 = snoise2(pixel_pos);
+
+    let type_id = species as usize;
+    if type_id >= MAX_SPECIES {
+        let [r, g, b] = hsv2rgb([0.0, 1.0, 1.0]);
+        return [r, g, b, 1.0];
+    }
+    let [h, s, l, extra] = [
+        palette[type_id * 4],
+        palette[type_id * 4 + 1],
+        palette[type_id * 4 + 2],
+        palette[type_id * 4 + 3],
+    ];
+    // 纹理里的 unorm 字节
+    let g = ra as f32 / 255.0;
+    let b = rb as f32 / 255.0;
+    let time = params.time;
+
+    let mut hue = h[0] + h[1] * g + h[2] * b + h[3] * time;
+    let mut saturation = s[0] + s[1] * g + s[2] * b + s[3] * time;
+    let mut lightness = l[0] + l[1] * g + l[2] * b + l[3] * noise3;
+    let mut a = extra[0];
+
+    let style = extra[1].round() as u8;
+    if style == ColourStyle::Background as u8 {
+        if params.is_snapshot {
+            saturation = 0.05;
+            lightness = 1.01;
+            a = 1.0;
+        }
+    } else if style == ColourStyle::Shimmer as u8 {
+        if (g * 255.0) as i32 % 2 == 0 {
+            lightness += 0.01;
+        }
+    } else if style == ColourStyle::Glow as u8 {
+        if params.is_snapshot {
+            lightness -= 0.2;
+        }
+        let [r, g, b] = hsv2rgb([hue, saturation, lightness]);
+        return [r, g, b, 1.0];
+    } else if style == ColourStyle::Wrapped as u8 {
+        hue = h[0] + fract(fract(h[1] * g + h[2] * b + h[3] * time) * 0.5);
+    }
+
+    lightness *= 0.975 + noise2 * 0.025;
+    let [r, g, b] = hsv2rgb([hue, saturation, lightness]);
+    [r, g, b, a]
+}
+
+fn to_byte(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    let v = v.clamp(0.0, 1.0);
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn fract(v: f32) -> f32 {
+    v - v.floor()
+}
+
+fn mod1(a: f32, b: f32) -> f32 {
+    a - b * (a / b).floor()
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
+
+// 与 sand.wgsl 的 hsv2rgb 相同，色相不回绕，小于 0 的按第一段处理
+fn hsv2rgb([h, s, v]: [f32; 3]) -> [f32; 3] {
+    let c = v * s;
+    let x = c * (1.0 - (mod1(h * 6.0, 2.0) - 1.0).abs());
+    let m = v - c;
+    if h < 1.0 / 6.0 {
+        [c + m, x + m, m]
+    } else if h < 2.0 / 6.0 {
+        [x + m, c + m, m]
+    } else if h < 3.0 / 6.0 {
+        [m, c + m, x + m]
+    } else if h < 4.0 / 6.0 {
+        [m, x + m, c + m]
+    } else if h < 5.0 / 6.0 {
+        [x + m, m, c + m]
+    } else {
+        [c + m, m, x + m]
+    }
+}
+
+fn random([x, y]: [f32; 2]) -> f32 {
+    fract((x * 12.9898 + y * 78.233).sin() * 43_758.547)
+}
+
+fn snoise2(pos: [f32; 2]) -> f32 {
+    let i = [pos[0].floor(), pos[1].floor()];
+    let f = [fract(pos[0]), fract(pos[1])];
+
+    let a = random(i);
+    let b = random([i[0] + 1.0, i[1]]);
+    let c = random([i[0], i[1] + 1.0]);
+    let d = random([i[0] + 1.0, i[1] + 1.0]);
+
+    let u = f.map(|f| f * f * (3.0 - 2.0 * f));
+    mix(a, b, u[0]) + (c - a) * u[1] * (1.0 - u[0]) + (d - b) * u[0] * u[1]
+}
+
+fn snoise3(pos: [f32; 3]) -> f32 {
+    snoise2([pos[0], pos[1]]) * 0.8 + snoise2([pos[1], pos[2]]) * 0.2
+}
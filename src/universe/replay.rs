@@ -0,0 +1,269 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use super::{CellGrid, Species};
+
+// 确定性回放：录制期间 CellGrid 记下每一次 paint（初始场景、投放线程、鼠标绘制都经过 paint）、
+// 笔画的开始和结束、撤销和重做，以及它们发生在第几次 tick 之前，每次 tick 之后记一个校验和
+// 撤销以笔画为单位，所以笔画的边界也要原样重放
+// 回放时从同样的初始网格出发，按原来的 tick 顺序重新 paint、tick，并逐代比对校验和
+// GPU 读回的风场依赖帧时序，不可复现，所以回放只针对无窗口模式（可选 CPU 流体求解器）
+//
+// 日志是纯文本：
+//   sandreplay 2
+//   size <width> <height>
+//   load <path>              （可选，从存档或 PNG 开始录制时）
+//   fluid <0|1>
+//   config <key> = <value>   （--fluid 时的 FluidConfig，每个字段一行，格式同配置文件）
+//   ticks <n>
+//   paint <tick> <x> <y> <size> <species>
+//   stroke <tick> | end <tick> | undo <tick> | redo <tick>
+//   check <tick> <checksum>
+// 同一个 tick 里的事件按文件里的顺序重放；版本 1 的日志没有 config 和笔画，照样能读
+const HEADER: &str = "sandreplay 2";
+const OLD_HEADERS: [&str; 1] = ["sandreplay 1"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Paint { x: i32, y: i32, size: i32, species: Species },
+    BeginStroke,
+    EndStroke,
+    Undo,
+    Redo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditEvent {
+    pub tick: u64,
+    pub edit: Edit,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayLog {
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+    // 录制开始时的网格来源，None 表示 CellGrid::new 出来的空网格
+    pub(crate) load: Option<String>,
+    pub(crate) fluid: bool,
+    // 录制时的流体配置，配置文件格式的文本；CellGrid 不认识 FluidConfig，由调用方写入和解析
+    pub(crate) fluid_config: Option<String>,
+    pub(crate) ticks: u64,
+    pub(crate) edits: Vec<EditEvent>,
+    // (tick 之后的 tick 计数, 校验和)
+    pub(crate) checksums: Vec<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    pub tick: u64,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl CellGrid {
+    // 开始录制，之前的 tick 计数清零
+    pub fn start_recording(&mut self) {
+        self.ticks = 0;
+        self.recording = Some(ReplayLog {
+            width: self.width,
+            height: self.height,
+            ..ReplayLog::default()
+        });
+    }
+
+    pub fn take_recording(&mut self) -> Option<ReplayLog> {
+        let mut log = self.recording.take()?;
+        log.ticks = self.ticks;
+        Some(log)
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    // 细胞状态和 generation 的 FNV-1a 校验和
+    pub fn checksum(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut feed = |byte: u8| {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        };
+        feed(self.generation);
+        for cell in self.cells.iter() {
+            feed(cell.species.id());
+            feed(cell.ra);
+            feed(cell.rb);
+            feed(cell.clock);
+        }
+        hash
+    }
+
+    pub(super) fn record_edit(&mut self, edit: Edit) {
+        let tick = self.ticks;
+        if let Some(log) = &mut self.recording {
+            log.edits.push(EditEvent { tick, edit });
+        }
+    }
+
+    pub(super) fn record_tick(&mut self) {
+        self.ticks += 1;
+        if self.recording.is_some() {
+            let checksum = self.checksum();
+            let tick = self.ticks;
+            if let Some(log) = &mut self.recording {
+                log.checksums.push((tick, checksum));
+            }
+        }
+    }
+}
+
+impl ReplayLog {
+    // 从 base 开始重放，after_tick 在每次 tick 之后调用（用来推进 CPU 流体求解器）
+    // 第一次校验和不一致时返回出错的 tick
+    pub fn replay<F: FnMut(&mut CellGrid)>(
+        &self,
+        mut base: CellGrid,
+        mut after_tick: F,
+    ) -> Result<CellGrid, Divergence> {
+        base.recording = None;
+        base.ticks = 0;
+        let mut edits = self.edits.iter().peekable();
+        let mut checksums = self.checksums.iter().peekable();
+        for tick in 0..=self.ticks {
+            while let Some(event) = edits.next_if(|e| e.tick == tick) {
+                match event.edit {
+                    Edit::Paint { x, y, size, species } => base.paint(x, y, size, species),
+                    Edit::BeginStroke => base.begin_stroke(),
+                    Edit::EndStroke => base.end_stroke(),
+                    Edit::Undo => {
+                        base.undo();
+                    }
+                    Edit::Redo => {
+                        base.redo();
+                    }
+                }
+            }
+            if tick == self.ticks {
+                break;
+            }
+            base.tick();
+            after_tick(&mut base);
+            if let Some(&(_, expected)) = checksums.next_if(|(t, _)| *t == base.ticks) {
+                let actual = base.checksum();
+                if actual != expected {
+                    return Err(Divergence { tick: base.ticks, expected, actual });
+                }
+            }
+        }
+        Ok(base)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", HEADER);
+        let _ = writeln!(out, "size {} {}", self.width, self.height);
+        if let Some(load) = &self.load {
+            let _ = writeln!(out, "load {}", load);
+        }
+        let _ = writeln!(out, "fluid {}", self.fluid as u8);
+        if let Some(config) = &self.fluid_config {
+            for line in config.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+                let _ = writeln!(out, "config {}", line);
+            }
+        }
+        let _ = writeln!(out, "ticks {}", self.ticks);
+        for e in self.edits.iter() {
+            let _ = match e.edit {
+                Edit::Paint { x, y, size, species } => {
+                    writeln!(out, "paint {} {} {} {} {}", e.tick, x, y, size, species.id())
+                }
+                Edit::BeginStroke => writeln!(out, "stroke {}", e.tick),
+                Edit::EndStroke => writeln!(out, "end {}", e.tick),
+                Edit::Undo => writeln!(out, "undo {}", e.tick),
+                Edit::Redo => writeln!(out, "redo {}", e.tick),
+            };
+        }
+        for (tick, checksum) in self.checksums.iter() {
+            let _ = writeln!(out, "check {} {:016x}", tick, checksum);
+        }
+        fs::write(path, out)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ReplayLog> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines();
+        let header = lines.next();
+        if header != Some(HEADER) && !header.is_some_and(|h| OLD_HEADERS.contains(&h)) {
+            return Err(invalid("not a replay log"));
+        }
+        let mut log = ReplayLog::default();
+        for (n, line) in lines.enumerate() {
+            let line_no = n + 2;
+            let bad = || invalid(&format!("bad replay line {}: {}", line_no, line));
+            let mut fields = line.split_whitespace();
+            let Some(kind) = fields.next() else {
+                continue;
+            };
+            match kind {
+                "load" => log.load = Some(line["load".len()..].trim().to_string()),
+                "config" => {
+                    let config = log.fluid_config.get_or_insert_with(String::new);
+                    let _ = writeln!(config, "{}", line["config".len()..].trim());
+                }
+                _ => {
+                    let values: Vec<&str> = fields.collect();
+                    let int = |i: usize| -> io::Result<i64> {
+                        values.get(i).and_then(|v| v.parse().ok()).ok_or_else(bad)
+                    };
+                    match kind {
+                        "size" => {
+                            log.width = int(0)? as i32;
+                            log.height = int(1)? as i32;
+                        }
+                        "fluid" => log.fluid = int(0)? != 0,
+                        "ticks" => log.ticks = int(0)? as u64,
+                        "paint" => {
+                            let species = Species::from_u8(int(4)? as u8).ok_or_else(bad)?;
+                            let edit = Edit::Paint {
+                                x: int(1)? as i32,
+                                y: int(2)? as i32,
+                                size: int(3)? as i32,
+                                species,
+                            };
+                            log.edits.push(EditEvent { tick: int(0)? as u64, edit });
+                        }
+                        "stroke" | "end" | "undo" | "redo" => {
+                            let edit = match kind {
+                                "stroke" => Edit::BeginStroke,
+                                "end" => Edit::EndStroke,
+                                "undo" => Edit::Undo,
+                                _ => Edit::Redo,
+                            };
+                            log.edits.push(EditEvent { tick: int(0)? as u64, edit });
+                        }
+                        "check" => {
+                            let checksum = values
+                                .get(1)
+                                .and_then(|v| u64::from_str_radix(v, 16).ok())
+                                .ok_or_else(bad)?;
+                            log.checksums.push((int(0)? as u64, checksum));
+                        }
+                        _ => return Err(bad()),
+                    }
+                }
+            }
+        }
+        if log.width <= 0 || log.height <= 0 {
+            return Err(invalid("replay log has no size"));
+        }
+        // 稳定排序，同一个 tick 里保持文件里的顺序
+        log.edits.sort_by_key(|e| e.tick);
+        log.checksums.sort_by_key(|c| c.0);
+        Ok(log)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,172 @@
+use std::fmt::Write as _;
+use rand::SeedableRng;
+use rand_xoshiro::SplitMix64;
+use super::{CellGrid, Species, Wind};
+
+// 物质规则的测试场景：用字符画搭一个小网格，固定种子跑若干次 tick，再对结果画面或物质数量断言
+//
+//   let mut scenario = Scenario::parse("
+//       .S.
+//       .W.
+//       ###
+//   ");
+//   scenario.run(10);
+//   scenario.assert_map("
+//       ...
+//       .S.
+//       ###
+//   ");
+//
+// 每一行是网格的一行，y 向下增长（和物质下落的方向一致），行首尾的空白会被去掉，空行会被跳过
+// 细胞按 paint 的方式逐个写入（笔刷大小为 1），ra 等参数和鼠标画出来的一样
+// 风场和 CellGrid::new 一样是静止的（CALM_WIND），场景只受物质规则影响，不依赖流体求解器；
+// 需要风的测试用 set_winds 写入，tick 不会改写风场
+const LEGEND: [(char, Species); 20] = [
+    ('.', Species::Empty),
+    ('#', Species::Wall),
+    ('S', Species::Sand),
+    ('W', Species::Water),
+    ('G', Species::Gas),
+    ('C', Species::Cloner),
+    ('F', Species::Fire),
+    ('T', Species::Wood),
+    ('L', Species::Lava),
+    ('I', Species::Ice),
+    ('P', Species::Plant),
+    ('A', Species::Acid),
+    ('R', Species::Stone),
+    ('D', Species::Dust),
+    ('M', Species::Mite),
+    ('O', Species::Oil),
+    ('K', Species::Rocket),
+    ('N', Species::Fungus),
+    ('E', Species::Seed),
+    ('Q', Species::Glass),
+];
+
+// 没有图例的物质（第三方注册的）画成 '?'
+const UNKNOWN_GLYPH: char = '?';
+
+// 和 CellGrid::new 默认的种子不同，避免场景无意间依赖那条序列
+pub const DEFAULT_SEED: u64 = 0x5ce7_a210;
+
+pub fn species_for(glyph: char) -> Option<Species> {
+    LEGEND.iter().find(|(c, _)| *c == glyph).map(|(_, species)| *species)
+}
+
+pub fn glyph_for(species: Species) -> char {
+    LEGEND
+        .iter()
+        .find(|(_, s)| *s == species)
+        .map_or(UNKNOWN_GLYPH, |(c, _)| *c)
+}
+
+pub struct Scenario {
+    grid: CellGrid,
+}
+
+impl Scenario {
+    pub fn parse(map: &str) -> Scenario {
+        Scenario::with_seed(map, DEFAULT_SEED)
+    }
+
+    pub fn with_seed(map: &str, seed: u64) -> Scenario {
+        let rows = rows(map);
+        assert!(!rows.is_empty(), "scenario map is empty");
+        let width = rows[0].len();
+        for (y, row) in rows.iter().enumerate() {
+            assert_eq!(row.len(), width, "row {} of the scenario map has a different width", y);
+        }
+
+        let mut grid = CellGrid::new(width as i32, rows.len() as i32);
+        grid.rng = SplitMix64::seed_from_u64(seed);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, glyph) in row.iter().enumerate() {
+                let species = species_for(*glyph)
+                    .unwrap_or_else(|| panic!("unknown glyph {:?} at ({}, {})", glyph, x, y));
+                if species != Species::Empty {
+                    grid.paint(x as i32, y as i32, 1, species);
+                }
+            }
+        }
+        Scenario { grid }
+    }
+
+    pub fn grid(&mut self) -> &mut CellGrid {
+        &mut self.grid
+    }
+
+    // 把整块风场设成同一个值
+    pub fn set_winds(&mut self, wind: Wind) {
+        self.grid.winds.fill(wind);
+        self.grid.refresh_windy_chunks();
+    }
+
+    pub fn run(&mut self, ticks: usize) -> &mut Scenario {
+        for _ in 0..ticks {
+            self.grid.tick();
+        }
+        self
+    }
+
+    pub fn species_at(&self, x: i32, y: i32) -> Species {
+        self.grid.get_cell(x, y).species
+    }
+
+    pub fn count(&self, species: Species) -> usize {
+        self.grid.cells.iter().filter(|cell| cell.species == species).count()
+    }
+
+    // 当前网格的字符画，和 parse 的输入格式相同
+    pub fn map(&self) -> String {
+        let mut out = String::new();
+        for y in 0..self.grid.height {
+            for x in 0..self.grid.width {
+                out.push(glyph_for(self.species_at(x, y)));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn assert_map(&self, expected: &str) {
+        let expected: Vec<String> = rows(expected).into_iter().map(|row| row.into_iter().collect()).collect();
+        let actual = self.map();
+        let actual: Vec<&str> = actual.lines().collect();
+        if expected != actual {
+            panic!(
+                "scenario map mismatch after {} ticks\nexpected:\n{}\nactual:\n{}",
+                self.grid.ticks,
+                expected.join("\n"),
+                actual.join("\n")
+            );
+        }
+    }
+
+    // 断言每种物质的数量，没列出的物质不检查
+    pub fn assert_counts(&self, expected: &[(Species, usize)]) {
+        let mut mismatches = String::new();
+        for (species, count) in expected {
+            let actual = self.count(*species);
+            if actual != *count {
+                let _ = writeln!(mismatches, "  {:?}: expected {}, got {}", species, count, actual);
+            }
+        }
+        if !mismatches.is_empty() {
+            panic!(
+                "species counts mismatch after {} ticks\n{}map:\n{}",
+                self.grid.ticks,
+                mismatches,
+                self.map()
+            );
+        }
+    }
+}
+
+fn rows(map: &str) -> Vec<Vec<char>> {
+    map.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.chars().collect())
+        .collect()
+}
@@ -0,0 +1,313 @@
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use rand::{RngCore, SeedableRng};
+use rand_xoshiro::SplitMix64;
+use super::*;
+
+// 并行 tick：网格切成 CHUNK_SIZE 见方的块，按块坐标的奇偶分成四个相位（棋盘格）
+// 同一相位的块之间至少隔着一整块，而 SandApi 的读写范围是 ±2，
+// 所以同一相位里的块互不干扰，可以交给不同线程同时更新；四个相位依次执行
+// 每个块有自己的 SplitMix64，种子由网格 rng 每次 tick 取出的一个数和块的编号决定，
+// 块内按固定顺序扫描，因此同一个种子的结果与线程数、线程调度都无关
+//
+// 块的边长必须大于 4（两侧各 ±2 的读写范围），否则相隔一块的两个块会碰到同一个细胞
+pub const CHUNK_SIZE: i32 = 32;
+
+const PHASES: [(i32, i32); 4] = [(0, 0), (1, 0), (0, 1), (1, 1)];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pass {
+    Wind,
+    Update,
+}
+
+// 一个块在 tick 期间看到的网格：cells/winds/burns/温度是共享的整张网格，其余是只读参数和块自己的 rng
+// 更新期间温度只会随移动的细胞交换（见 SandApi::set），扩散和相变在 tick 末尾的 step_heat 里
+// SandApi 通过它读写细胞，接口和直接操作 CellGrid 时一样
+pub struct Shard<'g> {
+    pub(super) width: i32,
+    pub(super) height: i32,
+    pub(super) generation: u8,
+    pub(super) cells: GridSlice<'g, Cell>,
+    pub(super) winds: GridSlice<'g, Wind>,
+    pub(super) burns: GridSlice<'g, Wind>,
+    pub(super) temperature: GridSlice<'g, f32>,
+    pub(super) latent: GridSlice<'g, f32>,
+    pub(super) rng: SplitMix64,
+    pub(super) registry: &'g SpeciesRegistry,
+    emission: &'g [Emission; MAX_SPECIES],
+    chunks_x: i32,
+    // 本块更新期间真正被改变的细胞所在的块（本块或邻块），相位结束后统一唤醒
+    changed: Vec<usize>,
+    // 本块里有 restless 物质
+    restless: bool,
+}
+
+impl Shard<'_> {
+    pub(super) fn get_index(&self, x: i32, y: i32) -> usize {
+        coords::cell_index(self.width, x, y)
+    }
+
+    pub(super) fn get_cell(&self, x: i32, y: i32) -> Cell {
+        self.cells[self.get_index(x, y)]
+    }
+
+    pub(super) fn emission(&self, species: Species) -> Emission {
+        self.emission[species.index()]
+    }
+
+    // 交换两个位置的温度和潜热
+    pub(super) fn swap_heat(&mut self, a: usize, b: usize) {
+        let (t, l) = (self.temperature[a], self.latent[a]);
+        self.temperature[a] = self.temperature[b];
+        self.latent[a] = self.latent[b];
+        self.temperature[b] = t;
+        self.latent[b] = l;
+    }
+
+    pub(super) fn touch(&mut self, x: i32, y: i32) {
+        let chunk = ((y / CHUNK_SIZE) * self.chunks_x + x / CHUNK_SIZE) as usize;
+        if !self.changed.contains(&chunk) {
+            self.changed.push(chunk);
+        }
+    }
+}
+
+// 多个线程同时持有的网格切片，只在相位划分保证不重叠的位置上读写
+pub struct GridSlice<'g, T> {
+    ptr: *mut T,
+    len: usize,
+    _marker: PhantomData<&'g mut [T]>,
+}
+
+// 同一相位内各线程访问的下标互不相交（见文件开头的说明）
+unsafe impl<T: Send> Send for GridSlice<'_, T> {}
+unsafe impl<T: Send> Sync for GridSlice<'_, T> {}
+
+impl<'g, T> GridSlice<'g, T> {
+    fn new(data: &'g mut [T]) -> Self {
+        GridSlice {
+            ptr: data.as_mut_ptr(),
+            len: data.len(),
+            _marker: PhantomData,
+        }
+    }
+
+    // 给一个块的复本，调用方负责保证块之间不重叠
+    fn share(&self) -> Self {
+        GridSlice {
+            ptr: self.ptr,
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Index<usize> for GridSlice<'_, T> {
+    type Output = T;
+
+    fn index(&self, i: usize) -> &T {
+        assert!(i < self.len, "grid index {} out of range", i);
+        unsafe { &*self.ptr.add(i) }
+    }
+}
+
+impl<T> IndexMut<usize> for GridSlice<'_, T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        assert!(i < self.len, "grid index {} out of range", i);
+        unsafe { &mut *self.ptr.add(i) }
+    }
+}
+
+struct SharedGrid<'g> {
+    width: i32,
+    height: i32,
+    generation: u8,
+    seed: u64,
+    cells: GridSlice<'g, Cell>,
+    winds: GridSlice<'g, Wind>,
+    burns: GridSlice<'g, Wind>,
+    temperature: GridSlice<'g, f32>,
+    latent: GridSlice<'g, f32>,
+    registry: &'g SpeciesRegistry,
+    emission: &'g [Emission; MAX_SPECIES],
+    chunks_x: i32,
+}
+
+impl SharedGrid<'_> {
+    fn shard(&self, pass: Pass, chunk: usize) -> Shard<'_> {
+        // 每个 (pass, 块) 一条独立的随机数流
+        let salt = (chunk as u64 * 2 + (pass == Pass::Update) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        Shard {
+            width: self.width,
+            height: self.height,
+            generation: self.generation,
+            cells: self.cells.share(),
+            winds: self.winds.share(),
+            burns: self.burns.share(),
+            temperature: self.temperature.share(),
+            latent: self.latent.share(),
+            rng: SplitMix64::seed_from_u64(self.seed ^ salt),
+            registry: self.registry,
+            emission: self.emission,
+            chunks_x: self.chunks_x,
+            changed: Vec::new(),
+            restless: false,
+        }
+    }
+}
+
+impl CellGrid {
+    pub fn tick(&mut self) {
+        let seed = self.rng.next_u64();
+        self.activity.begin_tick();
+        self.wake_windy_chunks();
+
+        self.run_pass(Pass::Wind, seed);
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 255 {
+            self.generation /= 2;
+        }
+        self.run_pass(Pass::Update, seed);
+        self.step_heat();
+        self.activity.end_tick();
+
+        self.generation = self.generation.wrapping_add(1);
+        self.record_tick();
+    }
+
+    // tick 使用的线程数，至少为 1；结果与线程数无关
+    pub fn tick_threads(&self) -> usize {
+        self.tick_threads
+    }
+
+    pub fn set_tick_threads(&mut self, threads: usize) {
+        self.tick_threads = threads.max(1);
+    }
+
+    pub(super) fn default_tick_threads() -> usize {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
+    fn run_pass(&mut self, pass: Pass, seed: u64) {
+        let chunks_x = self.activity.chunks_x();
+        let chunks_y = (self.height + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let threads = self.tick_threads;
+        let activity = &mut self.activity;
+        let grid = SharedGrid {
+            width: self.width,
+            height: self.height,
+            generation: self.generation,
+            seed,
+            cells: GridSlice::new(&mut self.cells),
+            winds: GridSlice::new(&mut self.winds),
+            burns: GridSlice::new(&mut self.burns),
+            temperature: GridSlice::new(&mut self.heat.temperature),
+            latent: GridSlice::new(&mut self.heat.latent),
+            registry: &self.registry,
+            emission: &self.emission,
+            chunks_x,
+        };
+
+        for (px, py) in PHASES {
+            // 休眠的块跳过；相位开始时才判断，前一个相位唤醒的块在本相位就会更新
+            let chunks: Vec<(i32, i32)> = (0..chunks_y)
+                .filter(|cy| cy % 2 == py)
+                .flat_map(|cy| (0..chunks_x).filter(move |cx| cx % 2 == px).map(move |cx| (cx, cy)))
+                .filter(|&(cx, cy)| activity.is_awake(activity.chunk_index(cx, cy)))
+                .collect();
+            let run = |&(cx, cy): &(i32, i32)| {
+                let chunk = (cy * chunks_x + cx) as usize;
+                let mut shard = grid.shard(pass, chunk);
+                update_chunk(&mut shard, pass, cx, cy);
+                ChunkResult {
+                    chunk,
+                    changed: shard.changed,
+                    restless: shard.restless,
+                }
+            };
+
+            let results: Vec<ChunkResult> = if threads <= 1 || chunks.len() <= 1 {
+                chunks.iter().map(run).collect()
+            } else {
+                // 线程从队列里领块，领到哪些块不影响结果
+                let next = AtomicUsize::new(0);
+                thread::scope(|scope| {
+                    let workers: Vec<_> = (0..threads.min(chunks.len()))
+                        .map(|_| {
+                            scope.spawn(|| {
+                                let mut results = Vec::new();
+                                while let Some(chunk) = chunks.get(next.fetch_add(1, Ordering::Relaxed)) {
+                                    results.push(run(chunk));
+                                }
+                                results
+                            })
+                        })
+                        .collect();
+                    workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+                })
+            };
+
+            // 唤醒、标脏都是幂等的，合并顺序不影响结果
+            for result in results {
+                activity.mark_dirty(result.chunk);
+                if result.restless {
+                    activity.keep_awake(result.chunk);
+                }
+                for chunk in result.changed {
+                    activity.wake(chunk);
+                }
+            }
+        }
+    }
+}
+
+struct ChunkResult {
+    chunk: usize,
+    changed: Vec<usize>,
+    restless: bool,
+}
+
+// 块内按行扫描，和行优先的存储顺序一致：风力一遍按 x 递增，更新一遍的 x 方向随 generation 交替
+fn update_chunk(shard: &mut Shard, pass: Pass, cx: i32, cy: i32) {
+    let x0 = cx * CHUNK_SIZE;
+    let x1 = (x0 + CHUNK_SIZE).min(shard.width);
+    let y0 = cy * CHUNK_SIZE;
+    let y1 = (y0 + CHUNK_SIZE).min(shard.height);
+
+    for y in y0..y1 {
+        for i in 0..x1 - x0 {
+            let x = match pass {
+                Pass::Update if shard.generation % 2 == 0 => x1 - (1 + i),
+                _ => x0 + i,
+            };
+            let idx = shard.get_index(x, y);
+            let cell = shard.cells[idx];
+            let wind = shard.winds[idx];
+            if pass == Pass::Update {
+                shard.burns[idx] = Wind {
+                    dx: 0,
+                    dy: 0,
+                    pressure: 0,
+                    density: 0,
+                };
+            }
+            if pass == Pass::Update && shard.registry.is_restless(cell.species) {
+                shard.restless = true;
+            }
+            let api = SandApi {
+                universe: &mut *shard,
+                x,
+                y,
+                species: cell.species,
+                carried: false,
+            };
+            match pass {
+                Pass::Wind => CellGrid::blow_wind(cell, wind, api),
+                Pass::Update => CellGrid::update_cell(cell, api),
+            }
+        }
+    }
+}
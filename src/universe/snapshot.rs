@@ -0,0 +1,286 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use rand::{RngCore, SeedableRng};
+use rand_xoshiro::SplitMix64;
+use super::activity::Activity;
+use super::heat::Heat;
+use super::history::History;
+use super::{coords, Cell, CellGrid, Emission, Species, SpeciesRegistry, Wind, MAX_SPECIES};
+
+// CellGrid 的存档格式（小端序）：
+//   magic "SNDG" | version u16 | width u32 | height u32 | generation u8 | rng 状态 u64
+//   | 32 组发射强度 (velocity, pressure, density: f32)
+//   | cells、winds、burns、温度、潜热五段，每段为 u32 字节长度 + 游程编码的数据
+//   | tick 计数 u64 | 每个块一个 u8 的连续空闲代数（见 activity.rs），块按行优先排列
+// 温度和潜热（见 heat.rs）每个细胞一个 f32，正好也是 4 字节的记录
+// 游程编码以 4 字节记录为单位：u16 重复次数 + 记录本身，大片空白的网格能压缩到很小
+// 读档后继续 tick 与存档时的网格逐位一致（rng 状态、generation、发射强度、tick 计数和块的休眠状态都会恢复）
+// 存档只记录物质 id，不包含注册表：读档后使用内置注册表，自定义物质需要重新 register
+// 版本 2 起各段数据按行优先排列（见 coords.rs）；版本 1 是列优先的，读入时转置
+// 版本 3 加入了温度、潜热、tick 计数和休眠状态，更早的存档读入时每个细胞取物质的初始温度，
+// tick 从 0 开始，所有块都是清醒的
+const MAGIC: &[u8; 4] = b"SNDG";
+const VERSION: u16 = 3;
+const COLUMN_MAJOR_VERSION: u16 = 1;
+const NO_HEAT_VERSION: u16 = 2;
+
+const GOLDEN_GAMMA: u64 = 0x9e3779b97f4a7c15;
+const MIX1: u64 = 0xbf58476d1ce4e5b9;
+const MIX2: u64 = 0x94d049bb133111eb;
+
+impl CellGrid {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<CellGrid> {
+        CellGrid::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.width as u32).to_le_bytes());
+        out.extend_from_slice(&(self.height as u32).to_le_bytes());
+        out.push(self.generation);
+        out.extend_from_slice(&rng_state(&self.rng).to_le_bytes());
+        for emission in self.emission.iter() {
+            out.extend_from_slice(&emission.velocity.to_le_bytes());
+            out.extend_from_slice(&emission.pressure.to_le_bytes());
+            out.extend_from_slice(&emission.density.to_le_bytes());
+        }
+        let cells: Vec<[u8; 4]> = self
+            .cells
+            .iter()
+            .map(|c| [c.species.id(), c.ra, c.rb, c.clock])
+            .collect();
+        write_section(&mut out, &cells);
+        write_section(&mut out, &self.winds.iter().map(wind_bytes).collect::<Vec<_>>());
+        write_section(&mut out, &self.burns.iter().map(wind_bytes).collect::<Vec<_>>());
+        write_section(&mut out, &self.heat.temperature.iter().map(|t| t.to_le_bytes()).collect::<Vec<_>>());
+        write_section(&mut out, &self.heat.latent.iter().map(|l| l.to_le_bytes()).collect::<Vec<_>>());
+        out.extend_from_slice(&self.ticks.to_le_bytes());
+        out.extend_from_slice(self.activity.idle());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<CellGrid> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(invalid("not a cell grid snapshot"));
+        }
+        let version = reader.u16()?;
+        if version != VERSION && version != NO_HEAT_VERSION && version != COLUMN_MAJOR_VERSION {
+            return Err(invalid(&format!("unsupported snapshot version {}", version)));
+        }
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+        if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+            return Err(invalid("bad grid size"));
+        }
+        let count = (width as usize)
+            .checked_mul(height as usize)
+            .ok_or_else(|| invalid("bad grid size"))?;
+        let generation = reader.u8()?;
+        let rng_state = reader.u64()?;
+        let mut emission = [Emission::default(); MAX_SPECIES];
+        for e in emission.iter_mut() {
+            *e = Emission::new(reader.f32()?, reader.f32()?, reader.f32()?);
+        }
+
+        let read = |reader: &mut Reader| -> io::Result<Vec<[u8; 4]>> {
+            let records = read_section(reader, count)?;
+            Ok(if version == COLUMN_MAJOR_VERSION {
+                transpose(records, width as i32, height as i32)
+            } else {
+                records
+            })
+        };
+        let cells = read(&mut reader)?
+            .into_iter()
+            .map(|[species, ra, rb, clock]| {
+                let species = Species::from_u8(species)
+                    .ok_or_else(|| invalid(&format!("unknown species {}", species)))?;
+                Ok(Cell { species, ra, rb, clock })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let winds = read(&mut reader)?.into_iter().map(bytes_wind).collect();
+        let burns = read(&mut reader)?.into_iter().map(bytes_wind).collect();
+        let mut activity = Activity::new(width as i32, height as i32);
+        let mut ticks = 0;
+        let heat = if version == VERSION {
+            let temperature = read(&mut reader)?.into_iter().map(f32::from_le_bytes).collect();
+            let latent = read(&mut reader)?.into_iter().map(f32::from_le_bytes).collect();
+            ticks = reader.u64()?;
+            let idle = reader.take(activity.idle().len())?;
+            activity.set_idle(idle);
+            Some(Heat::from_parts(temperature, latent))
+        } else {
+            None
+        };
+
+        let mut cell_grid = CellGrid {
+            width: width as i32,
+            height: height as i32,
+            cells,
+            history: History::default(),
+            winds,
+            burns,
+            generation,
+            rng: SplitMix64::from_seed(rng_state.to_le_bytes()),
+            emission,
+            registry: SpeciesRegistry::builtin(),
+            ticks,
+            recording: None,
+            tick_threads: CellGrid::default_tick_threads(),
+            activity,
+            heat: Heat::new(width as i32, height as i32),
+        };
+        match heat {
+            Some(heat) => cell_grid.heat = heat,
+            None => cell_grid.reset_temperature(),
+        }
+        cell_grid.refresh_windy_chunks();
+        Ok(cell_grid)
+    }
+}
+
+// SplitMix64 没有公开内部状态：克隆一份取下一个输出，反解混合函数得到 x + gamma，再减去 gamma
+// 用 from_seed(state) 恢复后产生的序列与原 rng 完全一致
+fn rng_state(rng: &SplitMix64) -> u64 {
+    let mut z = rng.clone().next_u64();
+    z = unxorshift(z, 31);
+    z = z.wrapping_mul(mod_inverse(MIX2));
+    z = unxorshift(z, 27);
+    z = z.wrapping_mul(mod_inverse(MIX1));
+    z = unxorshift(z, 30);
+    z.wrapping_sub(GOLDEN_GAMMA)
+}
+
+// 反解 y = x ^ (x >> shift)
+fn unxorshift(y: u64, shift: u32) -> u64 {
+    let mut x = y;
+    for _ in 0..(64 / shift + 1) {
+        x = y ^ (x >> shift);
+    }
+    x
+}
+
+// 奇数在模 2^64 下的乘法逆元（牛顿迭代，每次有效位数翻倍）
+fn mod_inverse(a: u64) -> u64 {
+    let mut inv = a;
+    for _ in 0..5 {
+        inv = inv.wrapping_mul(2u64.wrapping_sub(a.wrapping_mul(inv)));
+    }
+    inv
+}
+
+// 列优先（records[x * height + y]）转成行优先
+fn transpose(records: Vec<[u8; 4]>, width: i32, height: i32) -> Vec<[u8; 4]> {
+    (0..records.len())
+        .map(|i| {
+            let (x, y) = coords::cell_coords(width, i);
+            records[(x * height + y) as usize]
+        })
+        .collect()
+}
+
+fn wind_bytes(wind: &Wind) -> [u8; 4] {
+    [wind.dx, wind.dy, wind.pressure, wind.density]
+}
+
+fn bytes_wind([dx, dy, pressure, density]: [u8; 4]) -> Wind {
+    Wind { dx, dy, pressure, density }
+}
+
+fn write_section(out: &mut Vec<u8>, records: &[[u8; 4]]) {
+    let encoded = encode_records(records);
+    out.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+    out.extend_from_slice(&encoded);
+}
+
+fn read_section(reader: &mut Reader, count: usize) -> io::Result<Vec<[u8; 4]>> {
+    let len = reader.u32()? as usize;
+    decode_records(reader.take(len)?, count)
+}
+
+// 游程编码，撤销历史里的增量也用它压缩
+pub(super) fn encode_records(records: &[[u8; 4]]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut iter = records.iter().peekable();
+    while let Some(record) = iter.next() {
+        let mut run: u16 = 1;
+        while run < u16::MAX && iter.peek() == Some(&record) {
+            iter.next();
+            run += 1;
+        }
+        encoded.extend_from_slice(&run.to_le_bytes());
+        encoded.extend_from_slice(record);
+    }
+    encoded
+}
+
+// count 来自文件头，不可信：每 6 字节最多展开成 u16::MAX 条记录，先按段长检查，容量也不超过段能表示的数量
+pub(super) fn decode_records(bytes: &[u8], count: usize) -> io::Result<Vec<[u8; 4]>> {
+    let max_records = bytes.len() / 6 * u16::MAX as usize;
+    if count > max_records {
+        return Err(invalid("section does not cover the grid"));
+    }
+    let mut section = Reader { bytes, pos: 0 };
+    let mut records = Vec::with_capacity(count);
+    while section.pos < section.bytes.len() {
+        let run = section.u16()? as usize;
+        let record: [u8; 4] = section.take(4)?.try_into().unwrap();
+        if run == 0 || records.len() + run > count {
+            return Err(invalid("corrupt run length"));
+        }
+        records.extend(std::iter::repeat(record).take(run));
+    }
+    if records.len() != count {
+        return Err(invalid("section does not cover the grid"));
+    }
+    Ok(records)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len());
+        let Some(end) = end else {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "truncated snapshot"));
+        };
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
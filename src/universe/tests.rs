@@ -0,0 +1,817 @@
+use super::scenario::Scenario;
+use super::{render_cells_rgba, Cell, CellGrid, Edit, CALM_WIND, WIND_ZERO, RenderParams, ReplayLog, SandApi, Species, SpeciesDef, TexelRect, UnknownSpecies, Wind, SLEEP_AFTER};
+
+// 物质规则的回归测试，场景格式见 scenario.rs
+
+#[test]
+fn sand_falls_until_it_lands() {
+    let mut scenario = Scenario::parse(
+        "
+        .S.
+        ...
+        ...
+        ###
+        ",
+    );
+    scenario.run(10);
+    scenario.assert_map(
+        "
+        ...
+        ...
+        .S.
+        ###
+        ",
+    );
+}
+
+#[test]
+fn sand_sinks_through_water() {
+    let mut scenario = Scenario::parse(
+        "
+        #S#
+        #W#
+        #W#
+        ###
+        ",
+    );
+    scenario.run(10);
+    scenario.assert_map(
+        "
+        #W#
+        #W#
+        #S#
+        ###
+        ",
+    );
+}
+
+#[test]
+fn sand_piles_into_a_slope() {
+    let mut scenario = Scenario::parse(
+        "
+        ..SSS..
+        .......
+        .......
+        #######
+        ",
+    );
+    scenario.run(20);
+    scenario.assert_counts(&[(Species::Sand, 3)]);
+    // 三粒沙子不会叠成一柱，至少有两粒落在地面上
+    let grounded = (0..7).filter(|x| scenario.species_at(*x, 2) == Species::Sand).count();
+    assert!(grounded >= 2, "sand did not spread:\n{}", scenario.map());
+}
+
+#[test]
+fn water_levels_out() {
+    let mut scenario = Scenario::parse(
+        "
+        #.....#
+        #..W..#
+        #..W..#
+        #..W..#
+        #######
+        ",
+    );
+    scenario.run(100);
+    scenario.assert_counts(&[(Species::Water, 3)]);
+    for x in 1..6 {
+        for y in 0..3 {
+            assert_ne!(scenario.species_at(x, y), Species::Water, "water did not level:\n{}", scenario.map());
+        }
+    }
+}
+
+#[test]
+fn fire_ignites_wood() {
+    let mut scenario = Scenario::parse(
+        "
+        #####
+        #TTT#
+        #TFT#
+        #TTT#
+        #####
+        ",
+    );
+    scenario.run(20);
+    // 着火的木头 rb 开始倒计时
+    let burning = scenario
+        .grid()
+        .cells
+        .iter()
+        .filter(|cell| cell.species == Species::Wood && cell.rb > 0)
+        .count();
+    assert!(burning > 0, "no wood caught fire:\n{}", scenario.map());
+
+    scenario.run(400);
+    assert!(scenario.count(Species::Wood) < 8, "wood did not burn away:\n{}", scenario.map());
+}
+
+#[test]
+fn water_puts_out_fire() {
+    let mut scenario = Scenario::parse(
+        "
+        #####
+        #WWW#
+        #WFW#
+        #WWW#
+        #####
+        ",
+    );
+    scenario.run(100);
+    scenario.assert_counts(&[(Species::Fire, 0), (Species::Water, 8)]);
+}
+
+#[test]
+fn acid_dissolves_what_it_lands_on() {
+    let mut scenario = Scenario::parse(
+        "
+        #A#
+        #R#
+        #R#
+        ###
+        ",
+    );
+    scenario.run(20);
+    assert!(scenario.count(Species::Stone) < 2, "acid did not dissolve stone:\n{}", scenario.map());
+    scenario.assert_counts(&[(Species::Wall, 9)]);
+}
+
+#[test]
+fn acid_does_not_dissolve_walls() {
+    // Sandspiel 里墙是不可破坏的，酸被墙围住时只会停在原地
+    let mut scenario = Scenario::parse(
+        "
+        #A#
+        ###
+        ",
+    );
+    scenario.run(50);
+    scenario.assert_map(
+        "
+        #A#
+        ###
+        ",
+    );
+}
+
+#[test]
+fn lava_turns_water_into_stone() {
+    let mut scenario = Scenario::parse(
+        "
+        #L#
+        #W#
+        ###
+        ",
+    );
+    scenario.run(200);
+    // 水在 100 度停住吸收潜热，还没攒够汽化的热量岩浆就已经凝固；石头比水重，沉到下面
+    scenario.assert_map(
+        "
+        #W#
+        #R#
+        ###
+        ",
+    );
+}
+
+#[test]
+fn lava_melts_ice() {
+    let mut scenario = Scenario::parse(
+        "
+        #I#
+        #L#
+        ###
+        ",
+    );
+    scenario.run(300);
+    // 冰先化成水，水再把岩浆冷却成石头，水和上一个场景一样来不及汽化
+    scenario.assert_map(
+        "
+        #W#
+        #R#
+        ###
+        ",
+    );
+}
+
+#[test]
+fn lava_bakes_sand_into_glass() {
+    let mut scenario = Scenario::parse(
+        "
+        #####
+        #LLL#
+        #LSL#
+        #LLL#
+        #####
+        ",
+    );
+    scenario.run(60);
+    scenario.assert_counts(&[(Species::Sand, 0), (Species::Glass, 1)]);
+}
+
+#[test]
+fn ice_freezes_the_water_it_surrounds() {
+    let mut scenario = Scenario::parse(
+        "
+        #####
+        #III#
+        #IWI#
+        #III#
+        #####
+        ",
+    );
+    scenario.run(60);
+    scenario.assert_counts(&[(Species::Water, 0), (Species::Ice, 9)]);
+}
+
+#[test]
+fn water_boils_and_freezes_at_its_thresholds() {
+    let mut scenario = Scenario::parse(
+        "
+        #W#W#
+        #####
+        ",
+    );
+    scenario.grid().set_temperature(1, 0, 1000.0);
+    scenario.grid().set_temperature(3, 0, -200.0);
+    scenario.run(1);
+    assert_eq!(scenario.species_at(1, 0), Species::Gas, "{}", scenario.map());
+    assert_eq!(scenario.species_at(3, 0), Species::Ice, "{}", scenario.map());
+}
+
+#[test]
+fn wind_carries_heat_downwind() {
+    let mut scenario = Scenario::parse(
+        "
+        .........
+        .........
+        .........
+        ",
+    );
+    // 向右每代一格
+    scenario.set_winds(Wind { dx: 190, dy: WIND_ZERO, pressure: 0, density: 0 });
+    scenario.grid().set_temperature(2, 1, 500.0);
+    scenario.run(2);
+    let grid = scenario.grid();
+    let hottest = (0..9).max_by(|&a, &b| grid.temperature(a, 1).total_cmp(&grid.temperature(b, 1)));
+    assert_eq!(hottest, Some(4));
+}
+
+#[test]
+fn dust_explodes_under_pressure() {
+    let mut scenario = Scenario::parse(
+        "
+        ...
+        .D.
+        ###
+        ",
+    );
+    scenario.set_winds(Wind {
+        dx: WIND_ZERO,
+        dy: WIND_ZERO,
+        pressure: 200,
+        density: 0,
+    });
+    scenario.run(1);
+    scenario.assert_counts(&[(Species::Dust, 0), (Species::Fire, 1)]);
+}
+
+#[test]
+fn wind_blows_sand_but_not_walls() {
+    // winds 的 dx 通道对应 +x 方向，超过物质的阈值才会吹动
+    let mut scenario = Scenario::parse(
+        "
+        S.....
+        #.....
+        ######
+        ",
+    );
+    scenario.set_winds(Wind {
+        dx: 200,
+        dy: WIND_ZERO,
+        pressure: 0,
+        density: 0,
+    });
+    scenario.run(1);
+    assert_eq!(scenario.species_at(0, 0), Species::Empty, "sand was not blown:\n{}", scenario.map());
+    scenario.assert_counts(&[(Species::Sand, 1), (Species::Wall, 7)]);
+    assert_eq!(scenario.species_at(0, 1), Species::Wall);
+}
+
+#[test]
+fn calm_wind_leaves_sand_alone() {
+    let mut scenario = Scenario::parse(
+        "
+        .S.
+        ###
+        ",
+    );
+    scenario.run(10);
+    scenario.assert_map(
+        "
+        .S.
+        ###
+        ",
+    );
+}
+
+#[test]
+fn new_grid_starts_with_calm_winds() {
+    // 不经过场景、也没有流体求解器的网格（无窗口模式）：沙子不被吹走，块照常休眠
+    let mut grid = CellGrid::new(64, 64);
+    assert!(grid.winds.iter().all(|&wind| wind == CALM_WIND));
+    grid.paint(20, 63, 1, Species::Sand);
+    for _ in 0..SLEEP_AFTER * 2 {
+        grid.tick();
+    }
+    assert_eq!(grid.get_cell(20, 63).species, Species::Sand);
+    assert_eq!(grid.awake_chunks(), 0);
+}
+
+#[test]
+fn same_seed_gives_the_same_world() {
+    let map = "
+        ..SSSS..
+        ..WWWW..
+        ...FF...
+        .TTTTTT.
+        ########
+    ";
+    let mut a = Scenario::with_seed(map, 7);
+    let mut b = Scenario::with_seed(map, 7);
+    a.run(50);
+    b.run(50);
+    assert_eq!(a.map(), b.map());
+    assert_eq!(a.grid().checksum(), b.grid().checksum());
+}
+
+#[test]
+fn unregistered_species_stay_inert() {
+    let mut scenario = Scenario::parse(
+        "
+        ...
+        ...
+        ###
+        ",
+    );
+    let grid = scenario.grid();
+    let idx = grid.get_index(1, 0);
+    grid.cells[idx].species = Species(25);
+    scenario.run(10);
+    assert_eq!(scenario.species_at(1, 0), Species(25));
+}
+
+#[test]
+fn registered_species_use_their_update_function() {
+    let mut scenario = Scenario::parse(
+        "
+        ...
+        ...
+        ###
+        ",
+    );
+    let heavy = Species(25);
+    let grid = scenario.grid();
+    let sand = *grid.registry().get(Species::Sand).unwrap();
+    grid.registry_mut().register(SpeciesDef {
+        species: heavy,
+        name: "Heavy",
+        ..sand
+    });
+    let idx = grid.get_index(1, 0);
+    grid.cells[idx].species = heavy;
+    scenario.run(10);
+    assert_eq!(scenario.species_at(1, 1), heavy, "custom species did not fall:\n{}", scenario.map());
+}
+
+// 第三方物质：往下方的空格里长
+fn grow_down(cell: Cell, mut api: SandApi) {
+    if api.get(0, 1).species == Species::Empty {
+        api.set(0, 1, cell);
+    }
+}
+
+#[test]
+fn registered_species_reach_tick_palette_and_solid_mask() {
+    let mut scenario = Scenario::parse(
+        "
+        ...
+        ...
+        ...
+        ###
+        ",
+    );
+    let vine = Species(26);
+    let registry = scenario.grid().registry();
+    let palette = registry.palette();
+    let solid_mask = registry.solid_mask();
+    assert_eq!(solid_mask & 1 << vine.index(), 0);
+
+    let mut def = SpeciesDef::new(vine, "Vine", grow_down);
+    def.solid = true;
+    def.colour.hue = [0.3, 0.0, 0.0, 0.0];
+    scenario.grid().registry_mut().register(def);
+    let registry = scenario.grid().registry();
+    assert!(registry.contains(vine));
+    assert_eq!(registry.name(vine), "Vine");
+    assert_eq!(registry.solid_mask(), solid_mask | 1 << vine.index());
+    let entry = vine.index() * 4..vine.index() * 4 + 4;
+    assert_eq!(registry.palette()[entry.clone()], def.colour.packed());
+    assert_ne!(registry.palette()[entry.clone()], palette[entry]);
+
+    let grid = scenario.grid();
+    let idx = grid.get_index(1, 0);
+    grid.cells[idx].species = vine;
+    scenario.run(5);
+    for y in 0..3 {
+        assert_eq!(scenario.species_at(1, y), vine, "vine did not grow:\n{}", scenario.map());
+    }
+}
+
+#[test]
+fn tick_is_independent_of_thread_count() {
+    // 比两个块大，四个相位都有块可以并行
+    let mut map = String::new();
+    for y in 0..80 {
+        let row: String = (0..100)
+            .map(|x| match (x + y * 7) % 11 {
+                0 => 'S',
+                1 | 2 => 'W',
+                3 => 'T',
+                4 => 'F',
+                5 => 'O',
+                _ => '.',
+            })
+            .collect();
+        map.push_str(&row);
+        map.push('\n');
+    }
+    let mut single = Scenario::parse(&map);
+    single.grid().set_tick_threads(1);
+    let mut many = Scenario::parse(&map);
+    many.grid().set_tick_threads(8);
+    single.run(30);
+    many.run(30);
+    assert_eq!(single.grid().checksum(), many.grid().checksum());
+}
+
+fn settled_map() -> String {
+    // 三个块宽、两个块高，底部是静止的沙子
+    let mut map = String::new();
+    for y in 0..64 {
+        let glyph = match y {
+            63 => '#',
+            60..=62 => 'S',
+            _ => '.',
+        };
+        map.push_str(&glyph.to_string().repeat(96));
+        map.push('\n');
+    }
+    map
+}
+
+#[test]
+fn settled_chunks_fall_asleep() {
+    let mut scenario = Scenario::parse(&settled_map());
+    scenario.run(SLEEP_AFTER as usize + 2);
+    assert_eq!(scenario.grid().awake_chunks(), 0);
+}
+
+#[test]
+fn cold_settled_grid_falls_asleep() {
+    // 埋在沙子里的冰是冷源，周围每代的温度变化低于 HEAT_WAKE 时块照样休眠，休眠的块里温度不再推进
+    let mut rows: Vec<String> = settled_map().lines().map(str::to_string).collect();
+    rows[61].replace_range(40..42, "II");
+    let mut scenario = Scenario::parse(&rows.join("\n"));
+    scenario.run(SLEEP_AFTER as usize + 2);
+    assert_eq!(scenario.grid().awake_chunks(), 0);
+    assert_eq!(scenario.count(Species::Ice), 2);
+
+    let far = scenario.grid().get_index(80, 10);
+    scenario.grid().heat.temperature[far] = 500.0;
+    scenario.run(SLEEP_AFTER as usize);
+    assert_eq!(scenario.grid().temperature(80, 10), 500.0);
+    assert_eq!(scenario.grid().awake_chunks(), 0);
+}
+
+#[test]
+fn snapshot_resumes_sleeping_chunks_identically() {
+    // 存档时块已经休眠，读档后继续 tick 要和原来的网格逐位一致（休眠的块不更新 clock，也不推进温度）
+    let mut rows: Vec<String> = settled_map().lines().map(str::to_string).collect();
+    rows[61].replace_range(40..42, "II");
+    let mut scenario = Scenario::parse(&rows.join("\n"));
+    scenario.run(SLEEP_AFTER as usize + 2);
+    let original = scenario.grid();
+    let mut restored = CellGrid::from_bytes(&original.to_bytes()).unwrap();
+    assert_eq!(restored.awake_chunks(), 0);
+    assert_eq!(restored.ticks(), original.ticks());
+    for _ in 0..SLEEP_AFTER * 2 {
+        original.tick();
+        restored.tick();
+        assert_eq!(restored.checksum(), original.checksum());
+        assert_eq!(restored.heat.temperature, original.heat.temperature);
+    }
+}
+
+#[test]
+fn snapshot_file_resumes_a_busy_grid_bit_identically() {
+    // 清醒的块、风、热源和相变都在进行中时存档到文件，读档后每一步的存档字节都和原来的网格相同
+    let mut grid = CellGrid::new(96, 96);
+    grid.paint(20, 80, 8, Species::Sand);
+    grid.paint(50, 80, 8, Species::Water);
+    grid.paint(70, 20, 6, Species::Wood);
+    grid.paint(70, 12, 2, Species::Fire);
+    grid.paint(30, 20, 5, Species::Lava);
+    grid.paint(50, 50, 6, Species::Gas);
+    for wind in grid.winds.iter_mut().step_by(7) {
+        *wind = Wind { dx: 255, dy: 0, ..CALM_WIND };
+    }
+    grid.refresh_windy_chunks();
+    for _ in 0..20 {
+        grid.tick();
+    }
+
+    let path = std::env::temp_dir().join(format!("sand-snapshot-test-{}.sndg", std::process::id()));
+    grid.save(&path).unwrap();
+    let loaded = CellGrid::load(&path);
+    let _ = std::fs::remove_file(&path);
+    let mut loaded = loaded.unwrap();
+    assert_eq!(loaded.to_bytes(), grid.to_bytes());
+    for _ in 0..60 {
+        grid.tick();
+        loaded.tick();
+        assert_eq!(loaded.to_bytes(), grid.to_bytes());
+    }
+}
+
+#[test]
+fn snapshot_rejects_a_size_its_sections_cannot_cover() {
+    // 文件头声称 60000x60000，cells 段只有一条记录：读档要报错，不能先按头里的大小分配
+    let mut bytes = CellGrid::new(8, 8).to_bytes();
+    bytes[6..10].copy_from_slice(&60000u32.to_le_bytes());
+    bytes[10..14].copy_from_slice(&60000u32.to_le_bytes());
+    let error = CellGrid::from_bytes(&bytes).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn replay_repeats_strokes_undo_and_redo() {
+    // 撤销以笔画为单位，只重放 paint 的话撤销会落到别的笔画上
+    let mut grid = CellGrid::new(64, 64);
+    grid.start_recording();
+    grid.paint(10, 60, 6, Species::Wall);
+    grid.begin_stroke();
+    grid.paint(20, 10, 4, Species::Sand);
+    grid.paint(24, 10, 4, Species::Sand);
+    grid.end_stroke();
+    grid.tick();
+    grid.begin_stroke();
+    grid.paint(40, 10, 4, Species::Water);
+    assert!(grid.undo());
+    grid.tick();
+    assert!(grid.redo());
+    for _ in 0..5 {
+        grid.tick();
+    }
+    assert!(grid.undo());
+    assert!(grid.undo());
+    for _ in 0..5 {
+        grid.tick();
+    }
+    let mut log = grid.take_recording().unwrap();
+    log.fluid_config = Some("pressure_solver = \"multigrid\"\nmultigrid_cycles = 3\n".to_string());
+
+    let path = std::env::temp_dir().join(format!("sand-replay-test-{}.replay", std::process::id()));
+    log.save(&path).unwrap();
+    let loaded = ReplayLog::load(&path);
+    let _ = std::fs::remove_file(&path);
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.edits, log.edits);
+    assert_eq!(loaded.fluid_config, log.fluid_config);
+    let replayed = loaded.replay(CellGrid::new(64, 64), |_| {}).unwrap();
+    assert_eq!(replayed.checksum(), grid.checksum());
+
+    let mut paints_only = loaded.clone();
+    paints_only.edits.retain(|e| matches!(e.edit, Edit::Paint { .. }));
+    assert!(paints_only.replay(CellGrid::new(64, 64), |_| {}).is_err());
+}
+
+#[test]
+fn undo_restores_the_cells_a_stroke_painted_over() {
+    let mut grid = CellGrid::new(64, 64);
+    grid.paint(20, 20, 10, Species::Water);
+    grid.paint(30, 20, 6, Species::Wall);
+    let before = grid.cells.clone();
+    // 擦掉一部分已有的细胞，再在空出来和原本空着的地方画沙子
+    grid.begin_stroke();
+    grid.paint(25, 20, 8, Species::Empty);
+    grid.paint(25, 24, 12, Species::Sand);
+    grid.end_stroke();
+    let after = grid.cells.clone();
+    assert_ne!(after, before);
+
+    assert!(grid.undo());
+    assert_eq!(grid.cells, before);
+    assert!(grid.redo());
+    assert_eq!(grid.cells, after);
+}
+
+#[test]
+fn a_new_stroke_clears_the_redo_stack() {
+    let mut grid = CellGrid::new(64, 64);
+    for x in [10, 30] {
+        grid.begin_stroke();
+        grid.paint(x, 10, 4, Species::Sand);
+        grid.end_stroke();
+    }
+    assert!(grid.undo());
+    assert_eq!((grid.undo_depth(), grid.redo_depth()), (1, 1));
+
+    grid.begin_stroke();
+    grid.paint(50, 10, 4, Species::Water);
+    grid.end_stroke();
+    assert_eq!((grid.undo_depth(), grid.redo_depth()), (2, 0));
+    assert!(!grid.redo());
+}
+
+#[test]
+fn history_budget_drops_the_oldest_strokes() {
+    let mut grid = CellGrid::new(64, 64);
+    let mut sizes = Vec::new();
+    let mut first_two = Vec::new();
+    for (i, size) in [4, 8, 12, 6].into_iter().enumerate() {
+        let used = grid.history_bytes();
+        grid.begin_stroke();
+        grid.paint(8 + i as i32 * 16, 20, size, Species::Sand);
+        grid.end_stroke();
+        sizes.push(grid.history_bytes() - used);
+        if i == 1 {
+            first_two = grid.cells.clone();
+        }
+    }
+    assert!(sizes.iter().all(|&bytes| bytes > 0));
+    assert_eq!(grid.history_bytes(), sizes.iter().sum::<usize>());
+
+    // 撤销、重做只是在两个栈之间移动，占用不变
+    assert!(grid.undo());
+    assert_eq!(grid.history_bytes(), sizes.iter().sum::<usize>());
+    assert!(grid.redo());
+
+    // 只够放最新的两笔
+    grid.set_history_budget(sizes[2] + sizes[3]);
+    assert_eq!(grid.undo_depth(), 2);
+    assert_eq!(grid.history_bytes(), sizes[2] + sizes[3]);
+    assert!(grid.undo());
+    assert!(grid.undo());
+    assert!(!grid.undo());
+    // 第一、二笔留在网格上，撤销不到
+    assert_eq!(grid.cells, first_two);
+
+    grid.set_history_budget(0);
+    assert_eq!((grid.undo_depth(), grid.redo_depth(), grid.history_bytes()), (0, 0, 0));
+}
+
+#[test]
+fn png_round_trips_the_cells() {
+    let mut grid = CellGrid::new(48, 32);
+    grid.paint(10, 28, 8, Species::Wall);
+    grid.paint(20, 10, 8, Species::Sand);
+    grid.paint(34, 10, 8, Species::Water);
+    for _ in 0..5 {
+        grid.tick();
+    }
+    let path = std::env::temp_dir().join(format!("sand-png-test-{}.png", std::process::id()));
+    grid.to_png(&path).unwrap();
+    let loaded = CellGrid::from_png(&path, UnknownSpecies::Reject);
+    let _ = std::fs::remove_file(&path);
+    let loaded = loaded.unwrap();
+    assert_eq!((loaded.width, loaded.height), (grid.width, grid.height));
+    assert_eq!(loaded.cells, grid.cells);
+}
+
+#[test]
+fn png_with_an_unknown_species_is_rejected_or_emptied() {
+    let mut grid = CellGrid::new(16, 16);
+    grid.paint(8, 8, 4, Species::Sand);
+    let idx = grid.get_index(3, 5);
+    grid.cells[idx].species = Species(25);
+    let path = std::env::temp_dir().join(format!("sand-png-unknown-{}.png", std::process::id()));
+    grid.to_png(&path).unwrap();
+    let rejected = CellGrid::from_png(&path, UnknownSpecies::Reject);
+    let emptied = CellGrid::from_png(&path, UnknownSpecies::Empty);
+    let _ = std::fs::remove_file(&path);
+
+    let error = rejected.err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("(3, 5)"), "{}", error);
+    let emptied = emptied.unwrap();
+    assert_eq!(emptied.cells[idx].species, Species::Empty);
+    grid.cells[idx].species = Species::Empty;
+    let species = |grid: &CellGrid| grid.cells.iter().map(|c| c.species).collect::<Vec<_>>();
+    assert_eq!(species(&emptied), species(&grid));
+}
+
+#[test]
+fn paint_wakes_a_sleeping_chunk() {
+    let mut scenario = Scenario::parse(&settled_map());
+    scenario.run(SLEEP_AFTER as usize + 2);
+    scenario.grid().paint(40, 10, 1, Species::Sand);
+    assert!(scenario.grid().awake_chunks() > 0);
+    scenario.run(60);
+    assert_eq!(scenario.species_at(40, 10), Species::Empty, "painted sand did not fall:\n{}", scenario.map());
+    scenario.assert_counts(&[(Species::Sand, 96 * 3 + 1)]);
+}
+
+#[test]
+fn written_winds_wake_only_while_they_blow() {
+    // 风在 set_winds 时按块标记，之后每代都会唤醒有风的块；风停了块照样休眠
+    let mut scenario = Scenario::parse(&settled_map());
+    scenario.run(SLEEP_AFTER as usize + 2);
+    let grid = scenario.grid();
+    let mut winds = vec![CALM_WIND; grid.winds.len()];
+    winds[grid.get_index(40, 10)] = Wind { dx: 255, ..CALM_WIND };
+    grid.set_winds(&winds);
+    scenario.run(SLEEP_AFTER as usize + 2);
+    assert!(scenario.grid().awake_chunks() > 0);
+
+    scenario.grid().set_winds(&vec![CALM_WIND; winds.len()]);
+    scenario.run(SLEEP_AFTER as usize + 2);
+    assert_eq!(scenario.grid().awake_chunks(), 0);
+}
+
+#[test]
+fn restless_species_keep_their_chunk_awake() {
+    // 岩浆原地不动，但每代都会随机检查邻居
+    let mut scenario = Scenario::parse(
+        "
+        #L#
+        ###
+        ",
+    );
+    scenario.run(SLEEP_AFTER as usize * 4);
+    assert_eq!(scenario.grid().awake_chunks(), 1);
+}
+
+#[test]
+fn dirty_rects_cover_only_changed_chunks() {
+    let mut scenario = Scenario::parse(&settled_map());
+    scenario.run(SLEEP_AFTER as usize + 2);
+    scenario.grid().take_dirty_rects();
+    scenario.grid().paint(40, 10, 1, Species::Sand);
+    let rects = scenario.grid().take_dirty_rects();
+    let texels: u32 = rects.iter().map(|r| r.width * r.height).sum();
+    assert!(texels > 0 && texels < 96 * 64, "{:?}", rects);
+    assert!(scenario.grid().take_dirty_rects().is_empty());
+}
+
+#[test]
+fn texels_use_cell_coordinates() {
+    // 纹理和网格共用同一套坐标，脏区域就是被改动的块本身
+    let mut scenario = Scenario::parse(&settled_map());
+    scenario.run(SLEEP_AFTER as usize + 2);
+    scenario.grid().take_dirty_rects();
+    scenario.grid().paint(40, 10, 1, Species::Sand);
+    let rects = scenario.grid().take_dirty_rects();
+    assert_eq!(rects, vec![TexelRect { x: 32, y: 0, width: 32, height: 32 }]);
+    let row = scenario.grid().cells_rgba_rect(TexelRect { x: 40, y: 10, width: 1, height: 1 });
+    assert_eq!(row[0], Species::Sand.id());
+}
+
+#[test]
+fn snapshot_render_paints_the_background_white() {
+    let mut scenario = Scenario::parse(
+        "
+        ...
+        .#.
+        ",
+    );
+    let live = render_cells_rgba(scenario.grid(), RenderParams::default());
+    let snapshot = render_cells_rgba(scenario.grid(), RenderParams::snapshot());
+    // 背景平时几乎透明，截图时是不透明的近白色
+    assert_eq!(live.pixel(0, 0)[3], 26);
+    let [r, g, b, a] = snapshot.pixel(0, 0);
+    assert_eq!(a, 255);
+    assert!(r > 240 && g > 240 && b > 240, "{:?}", snapshot.pixel(0, 0));
+    // 墙不受截图模式影响
+    assert_eq!(live.pixel(1, 1), snapshot.pixel(1, 1));
+    assert_eq!(live.pixel(1, 1)[3], 153);
+}
+
+#[test]
+fn render_follows_the_registry_palette() {
+    let mut scenario = Scenario::parse(
+        "
+        SW
+        ",
+    );
+    let before = render_cells_rgba(scenario.grid(), RenderParams::default());
+    let mut sand = *scenario.grid().registry().get(Species::Sand).unwrap();
+    sand.colour.lightness = [0.0, 0.0, 0.0, 0.0];
+    scenario.grid().registry_mut().register(sand);
+    let after = render_cells_rgba(scenario.grid(), RenderParams::default());
+    assert_eq!(&after.pixel(0, 0)[..3], &[0, 0, 0]);
+    assert_ne!(before.pixel(0, 0), after.pixel(0, 0));
+    assert_eq!(before.pixel(1, 0), after.pixel(1, 0));
+}
+
@@ -3,22 +3,16 @@ use bevy::prelude::*;
 use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
 use bevy::render::render_asset::RenderAssets;
 use bevy::render::{render_graph, Render, RenderApp, RenderSet};
-use bevy::render::render_graph::{NodeRunError, RenderGraph, RenderGraphContext, RenderLabel};
+use bevy::render::render_graph::{NodeRunError, RenderGraphContext, RenderLabel};
 use bevy::render::render_resource::*;
-use bevy::render::render_resource::binding_types::{sampler, texture_2d, texture_storage_2d, uniform_buffer};
+use bevy::render::render_resource::binding_types::{sampler, texture_2d, texture_storage_2d};
 use bevy::render::renderer::{RenderContext, RenderDevice};
-use crate::{FluidConfig, HEIGHT, WIDTH, WORKGROUP_SIZE};
-
-// 速度场修正所需的uniform数据
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable,ShaderType)]
-struct VelocityOutUniforms {
-    min_value: f32,
-    max_value: f32,
-    scale_factor: f32,
-    _padding: f32,
-    offset: [f32; 2],
-}
+use crate::advection::{DensityAdvectionImage, VelocityAdvectionImage};
+use crate::{FluidConfig, SimulationSize};
+
+// 把最终的速度和压力编码成 Wind 的字节布局（Rgba8Unorm），读回后直接写入 CellGrid::winds
+// 范围限制、缩放和偏移是 wind.wgsl 里的常量，与 FluidSolver::write_winds 使用的 encode_wind 相同
+// 这是一帧里最后一个计算节点，编码之后把 velocity.1、density.1 复制回 .0，下一帧的平流从这一帧的结果开始
 
 // 存储速度场修正管线的资源
 #[derive(Resource)]
@@ -42,14 +36,13 @@ impl FromWorld for VelocityOutPipeline {
                     texture_storage_2d(TextureFormat::Rgba8Unorm, StorageTextureAccess::WriteOnly),
                     sampler(SamplerBindingType::Filtering),
                     sampler(SamplerBindingType::Filtering),
-                    uniform_buffer::<VelocityOutUniforms>(false)
                 )
             )
         );
 
         let shader = world
             .resource::<AssetServer>()
-            .load("velocity_Out.wgsl");
+            .load("velocity_out.wgsl");
 
         let pipeline_cache = world.resource::<PipelineCache>();
 
@@ -69,15 +62,10 @@ impl FromWorld for VelocityOutPipeline {
     }
 }
 
-#[derive(Resource, Clone, ExtractResource, AsBindGroup)]
+#[derive(Resource, Clone, ExtractResource)]
 pub struct VelocityOutImage {
-    #[texture(0, visibility(compute))]
-    #[sampler(3)]
     pub(crate) velocity_tex: Handle<Image>,
-    #[texture(1, visibility(compute))]
-    #[sampler(4)]
     pub(crate) pressure_tex: Handle<Image>,
-    #[storage_texture(2, image_format = Rgba8Unorm, access = WriteOnly)]
     pub(crate) output_tex: Handle<Image>,
 }
 
@@ -87,14 +75,14 @@ pub struct VelocityOutBindGroup(pub(crate) BindGroup);
 fn prepare_bind_group(
     mut commands: Commands,
     gpu_images: Res<RenderAssets<Image>>,
-    velocity_Out_image: Res<VelocityOutImage>,
+    velocity_out_image: Res<VelocityOutImage>,
     render_device: Res<RenderDevice>,
-    velocity_Out_pipeline: Res<VelocityOutPipeline>,
-    fluid_config: Res<FluidConfig>,
+    velocity_out_pipeline: Res<VelocityOutPipeline>,
+    _fluid_config: Res<FluidConfig>,
 ) {
-    let velocity_tex_view = gpu_images.get(&velocity_Out_image.velocity_tex).unwrap();
-    let pressure_tex_view = gpu_images.get(&velocity_Out_image.pressure_tex).unwrap();
-    let output_tex_view = gpu_images.get(&velocity_Out_image.output_tex).unwrap();
+    let velocity_tex_view = gpu_images.get(&velocity_out_image.velocity_tex).unwrap();
+    let pressure_tex_view = gpu_images.get(&velocity_out_image.pressure_tex).unwrap();
+    let output_tex_view = gpu_images.get(&velocity_out_image.output_tex).unwrap();
 
     let velocity_sampler = render_device.create_sampler(&SamplerDescriptor {
         address_mode_u: AddressMode::ClampToEdge,
@@ -110,23 +98,9 @@ fn prepare_bind_group(
         min_filter: FilterMode::Linear,
         ..Default::default()
     });
-    let uniforms = VelocityOutUniforms {
-        min_value: -250.0,    // 速度最小值
-        max_value: 250.0,     // 速度最大值
-        scale_factor: 500.0,  // 缩放因子
-        _padding: 0.,
-        offset: [0.5, 0.5],   // 偏移量
-    };
-
-    let uniform_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-        label: Some("velocity_Out_uniform_buffer"),
-        contents: bytemuck::cast_slice(&[uniforms]),
-        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-    });
-
     let bind_group = render_device.create_bind_group(
-        "velocity_Out_bind_group",
-        &velocity_Out_pipeline.bind_group_layout,
+        "velocity_out_bind_group",
+        &velocity_out_pipeline.bind_group_layout,
         &BindGroupEntries::sequential
             (
                 (
@@ -135,11 +109,6 @@ fn prepare_bind_group(
                     &output_tex_view.texture_view,
                     &velocity_sampler,
                     &pressure_sampler,
-                    BindingResource::Buffer(BufferBinding {
-                        buffer: &uniform_buffer,
-                        offset: 0,
-                        size: None,
-                    })
                 )
             )
     );
@@ -153,13 +122,13 @@ pub(crate) struct VelocityOutComputeNode;
 impl render_graph::Node for VelocityOutComputeNode {
     fn run(
         &self,
-        graph: &mut RenderGraphContext,
+        _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
         let pipeline_cache = world.resource::<PipelineCache>();
-        let velocity_Out_pipeline = world.resource::<VelocityOutPipeline>();
-        let velocity_Out_bind_group = world.resource::<VelocityOutBindGroup>();
+        let velocity_out_pipeline = world.resource::<VelocityOutPipeline>();
+        let velocity_out_bind_group = world.resource::<VelocityOutBindGroup>();
         // println!("Velocity Out  Compute Pass");
         let mut pass = render_context
             .command_encoder()
@@ -168,10 +137,27 @@ impl render_graph::Node for VelocityOutComputeNode {
                 ..default()
             });
 
-        if let Some(pipeline) = pipeline_cache.get_compute_pipeline(velocity_Out_pipeline.pipeline) {
+        if let Some(pipeline) = pipeline_cache.get_compute_pipeline(velocity_out_pipeline.pipeline) {
             pass.set_pipeline(pipeline);
-            pass.set_bind_group(0, &velocity_Out_bind_group.0, &[]);
-            pass.dispatch_workgroups(WIDTH / WORKGROUP_SIZE, HEIGHT / WORKGROUP_SIZE, 1);
+            pass.set_bind_group(0, &velocity_out_bind_group.0, &[]);
+            let (workgroups_x, workgroups_y) = world.resource::<SimulationSize>().workgroups();
+            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
+        }
+        drop(pass);
+
+        // 与 PressureComputeNode 相同，用纹理复制代替交换句柄：各个 *Image 资源只在 insert_pass_images 里建一次
+        let gpu_images = world.resource::<RenderAssets<Image>>();
+        let velocity = world.resource::<VelocityAdvectionImage>();
+        let density = world.resource::<DensityAdvectionImage>();
+        let size = world.resource::<SimulationSize>().extent();
+        let encoder = render_context.command_encoder();
+        for (from, to) in [
+            (&velocity.output_tex, &velocity.velocity_tex),
+            (&density.output_tex, &density.density_tex),
+        ] {
+            if let (Some(from), Some(to)) = (gpu_images.get(from), gpu_images.get(to)) {
+                encoder.copy_texture_to_texture(from.texture.as_image_copy(), to.texture.as_image_copy(), size);
+            }
         }
 
         Ok(())
@@ -3,13 +3,12 @@ use bevy::prelude::*;
 use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
 use bevy::render::render_asset::RenderAssets;
 use bevy::render::{render_graph, Render, RenderApp, RenderSet};
-use bevy::render::render_graph::{NodeRunError, RenderGraph, RenderGraphContext, RenderLabel};
+use bevy::render::render_graph::{NodeRunError, RenderGraphContext, RenderLabel};
 use bevy::render::render_resource::*;
 use bevy::render::render_resource::binding_types::{sampler, texture_2d, texture_storage_2d, uniform_buffer};
 use bevy::render::renderer::{RenderContext, RenderDevice};
 use bytemuck::{Pod, Zeroable};
-use crate::{FluidConfig, FluidTextures, HEIGHT, WIDTH, WORKGROUP_SIZE};
-use crate::advection::{ AdvectionPipeline};
+use crate::{FluidConfig, FluidFormats, SimulationSize};
 // ... 原有代码 ...
 
 pub struct VorticityPlugin;
@@ -17,7 +16,6 @@ pub struct VorticityPlugin;
 impl Plugin for VorticityPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(ExtractResourcePlugin::<VorticityImage>::default())
-            .add_systems(Update,swap_velocity_buffer)
         ;
 
         let render_app = app.sub_app_mut(RenderApp);
@@ -36,11 +34,6 @@ impl Plugin for VorticityPlugin {
     }
 }
 
-fn swap_velocity_buffer(mut fluid_textures: ResMut<FluidTextures>) {
-    let velocity = &mut fluid_textures.velocity;
-    std::mem::swap(&mut velocity.0, &mut velocity.1);
-
-}
 #[derive(Debug, Hash, PartialEq, Eq, Clone,RenderLabel)]
 pub(crate) struct VorticityLabel;
 
@@ -54,13 +47,10 @@ struct VorticityUniforms {
 }
 
 
-#[derive(Resource, Clone, ExtractResource, AsBindGroup)]
+#[derive(Resource, Clone, ExtractResource)]
 pub struct VorticityImage {
-    #[texture(0, visibility(compute))]
     pub(crate) velocity_tex: Handle<Image>,
-    #[texture(1, visibility(compute))]
     pub(crate) curl_tex: Handle<Image>,
-    #[storage_texture(2, image_format = Rgba8Unorm, access = WriteOnly)]
     pub(crate) output_tex: Handle<Image>,
 }
 
@@ -74,10 +64,9 @@ pub struct VorticityPipeline {
 pub struct VorticityBindGroup (pub(crate) BindGroup);
 impl FromWorld for VorticityPipeline {
     fn from_world(world: &mut World) -> Self {
-
-        println!("VorticityPipeline_from_world");
         let render_device = world.resource::<RenderDevice>();
         // let bind_group_layout = AdvectionImage::bind_group_layout(render_device);
+        let formats = *world.resource::<FluidFormats>();
         let bind_group_layout = render_device.create_bind_group_layout(
             "vorticity_bind_group_layout",
             &BindGroupLayoutEntries::sequential(
@@ -85,7 +74,7 @@ impl FromWorld for VorticityPipeline {
                 (
                     texture_2d(TextureSampleType::Float { filterable: true }),
                     texture_2d(TextureSampleType::Float { filterable: true }),
-                    texture_storage_2d(TextureFormat::Rgba8Unorm, StorageTextureAccess::WriteOnly),
+                    texture_storage_2d(formats.velocity, StorageTextureAccess::WriteOnly),
                     sampler(SamplerBindingType::Filtering),
                     sampler(SamplerBindingType::Filtering),
                     uniform_buffer::<VorticityUniforms>(false)
@@ -104,7 +93,7 @@ impl FromWorld for VorticityPipeline {
             layout: vec![bind_group_layout.clone()],
             push_constant_ranges: Vec::new(),
             shader: shader.clone(),
-            shader_defs: vec![],
+            shader_defs: vec![FluidFormats::output_def(formats.velocity)],
             entry_point: Cow::from("vorticity_main"),
         });
 
@@ -121,7 +110,7 @@ pub(crate) struct VorticityComputeNode;
 impl render_graph::Node for VorticityComputeNode {
     fn run(
         &self,
-        graph: &mut RenderGraphContext,
+        _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
@@ -140,7 +129,8 @@ impl render_graph::Node for VorticityComputeNode {
         if let Some(pipeline) = pipeline_cache.get_compute_pipeline(vorticity_pipeline.pipeline) {
             pass.set_pipeline(pipeline);
             pass.set_bind_group(0, &vorticity_bind_group.0, &[]);
-            pass.dispatch_workgroups(WIDTH / WORKGROUP_SIZE, HEIGHT / WORKGROUP_SIZE, 1);
+            let (workgroups_x, workgroups_y) = world.resource::<SimulationSize>().workgroups();
+            pass.dispatch_workgroups(workgroups_x, workgroups_y, 1);
         }
 
         Ok(())
@@ -154,6 +144,7 @@ fn prepare_bind_group(
     vorticity_pipeline: Res<VorticityPipeline>,
     time: Res<Time>,
     fluid_config: Res<FluidConfig>,
+    size: Res<SimulationSize>,
 ) {
     let velocity_tex_view = gpu_images.get(&vorticity_image.velocity_tex).unwrap();
     let curl_tex_view = gpu_images.get(&vorticity_image.curl_tex).unwrap();
@@ -178,7 +169,7 @@ fn prepare_bind_group(
     let curl_strength = fluid_config.curl_strength;
 
     let uniforms = VorticityUniforms {
-        texel_size: [1.0 / WIDTH as f32, 1.0 / HEIGHT as f32],
+        texel_size: size.texel_size(),
         curl_strength,
         dt,
     };